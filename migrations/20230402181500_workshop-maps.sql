@@ -0,0 +1,5 @@
+-- Add migration script here
+alter table maps
+    add display_name text;
+alter table maps
+    add workshop_id text;
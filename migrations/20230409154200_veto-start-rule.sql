@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TYPE veto_start_rule AS ENUM ('fixed', 'higher_seed_chooses', 'lower_seed_starts', 'coin_toss');
+
+alter table tournament
+    add veto_start_rule veto_start_rule default 'fixed' not null;
+
+alter table match_series
+    add coin_toss_winner integer REFERENCES teams (id);
+alter table match_series
+    add veto_start_team integer REFERENCES teams (id);
+alter table match_series
+    add decider_side_team integer REFERENCES teams (id);
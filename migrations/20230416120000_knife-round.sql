@@ -0,0 +1,11 @@
+-- Add migration script here
+CREATE TYPE side_selection AS ENUM ('pick', 'knife_decider', 'knife_all');
+
+alter table tournament
+    add side_selection side_selection default 'pick' not null;
+
+alter table match_series
+    add side_selection side_selection;
+
+alter table match
+    add knife_round boolean default false not null;
@@ -0,0 +1,25 @@
+-- Add migration script here
+alter table tournament
+    add registration_opens_at timestamptz;
+alter table tournament
+    add registration_closes_at timestamptz;
+alter table tournament
+    add check_in_opens_at timestamptz;
+alter table tournament
+    add check_in_finalized boolean default false not null;
+alter table tournament
+    add max_teams integer;
+alter table tournament
+    add min_roster integer default 5 not null;
+
+CREATE TYPE registration_status AS ENUM ('registered', 'waitlisted', 'dropped');
+CREATE TABLE tournament_registrations
+(
+    tournament    INTEGER             NOT NULL REFERENCES tournament (id),
+    team          INTEGER             NOT NULL REFERENCES teams (id),
+    status        registration_status NOT NULL,
+    registered_at TIMESTAMPTZ         NOT NULL DEFAULT now(),
+    checked_in_at TIMESTAMPTZ,
+    UNIQUE (tournament, team)
+);
+CREATE INDEX ON tournament_registrations (tournament);
@@ -0,0 +1,17 @@
+-- Add migration script here
+CREATE TABLE team_archive
+(
+    tournament  INTEGER     NOT NULL REFERENCES tournament (id),
+    team        INTEGER     NOT NULL REFERENCES teams (id),
+    name        TEXT        NOT NULL,
+    role        INT8        NOT NULL,
+    captain     INT8        NOT NULL,
+    members     INT8[]      NOT NULL,
+    series_won  INTEGER     NOT NULL,
+    series_lost INTEGER     NOT NULL,
+    maps_won    INTEGER     NOT NULL,
+    maps_lost   INTEGER     NOT NULL,
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (tournament, team)
+);
+CREATE INDEX ON team_archive (captain);
@@ -0,0 +1,15 @@
+CREATE TABLE tournament_teams
+(
+    tournament INTEGER     NOT NULL REFERENCES tournament (id),
+    team       INTEGER     NOT NULL REFERENCES teams (id),
+    joined_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (tournament, team)
+);
+CREATE INDEX ON tournament_teams (team);
+
+INSERT INTO tournament_teams (tournament, team)
+SELECT t.tournament, t.id
+FROM teams t
+         JOIN tournament tr ON tr.id = t.tournament
+WHERE t.is_active IS TRUE
+  AND tr.completed_at IS NULL;
@@ -0,0 +1,28 @@
+-- Rows without a guild predate multi-guild support and are claimed by the
+-- first guild the bot runs in
+ALTER TABLE teams
+    ADD guild INT8;
+ALTER TABLE maps
+    ADD guild INT8;
+ALTER TABLE tournament
+    ADD guild INT8;
+ALTER TABLE server_templates
+    ADD guild INT8;
+ALTER TABLE server_templates
+    DROP CONSTRAINT server_templates_pkey;
+CREATE UNIQUE INDEX ON server_templates (coalesce(guild, 0), location);
+CREATE INDEX ON teams (guild);
+CREATE INDEX ON maps (guild);
+CREATE INDEX ON tournament (guild);
+
+CREATE TABLE guild_config
+(
+    guild                  INT8 PRIMARY KEY,
+    dathost_user           TEXT,
+    dathost_password_env   TEXT,
+    match_end_webhook_url  TEXT,
+    round_end_webhook_url  TEXT,
+    series_end_webhook_url TEXT,
+    announcement_channel   INT8,
+    admin_role             INT8
+);
@@ -0,0 +1,16 @@
+CREATE TYPE bot_role AS ENUM ('organiser', 'referee', 'caster');
+CREATE TABLE guild_roles
+(
+    guild    INT8     NOT NULL,
+    role     INT8     NOT NULL,
+    bot_role bot_role NOT NULL,
+    UNIQUE (guild, role, bot_role)
+);
+CREATE INDEX ON guild_roles (guild);
+
+INSERT INTO guild_roles (guild, role, bot_role)
+SELECT guild, admin_role, 'organiser'
+FROM guild_config
+WHERE admin_role IS NOT NULL;
+ALTER TABLE guild_config
+    DROP COLUMN admin_role;
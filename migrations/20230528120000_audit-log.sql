@@ -0,0 +1,16 @@
+CREATE TABLE audit_log
+(
+    id         SERIAL PRIMARY KEY,
+    guild      INT8        NOT NULL,
+    actor      INT8        NOT NULL,
+    action     TEXT        NOT NULL,
+    target     TEXT        NOT NULL,
+    before     TEXT,
+    after      TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX ON audit_log (guild, created_at);
+CREATE INDEX ON audit_log (guild, actor);
+
+ALTER TABLE guild_config
+    ADD audit_channel INT8;
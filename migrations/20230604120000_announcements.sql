@@ -0,0 +1,13 @@
+ALTER TABLE match_series
+    ADD scheduled_at        TIMESTAMPTZ,
+    ADD result_announced_at TIMESTAMPTZ;
+CREATE INDEX ON match_series (scheduled_at);
+
+-- Series finished before announcements existed are not announced
+UPDATE match_series
+SET result_announced_at = completed_at
+WHERE completed_at IS NOT NULL;
+
+ALTER TABLE guild_config
+    ADD digest_hour      INT4 NOT NULL DEFAULT 9,
+    ADD digest_posted_at TIMESTAMPTZ;
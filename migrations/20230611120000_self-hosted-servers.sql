@@ -0,0 +1,25 @@
+CREATE TYPE match_plugin AS ENUM ('matchzy', 'get5');
+
+-- Game servers run by the league itself, matches are loaded over RCON
+CREATE TABLE rcon_servers
+(
+    id                SERIAL PRIMARY KEY,
+    guild             INT8         NOT NULL,
+    location          TEXT         NOT NULL,
+    hostname          TEXT         NOT NULL,
+    game_port         INT4         NOT NULL,
+    gotv_port         INT4         NOT NULL,
+    rcon_password_env TEXT         NOT NULL,
+    plugin            match_plugin NOT NULL DEFAULT 'matchzy',
+    UNIQUE (guild, hostname, game_port)
+);
+CREATE INDEX ON rcon_servers (guild, location);
+
+-- get5/MatchZy match configs fetched by self-hosted servers
+CREATE TABLE match_configs
+(
+    match_series INT4        PRIMARY KEY REFERENCES match_series (id) ON DELETE CASCADE,
+    token        TEXT        NOT NULL UNIQUE,
+    config       TEXT        NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
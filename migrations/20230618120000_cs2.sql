@@ -0,0 +1,8 @@
+CREATE TYPE game AS ENUM ('csgo', 'cs2');
+
+ALTER TABLE tournament
+    ADD game game NOT NULL DEFAULT 'csgo';
+
+-- Maps without a game are in the pool of both games
+ALTER TABLE maps
+    ADD game game;
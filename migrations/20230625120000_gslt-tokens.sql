@@ -0,0 +1,13 @@
+-- Game server login tokens of the bot's Steam account, reused across matches
+CREATE TABLE gslt_tokens
+(
+    id           SERIAL PRIMARY KEY,
+    steamid      TEXT        NOT NULL UNIQUE,
+    app_id       INT4        NOT NULL,
+    login_token  TEXT        NOT NULL,
+    server_id    TEXT,
+    match_series INT4        REFERENCES match_series (id) ON DELETE SET NULL,
+    last_used_at TIMESTAMPTZ,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX ON gslt_tokens (app_id, last_used_at);
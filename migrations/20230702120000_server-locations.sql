@@ -0,0 +1,31 @@
+CREATE TYPE server_selection AS ENUM ('pick', 'auto', 'auto_or_pick');
+
+ALTER TABLE tournament
+    ADD server_selection server_selection NOT NULL DEFAULT 'pick';
+
+-- Capacity is the number of servers a Dathost template may be duplicated into at once
+ALTER TABLE server_templates
+    ADD region   TEXT,
+    ADD capacity INT4;
+ALTER TABLE rcon_servers
+    ADD region TEXT;
+
+-- Location the series was played at, counts towards the capacity while the series is live
+ALTER TABLE match_series
+    ADD server_location TEXT;
+
+-- Expected ping from players of a region to a location
+CREATE TABLE server_pings
+(
+    guild    INT8 NOT NULL,
+    location TEXT NOT NULL,
+    region   TEXT NOT NULL,
+    ping     INT4 NOT NULL,
+    PRIMARY KEY (guild, location, region)
+);
+
+CREATE TABLE team_regions
+(
+    team   INT4 PRIMARY KEY REFERENCES teams (id) ON DELETE CASCADE,
+    region TEXT NOT NULL
+);
@@ -0,0 +1,4 @@
+-- Object key of the uploaded demo, demo buttons are only shown for stored demos
+ALTER TABLE match
+    ADD demo_key      TEXT,
+    ADD demo_attempts INT4 NOT NULL DEFAULT 0;
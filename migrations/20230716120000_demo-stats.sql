@@ -0,0 +1,44 @@
+ALTER TABLE match
+    ADD demo_parsed_at      TIMESTAMPTZ,
+    ADD demo_parse_attempts INT4 NOT NULL DEFAULT 0;
+
+CREATE TABLE demo_rounds
+(
+    match_id         INT4 NOT NULL REFERENCES match (id) ON DELETE CASCADE,
+    round            INT4 NOT NULL,
+    winner_side      TEXT NOT NULL,
+    reason           TEXT NOT NULL,
+    -- Player that won the round as the last one alive of their side
+    clutch_steamid   INT8,
+    clutch_opponents INT4,
+    PRIMARY KEY (match_id, round)
+);
+
+CREATE TABLE demo_kills
+(
+    id       SERIAL PRIMARY KEY,
+    match_id INT4    NOT NULL REFERENCES match (id) ON DELETE CASCADE,
+    round    INT4    NOT NULL,
+    tick     INT4    NOT NULL,
+    -- Not set for deaths without a killer, i.e. fall damage or the bomb
+    attacker INT8,
+    victim   INT8    NOT NULL,
+    weapon   TEXT    NOT NULL,
+    headshot BOOLEAN NOT NULL
+);
+CREATE INDEX ON demo_kills (match_id);
+
+CREATE TABLE demo_player_stats
+(
+    match_id  INT4 NOT NULL REFERENCES match (id) ON DELETE CASCADE,
+    steamid   INT8 NOT NULL,
+    kills     INT4 NOT NULL,
+    deaths    INT4 NOT NULL,
+    headshots INT4 NOT NULL,
+    -- Rounds with 3, 4 and 5 kills
+    triples   INT4 NOT NULL,
+    quads     INT4 NOT NULL,
+    aces      INT4 NOT NULL,
+    clutches  INT4 NOT NULL,
+    PRIMARY KEY (match_id, steamid)
+);
@@ -0,0 +1,13 @@
+CREATE TABLE match_rounds
+(
+    match_id       INT4        NOT NULL REFERENCES match (id) ON DELETE CASCADE,
+    round          INT4        NOT NULL,
+    winner_side    TEXT        NOT NULL,
+    reason         TEXT        NOT NULL,
+    team_one_won   BOOLEAN     NOT NULL,
+    -- Score after the round, team one is team1 of the match config
+    team_one_score INT4        NOT NULL,
+    team_two_score INT4        NOT NULL,
+    ended_at       TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (match_id, round)
+);
@@ -0,0 +1,12 @@
+-- Discord role changes of ended tournaments, kept until Discord accepted them
+CREATE TABLE role_cleanups
+(
+    id         SERIAL PRIMARY KEY,
+    guild      INT8        NOT NULL,
+    role       INT8        NOT NULL,
+    -- delete, rename or uncolour
+    action     TEXT        NOT NULL,
+    -- Role name after a rename
+    name       TEXT        NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,3 @@
+-- Discord & Steam ids of team members are only served by the API once a server opted in
+ALTER TABLE guild_config
+    ADD api_rosters BOOLEAN NOT NULL DEFAULT false;
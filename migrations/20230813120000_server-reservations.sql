@@ -0,0 +1,13 @@
+-- A location picked during setup is reserved for the series until its server runs
+ALTER TABLE match_series
+    ADD server_reserved_at TIMESTAMPTZ;
+
+-- Ping hints are looked up case-insensitively, keep one per location & region
+DELETE
+FROM server_pings a
+    USING server_pings b
+WHERE a.guild = b.guild
+  AND lower(a.location) = lower(b.location)
+  AND lower(a.region) = lower(b.region)
+  AND a.ctid < b.ctid;
+CREATE UNIQUE INDEX server_pings_lower_idx ON server_pings (guild, lower(location), lower(region));
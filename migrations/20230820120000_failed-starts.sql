@@ -0,0 +1,10 @@
+-- Server starts waiting for an admin to retry them, kept so they can be retried after a restart
+CREATE TABLE failed_starts
+(
+    match_series INT4        PRIMARY KEY REFERENCES match_series (id) ON DELETE CASCADE,
+    channel      INT8        NOT NULL,
+    message      INT8        NOT NULL,
+    failed_at    TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- Set once the retry button stopped working and admins were pointed to the command
+    expired      BOOLEAN     NOT NULL DEFAULT false
+);
@@ -0,0 +1,7 @@
+-- Random tokens the connect links of a series are served under
+CREATE TABLE connect_tokens
+(
+    match_series INT4        PRIMARY KEY REFERENCES match_series (id) ON DELETE CASCADE,
+    token        TEXT        NOT NULL UNIQUE,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,10 @@
+-- Series a self-hosted server was claimed for, claims are only taken with a conditional update
+ALTER TABLE rcon_servers
+    ADD claimed_by_series INT4 REFERENCES match_series (id) ON DELETE SET NULL;
+
+UPDATE rcon_servers r
+SET claimed_by_series = s.match_series
+FROM servers s
+         JOIN match_series ms ON ms.id = s.match_series
+WHERE s.server_id = 'rcon:' || r.id
+  AND ms.completed_at IS NULL;
@@ -0,0 +1,126 @@
+#!/usr/bin/env python3
+"""Prints the rounds & kills of a CS2 demo as the JSON the bot's `demo_parser` reads.
+
+Usage: python3 scripts/parse_demo.py <demo.dem>
+
+Needs demoparser2 (`pip install demoparser2`). Rounds that were played again after a
+backup was restored are only printed once, with the kills of their last play.
+"""
+import json
+import math
+import sys
+
+from demoparser2 import DemoParser
+
+# `reason` of the round_end event, the game's round end reasons counted from 1
+REASONS = {
+    1: "target_bombed",
+    7: "bomb_defused",
+    8: "t_eliminated",
+    9: "ct_eliminated",
+    10: "draw",
+    12: "target_saved",
+    17: "t_surrender",
+    18: "ct_surrender",
+}
+# names some demoparser2 versions give the reasons instead
+REASON_NAMES = {
+    "bomb_exploded": "target_bombed",
+    "t_killed": "t_eliminated",
+    "ct_killed": "ct_eliminated",
+    "time_ran_out": "target_saved",
+}
+T_TEAM = 2
+CT_TEAM = 3
+
+
+def missing(value):
+    return value is None or (isinstance(value, float) and math.isnan(value))
+
+
+def side(team):
+    if str(team).upper() in ("2", "T", "TERRORIST"):
+        return "t"
+    if str(team).upper() in ("3", "CT"):
+        return "ct"
+    raise ValueError(f"unknown team {team!r}")
+
+
+def reason(value):
+    if isinstance(value, str) and not value.isdigit():
+        return REASON_NAMES.get(value.lower(), value.lower())
+    value = int(value)
+    return REASONS.get(value, f"reason_{value - 1}")
+
+
+def steamid(value):
+    return None if missing(value) or int(value) == 0 else int(value)
+
+
+def parse(path):
+    parser = DemoParser(path)
+    round_ends = parser.parse_event("round_end")
+    end_ticks = sorted(int(t) for t in round_ends["tick"])
+    if not end_ticks:
+        return {"rounds": [], "kills": []}
+    # the state just before the round ended: who played on which side & which round it was
+    states = parser.parse_ticks(
+        ["team_num", "total_rounds_played", "is_warmup_period"],
+        ticks=[t - 1 for t in end_ticks],
+    )
+
+    rounds = {}
+    for end in round_ends.itertuples():
+        tick = int(end.tick)
+        players = states[states["tick"] == tick - 1]
+        if players.empty or bool(players["is_warmup_period"].iloc[0]):
+            continue
+        number = int(players["total_rounds_played"].iloc[0]) + 1
+        previous_ends = [t for t in end_ticks if t < tick]
+        rounds[number] = {
+            "number": number,
+            "winner": side(end.winner),
+            "reason": reason(end.reason),
+            "ct_players": [int(p.steamid) for p in players.itertuples() if p.team_num == CT_TEAM],
+            "t_players": [int(p.steamid) for p in players.itertuples() if p.team_num == T_TEAM],
+            # ticks of the last play of the round
+            "start_tick": previous_ends[-1] if previous_ends else 0,
+            "end_tick": tick,
+        }
+
+    kills = []
+    deaths = parser.parse_event("player_death", other=["total_rounds_played"])
+    for death in deaths.itertuples():
+        number = int(death.total_rounds_played) + 1
+        tick = int(death.tick)
+        played = rounds.get(number)
+        if played is None or not played["start_tick"] < tick <= played["end_tick"]:
+            continue
+        victim = steamid(death.user_steamid)
+        if victim is None:
+            continue
+        kills.append(
+            {
+                "round": number,
+                "tick": tick,
+                "attacker": steamid(death.attacker_steamid),
+                "victim": victim,
+                "weapon": str(death.weapon),
+                "headshot": bool(death.headshot),
+            }
+        )
+
+    for played in rounds.values():
+        del played["start_tick"], played["end_tick"]
+    return {"rounds": sorted(rounds.values(), key=lambda r: r["number"]), "kills": kills}
+
+
+def main():
+    if len(sys.argv) != 2:
+        print(__doc__.strip(), file=sys.stderr)
+        sys.exit(2)
+    json.dump(parse(sys.argv[1]), sys.stdout)
+
+
+if __name__ == "__main__":
+    main()
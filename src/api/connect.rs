@@ -0,0 +1,127 @@
+use crate::api::ApiResult;
+use crate::connect::ConnectTarget;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Redirect};
+use sqlx::PgPool;
+
+/// Redirects to `steam://connect` for the server of a series in progress,
+/// the target of the connect button
+#[utoipa::path(
+    get,
+    path = "/connect/{token}",
+    params(("token" = String, Path, description = "Connect token of the series")),
+    responses(
+        (status = 307, description = "Redirect to `steam://connect/<host>:<port>`"),
+        (status = 404, description = "Unknown token, no server for the series or the series is completed")
+    )
+)]
+pub async fn connect(State(pool): State<PgPool>, Path(token): Path<String>) -> ApiResult {
+    redirect(&pool, &token, ConnectTarget::Game).await
+}
+
+/// Redirects to `steam://connect` for GOTV of a series in progress,
+/// the target of the GOTV button
+#[utoipa::path(
+    get,
+    path = "/connect/{token}/gotv",
+    params(("token" = String, Path, description = "Connect token of the series")),
+    responses(
+        (status = 307, description = "Redirect to `steam://connect/<host>:<gotv port>`"),
+        (status = 404, description = "Unknown token, no server for the series or the series is completed")
+    )
+)]
+pub async fn connect_gotv(State(pool): State<PgPool>, Path(token): Path<String>) -> ApiResult {
+    redirect(&pool, &token, ConnectTarget::Gotv).await
+}
+
+/// The token is random so the servers of other series can't be found by counting up ids
+async fn redirect(pool: &PgPool, token: &str, target: ConnectTarget) -> ApiResult {
+    let server = sqlx::query!(
+        "select s.hostname, s.game_port, s.gotv_port
+             from servers s
+                join match_series ms on ms.id = s.match_series
+                join connect_tokens ct on ct.match_series = ms.id
+             where ct.token = $1 and ms.completed_at is null
+             order by s.id desc
+             limit 1",
+        token
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(server) = server else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let port = match target {
+        ConnectTarget::Game => server.game_port,
+        ConnectTarget::Gotv => server.gotv_port,
+    };
+    let url = format!("steam://connect/{}:{}", server.hostname, port);
+    Ok(Redirect::temporary(&url).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connect::connect_token;
+    use crate::connect::tests::create_series;
+    use axum::http::header;
+
+    async fn add_server(pool: &PgPool, series: i32) {
+        sqlx::query(
+            "insert into servers (match_series, server_id, hostname, game_port, gotv_port)
+                 values ($1, 'dathost-id', '1.2.3.4', 27015, 27020)",
+        )
+        .bind(series)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn location(pool: &PgPool, token: &str, target: ConnectTarget) -> Option<String> {
+        let response = redirect(pool, token, target).await.ok().unwrap();
+        if response.status() == StatusCode::NOT_FOUND {
+            return None;
+        }
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        Some(
+            response.headers()[header::LOCATION]
+                .to_str()
+                .unwrap()
+                .to_string(),
+        )
+    }
+
+    #[sqlx::test]
+    async fn redirects_by_token(pool: PgPool) {
+        let series = create_series(&pool).await;
+        add_server(&pool, series).await;
+        let token = connect_token(&pool, series).await.unwrap();
+        assert_eq!(connect_token(&pool, series).await.unwrap(), token);
+        assert_eq!(
+            location(&pool, &token, ConnectTarget::Game).await.unwrap(),
+            "steam://connect/1.2.3.4:27015"
+        );
+        assert_eq!(
+            location(&pool, &token, ConnectTarget::Gotv).await.unwrap(),
+            "steam://connect/1.2.3.4:27020"
+        );
+        assert_eq!(
+            location(&pool, &series.to_string(), ConnectTarget::Game).await,
+            None
+        );
+    }
+
+    #[sqlx::test]
+    async fn completed_series_are_not_found(pool: PgPool) {
+        let series = create_series(&pool).await;
+        add_server(&pool, series).await;
+        let token = connect_token(&pool, series).await.unwrap();
+        sqlx::query("update match_series set completed_at = now() where id = $1")
+            .bind(series)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(location(&pool, &token, ConnectTarget::Game).await, None);
+    }
+}
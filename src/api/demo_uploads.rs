@@ -0,0 +1,68 @@
+use crate::api::{ApiResult, ApiState};
+use crate::commands::demos::{upload_demo, StoredDemo};
+use crate::commands::self_hosted::MatchConfig;
+use axum::extract::{BodyStream, Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use futures::TryStreamExt;
+
+/// Headers get5 & MatchZy send the map number of an uploaded demo in, counted from 0
+const MAP_NUMBER_HEADERS: [&str; 2] = ["Get5-MapNumber", "MatchZy-MapNumber"];
+
+/// Demo upload of `get5_demo_upload_url`/`matchzy_demo_upload_url`, streamed to the demo
+/// storage. The token of the match config is the only access check
+#[utoipa::path(
+    post,
+    path = "/api/demo-uploads/{token}",
+    params(("token" = String, Path, description = "Token of the match config")),
+    request_body(content = Vec<u8>, description = "Demo file", content_type = "application/octet-stream"),
+    responses(
+        (status = 204, description = "Demo stored"),
+        (status = 400, description = "Missing or invalid map number header"),
+        (status = 404, description = "No such config or map, or no demo storage"),
+        (status = 411, description = "Missing `Content-Length`")
+    )
+)]
+pub async fn demo_upload(
+    State(state): State<ApiState>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+    body: BodyStream,
+) -> ApiResult {
+    let Some(storage) = state.storage else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let Some(series) = MatchConfig::get_series_by_token(&state.pool, &token).await? else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let map_number = MAP_NUMBER_HEADERS
+        .iter()
+        .find_map(|name| headers.get(*name))
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i64>().ok());
+    let Some(map_number) = map_number.filter(|n| *n >= 0) else {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    };
+    let length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let Some(length) = length else {
+        return Ok(StatusCode::LENGTH_REQUIRED.into_response());
+    };
+    let match_id = sqlx::query_scalar!(
+        "select id from match where match_series = $1 order by id offset $2 limit 1",
+        series,
+        map_number
+    )
+    .fetch_optional(&state.pool)
+    .await?;
+    let Some(match_id) = match_id else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let body = body.map_err(anyhow::Error::from);
+    let key = upload_demo(storage.as_ref(), series, match_id, length, Box::pin(body)).await?;
+    StoredDemo::set_key(&state.pool, match_id, &key).await?;
+    log::info!("Stored uploaded demo of match {} as {}", match_id, key);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
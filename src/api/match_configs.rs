@@ -0,0 +1,24 @@
+use crate::api::ApiResult;
+use crate::commands::self_hosted::MatchConfig;
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use sqlx::PgPool;
+
+/// get5/MatchZy match config for `matchzy_loadmatch_url`/`get5_loadmatch_url`,
+/// the random token in the path is the only access check
+#[utoipa::path(
+    get,
+    path = "/api/match-configs/{token}",
+    params(("token" = String, Path, description = "Token sent to the game server")),
+    responses(
+        (status = 200, description = "Match config in the get5 format"),
+        (status = 404, description = "No such config")
+    )
+)]
+pub async fn match_config(State(pool): State<PgPool>, Path(token): Path<String>) -> ApiResult {
+    match MatchConfig::get_by_token(&pool, &token).await? {
+        Some(config) => Ok(([(header::CONTENT_TYPE, "application/json")], config).into_response()),
+        None => Ok(StatusCode::NOT_FOUND.into_response()),
+    }
+}
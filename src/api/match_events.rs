@@ -0,0 +1,312 @@
+use crate::api::ApiResult;
+use crate::commands::admin::set_map_score;
+use crate::commands::self_hosted::MatchConfig;
+use crate::commands::timeline::MatchRound;
+use crate::commands::tournament::Game;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::PgPool;
+
+#[derive(Debug, Deserialize)]
+struct EventWinner {
+    side: String,
+    /// `team1` or `team2`
+    team: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventTeam {
+    score: i32,
+}
+
+/// `round_end` event of get5 & MatchZy
+#[derive(Debug, Deserialize)]
+struct RoundEndEvent {
+    /// Counted from 0
+    map_number: i64,
+    /// Counted from 0 by get5 and from 1 by MatchZy
+    round_number: i32,
+    reason: i32,
+    winner: EventWinner,
+    team1: EventTeam,
+    team2: EventTeam,
+}
+
+/// `map_result` event of get5 & MatchZy
+#[derive(Debug, Deserialize)]
+struct MapResultEvent {
+    /// Counted from 0
+    map_number: i64,
+    team1: EventTeam,
+    team2: EventTeam,
+}
+
+/// Match events of the get5/MatchZy remote log url, `round_end`, `map_result` & `series_end`
+/// events are recorded. The token of the match config is the only access check
+#[utoipa::path(
+    post,
+    path = "/api/match-events/{token}",
+    params(("token" = String, Path, description = "Token of the match config")),
+    responses(
+        (status = 204, description = "Event recorded or ignored"),
+        (status = 400, description = "Malformed event"),
+        (status = 404, description = "No such config or map")
+    )
+)]
+pub async fn match_event(
+    State(pool): State<PgPool>,
+    Path(token): Path<String>,
+    Json(event): Json<Value>,
+) -> ApiResult {
+    let Some(series) = MatchConfig::get_series_by_token(&pool, &token).await? else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    match event.get("event").and_then(Value::as_str) {
+        Some("round_end") => round_end(&pool, series, event).await,
+        Some("map_result") => map_result(&pool, series, event).await,
+        Some("series_end") => series_end(&pool, series).await,
+        _ => Ok(StatusCode::NO_CONTENT.into_response()),
+    }
+}
+
+async fn round_end(pool: &PgPool, series: i32, event: Value) -> ApiResult {
+    let Ok(event) = serde_json::from_value::<RoundEndEvent>(event) else {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    };
+    // a negative offset is a query error
+    if event.map_number < 0 {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+    let match_id = sqlx::query_scalar!(
+        "select id from match where match_series = $1 order by id offset $2 limit 1",
+        series,
+        event.map_number
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(match_id) = match_id else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let plugin = Game::get_by_series(pool, series).await?.match_plugin();
+    let round = MatchRound {
+        // the score does not count drawn rounds
+        round: plugin.round_number(event.round_number),
+        winner_side: event.winner.side.to_lowercase(),
+        reason: plugin.round_end_reason(event.reason),
+        team_one_won: event.winner.team == "team1",
+        team_one_score: event.team1.score,
+        team_two_score: event.team2.score,
+        ended_at: Some(OffsetDateTime::now_utc()),
+    };
+    MatchRound::add(pool, match_id, &round).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Final score of a map, like the score `/admin matches score` enters
+async fn map_result(pool: &PgPool, series: i32, event: Value) -> ApiResult {
+    let Ok(event) = serde_json::from_value::<MapResultEvent>(event) else {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    };
+    if event.map_number < 0 {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+    let mut transaction = pool.begin().await?;
+    let recorded = set_map_score(
+        &mut transaction,
+        series,
+        event.map_number + 1,
+        event.team1.score,
+        event.team2.score,
+    )
+    .await?;
+    if !recorded {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    transaction.commit().await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Completes the series and frees its self-hosted server for the next one
+async fn series_end(pool: &PgPool, series: i32) -> ApiResult {
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        "update match_series set completed_at = coalesce(completed_at, now()) where id = $1",
+        series
+    )
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query!(
+        "update rcon_servers set claimed_by_series = null where claimed_by_series = $1",
+        series
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::self_hosted::{MatchPlugin, RconServer};
+    use crate::connect::tests::create_series;
+    use serde_json::json;
+
+    /// Series with one map and a match config of token `token-<series>`
+    async fn configured_series(pool: &PgPool) -> i32 {
+        let series = create_series(pool).await;
+        sqlx::query(
+            "insert into match (match_series, map, picked_by, start_ct_team, start_t_team)
+                 select ms.id, (select id from maps limit 1), ms.team_one, ms.team_one, ms.team_two
+                   from match_series ms
+                   where ms.id = $1",
+        )
+        .bind(series)
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "insert into match_configs (match_series, token, config) values ($1, $2, '{}')",
+        )
+        .bind(series)
+        .bind(format!("token-{}", series))
+        .execute(pool)
+        .await
+        .unwrap();
+        series
+    }
+
+    async fn post(pool: &PgPool, series: i32, event: Value) -> StatusCode {
+        let response = match_event(
+            State(pool.clone()),
+            Path(format!("token-{}", series)),
+            Json(event),
+        )
+        .await;
+        response.ok().unwrap().status()
+    }
+
+    async fn post_round_end(pool: &PgPool, map_number: i64) -> StatusCode {
+        let series = configured_series(pool).await;
+        post(pool, series, round_end_event(map_number, 0, 7, "team1", 1)).await
+    }
+
+    fn round_end_event(
+        map_number: i64,
+        round_number: i32,
+        reason: i32,
+        winner: &str,
+        team1_score: i32,
+    ) -> Value {
+        json!({
+            "event": "round_end",
+            "map_number": map_number,
+            // get5 numbering & reasons, the game of the default tournament is CS:GO
+            "round_number": round_number,
+            "reason": reason,
+            "winner": {"side": "CT", "team": winner},
+            "team1": {"score": team1_score},
+            "team2": {"score": 0},
+        })
+    }
+
+    #[sqlx::test]
+    async fn records_round_ends(pool: PgPool) {
+        assert_eq!(post_round_end(&pool, 0).await, StatusCode::NO_CONTENT);
+        let rounds: Vec<(i32, String, bool)> =
+            sqlx::query_as("select round, reason, team_one_won from match_rounds")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(rounds, vec![(1, String::from("t_eliminated"), true)]);
+    }
+
+    #[sqlx::test]
+    async fn records_rounds_after_a_draw(pool: PgPool) {
+        let series = configured_series(&pool).await;
+        for event in [
+            round_end_event(0, 0, 7, "team1", 1),
+            round_end_event(0, 1, 9, "none", 1),
+            round_end_event(0, 2, 7, "team1", 2),
+        ] {
+            assert_eq!(post(&pool, series, event).await, StatusCode::NO_CONTENT);
+        }
+        let rounds: Vec<(i32, String, i32)> =
+            sqlx::query_as("select round, reason, team_one_score from match_rounds order by round")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            rounds,
+            vec![
+                (1, String::from("t_eliminated"), 1),
+                (2, String::from("draw"), 1),
+                (3, String::from("t_eliminated"), 2),
+            ]
+        );
+    }
+
+    #[sqlx::test]
+    async fn rejects_unknown_and_negative_maps(pool: PgPool) {
+        assert_eq!(post_round_end(&pool, 1).await, StatusCode::NOT_FOUND);
+        assert_eq!(post_round_end(&pool, -1).await, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test]
+    async fn frees_the_server_after_series_end(pool: PgPool) {
+        sqlx::query(
+            "insert into rcon_servers (guild, location, hostname, game_port, gotv_port, rcon_password_env, plugin)
+                 values (1, 'eu', 'localhost', 27015, 27020, 'RCON_PASSWORD_EU', 'get5')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let first = configured_series(&pool).await;
+        let next = configured_series(&pool).await;
+        assert!(RconServer::claim(&pool, 1, "eu", MatchPlugin::Get5, first)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(RconServer::claim(&pool, 1, "eu", MatchPlugin::Get5, next)
+            .await
+            .unwrap()
+            .is_none());
+
+        let map_result = json!({
+            "event": "map_result",
+            "map_number": 0,
+            "winner": {"side": "CT", "team": "team1"},
+            "team1": {"score": 13},
+            "team2": {"score": 7},
+        });
+        assert_eq!(post(&pool, first, map_result).await, StatusCode::NO_CONTENT);
+        let series_end = json!({
+            "event": "series_end",
+            "team1_series_score": 1,
+            "team2_series_score": 0,
+            "winner": {"side": "CT", "team": "team1"},
+        });
+        assert_eq!(post(&pool, first, series_end).await, StatusCode::NO_CONTENT);
+
+        let scores: Vec<(i32, i32)> = sqlx::query_as(
+            "select sc.team_one_score, sc.team_two_score
+                 from match_scores sc
+                    join match m on m.id = sc.match_id
+                 where m.match_series = $1",
+        )
+        .bind(first)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(scores, vec![(13, 7)]);
+        assert!(RconServer::claim(&pool, 1, "eu", MatchPlugin::Get5, next)
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
@@ -0,0 +1,237 @@
+use crate::api::{json_with_etag, ApiResult};
+use anyhow::Result;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
+use sqlx::PgPool;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiMap {
+    pub map: String,
+    pub picked_by: i32,
+    pub start_ct_team: i32,
+    pub knife_round: bool,
+    pub team_one_score: i32,
+    pub team_two_score: i32,
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub completed_at: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiVote {
+    pub map: String,
+    /// `pick` or `veto`
+    pub vote_type: String,
+    pub team: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiSeries {
+    pub id: i32,
+    pub tournament: i32,
+    /// `bo1`, `bo3` or `bo5`
+    pub series_type: String,
+    pub team_one: i32,
+    pub team_two: i32,
+    /// Rounds won for Bo1, maps won otherwise
+    pub team_one_score: i32,
+    pub team_two_score: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub scheduled_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub completed_at: Option<OffsetDateTime>,
+    pub maps: Vec<ApiMap>,
+    pub vetoes: Vec<ApiVote>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct MatchFilter {
+    /// Only series of this tournament
+    tournament: Option<i32>,
+    /// Only completed (`true`) or unfinished (`false`) series
+    completed: Option<bool>,
+    /// Number of series, newest first (default 50, max 200)
+    limit: Option<i64>,
+}
+
+/// Series of `guild` with their maps & vetoes, newest first
+async fn get_series(
+    pool: &PgPool,
+    guild: i64,
+    series: Option<i32>,
+    filter: MatchFilter,
+) -> Result<Vec<ApiSeries>> {
+    let rows = sqlx::query!(
+        r#"select ms.id,
+                  ms.tournament,
+                  ms.series_type::text as "series_type!",
+                  ms.team_one,
+                  ms.team_two,
+                  ms.created_at,
+                  ms.scheduled_at,
+                  ms.completed_at
+             from match_series ms
+                join teams t on t.id = ms.team_one
+             where t.guild = $1
+               and ($2::int4 is null or ms.id = $2)
+               and ($3::int4 is null or ms.tournament = $3)
+               and ($4::bool is null or (ms.completed_at is not null) = $4)
+             order by ms.id desc
+             limit $5"#,
+        guild,
+        series,
+        filter.tournament,
+        filter.completed,
+        filter.limit.unwrap_or(50).clamp(1, 200),
+    )
+    .fetch_all(pool)
+    .await?;
+    let ids: Vec<i32> = rows.iter().map(|r| r.id).collect();
+    let maps = sqlx::query!(
+        r#"select m.match_series,
+                  mp.name as map,
+                  m.picked_by,
+                  m.start_ct_team,
+                  m.knife_round,
+                  m.completed_at,
+                  coalesce(sc.team_one_score, 0) as "team_one_score!",
+                  coalesce(sc.team_two_score, 0) as "team_two_score!"
+             from match m
+                join maps mp on mp.id = m.map
+                left join match_scores sc on sc.match_id = m.id
+             where m.match_series = any($1)
+             order by m.id"#,
+        &ids
+    )
+    .fetch_all(pool)
+    .await?;
+    let vetoes = sqlx::query!(
+        r#"select v.match_series, mp.name as map, v.type::text as "vote_type!", v.team
+             from vote_info v
+                join maps mp on mp.id = v.map
+             where v.match_series = any($1)
+             order by v.id"#,
+        &ids
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let maps: Vec<ApiMap> = maps
+                .iter()
+                .filter(|m| m.match_series == r.id)
+                .map(|m| ApiMap {
+                    map: m.map.clone(),
+                    picked_by: m.picked_by,
+                    start_ct_team: m.start_ct_team,
+                    knife_round: m.knife_round,
+                    team_one_score: m.team_one_score,
+                    team_two_score: m.team_two_score,
+                    completed_at: m.completed_at,
+                })
+                .collect();
+            let (team_one_score, team_two_score) = series_score(&r.series_type, &maps);
+            ApiSeries {
+                id: r.id,
+                tournament: r.tournament,
+                series_type: r.series_type,
+                team_one: r.team_one,
+                team_two: r.team_two,
+                team_one_score,
+                team_two_score,
+                created_at: r.created_at,
+                scheduled_at: r.scheduled_at,
+                completed_at: r.completed_at,
+                maps,
+                vetoes: vetoes
+                    .iter()
+                    .filter(|v| v.match_series == r.id)
+                    .map(|v| ApiVote {
+                        map: v.map.clone(),
+                        vote_type: v.vote_type.clone(),
+                        team: v.team,
+                    })
+                    .collect(),
+            }
+        })
+        .collect())
+}
+
+/// Same scoring as `/matches info`: rounds for Bo1, maps won otherwise
+fn series_score(series_type: &str, maps: &[ApiMap]) -> (i32, i32) {
+    if series_type == "bo1" {
+        return maps
+            .first()
+            .map(|m| (m.team_one_score, m.team_two_score))
+            .unwrap_or_default();
+    }
+    maps.iter().fold((0, 0), |(one, two), m| {
+        if m.team_one_score > m.team_two_score {
+            (one + 1, two)
+        } else if m.team_two_score > m.team_one_score {
+            (one, two + 1)
+        } else {
+            (one, two)
+        }
+    })
+}
+
+/// Match series with per-map scores & vetoes
+#[utoipa::path(
+    get,
+    path = "/api/guilds/{guild}/matches",
+    params(("guild" = i64, Path, description = "Discord server id"), MatchFilter),
+    responses(
+        (status = 200, description = "Series, newest first", body = [ApiSeries]),
+        (status = 304, description = "Not modified")
+    )
+)]
+pub async fn matches(
+    State(pool): State<PgPool>,
+    Path(guild): Path<i64>,
+    Query(filter): Query<MatchFilter>,
+    headers: HeaderMap,
+) -> ApiResult {
+    let series = get_series(&pool, guild, None, filter).await?;
+    json_with_etag(&headers, &series)
+}
+
+/// A single match series
+#[utoipa::path(
+    get,
+    path = "/api/guilds/{guild}/matches/{series}",
+    params(
+        ("guild" = i64, Path, description = "Discord server id"),
+        ("series" = i32, Path, description = "Series id")
+    ),
+    responses(
+        (status = 200, description = "Series", body = ApiSeries),
+        (status = 304, description = "Not modified"),
+        (status = 404, description = "No such series")
+    )
+)]
+pub async fn series(
+    State(pool): State<PgPool>,
+    Path((guild, series)): Path<(i64, i32)>,
+    headers: HeaderMap,
+) -> ApiResult {
+    let filter = MatchFilter {
+        tournament: None,
+        completed: None,
+        limit: Some(1),
+    };
+    match get_series(&pool, guild, Some(series), filter).await?.pop() {
+        Some(series) => json_with_etag(&headers, &series),
+        None => Ok(StatusCode::NOT_FOUND.into_response()),
+    }
+}
@@ -0,0 +1,156 @@
+mod connect;
+mod demo_uploads;
+mod match_configs;
+mod match_events;
+mod matches;
+mod servers;
+mod teams;
+mod tournaments;
+
+use crate::storage::DemoStorage;
+use axum::extract::FromRef;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use utoipa::OpenApi;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "csgo-matchbot",
+        description = "Read-only access to the teams, matches & tournaments of a Discord server"
+    ),
+    paths(
+        teams::teams,
+        matches::matches,
+        matches::series,
+        match_configs::match_config,
+        match_events::match_event,
+        demo_uploads::demo_upload,
+        connect::connect,
+        connect::connect_gotv,
+        tournaments::tournaments,
+        tournaments::standings,
+        servers::servers
+    ),
+    components(schemas(
+        teams::ApiTeam,
+        teams::ApiTeamMember,
+        matches::ApiSeries,
+        matches::ApiMap,
+        matches::ApiVote,
+        tournaments::ApiTournament,
+        tournaments::ApiStanding,
+        servers::ApiServer
+    ))
+)]
+pub struct ApiDoc;
+
+/// State of the handlers, most only need the pool
+#[derive(Clone)]
+pub struct ApiState {
+    pool: PgPool,
+    /// Demos uploaded by self-hosted servers are only accepted when set
+    storage: Option<Arc<dyn DemoStorage>>,
+}
+
+impl FromRef<ApiState> for PgPool {
+    fn from_ref(state: &ApiState) -> PgPool {
+        state.pool.clone()
+    }
+}
+
+/// Errors are logged and answered with a bare `500 Internal Server Error`
+pub struct ApiError(anyhow::Error);
+
+impl<E: Into<anyhow::Error>> From<E> for ApiError {
+    fn from(err: E) -> Self {
+        ApiError(err.into())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        log::error!("{:#?}", self.0);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    }
+}
+
+pub type ApiResult = Result<Response, ApiError>;
+
+/// Serializes `value` with an `ETag` of the body, answers `304 Not Modified`
+/// when the client already has the same body
+pub fn json_with_etag<T: Serialize>(headers: &HeaderMap, value: &T) -> ApiResult {
+    let body = serde_json::to_vec(value)?;
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    let etag = format!("\"{:016x}\"", hasher.finish());
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+            v.split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        })
+        .unwrap_or(false);
+    let etag = HeaderValue::from_str(&etag)?;
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            ),
+            (header::ETAG, etag),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Serves the API until the process exits, started when `API_ADDRESS` is set
+pub async fn serve(pool: PgPool, storage: Option<Arc<dyn DemoStorage>>, address: SocketAddr) {
+    let router = Router::new()
+        .route("/api/openapi.json", get(openapi))
+        .route("/api/guilds/:guild/teams", get(teams::teams))
+        .route("/api/guilds/:guild/matches", get(matches::matches))
+        .route("/api/guilds/:guild/matches/:series", get(matches::series))
+        .route(
+            "/api/guilds/:guild/tournaments",
+            get(tournaments::tournaments),
+        )
+        .route(
+            "/api/guilds/:guild/tournaments/:tournament/standings",
+            get(tournaments::standings),
+        )
+        .route("/api/guilds/:guild/servers", get(servers::servers))
+        .route(
+            "/api/match-configs/:token",
+            get(match_configs::match_config),
+        )
+        .route("/api/match-events/:token", post(match_events::match_event))
+        .route("/api/demo-uploads/:token", post(demo_uploads::demo_upload))
+        .route("/connect/:token", get(connect::connect))
+        .route("/connect/:token/gotv", get(connect::connect_gotv))
+        .with_state(ApiState { pool, storage });
+    log::info!("Serving API on {}", address);
+    if let Err(err) = axum::Server::bind(&address)
+        .serve(router.into_make_service())
+        .await
+    {
+        log::error!("API error: {}", err);
+    }
+}
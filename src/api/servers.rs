@@ -0,0 +1,52 @@
+use crate::api::{json_with_etag, ApiResult};
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use serde::Serialize;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiServer {
+    pub match_series: i32,
+    pub team_one: i32,
+    pub team_two: i32,
+    /// GOTV address, i.e. `connect <gotv>` in the game console
+    pub gotv: String,
+}
+
+/// Servers of series in progress with their GOTV address
+#[utoipa::path(
+    get,
+    path = "/api/guilds/{guild}/servers",
+    params(("guild" = i64, Path, description = "Discord server id")),
+    responses(
+        (status = 200, description = "Servers in use", body = [ApiServer]),
+        (status = 304, description = "Not modified")
+    )
+)]
+pub async fn servers(
+    State(pool): State<PgPool>,
+    Path(guild): Path<i64>,
+    headers: HeaderMap,
+) -> ApiResult {
+    let servers: Vec<ApiServer> = sqlx::query!(
+        "select s.match_series, ms.team_one, ms.team_two, s.hostname, s.gotv_port
+             from servers s
+                join match_series ms on ms.id = s.match_series
+                join teams t on t.id = ms.team_one
+             where t.guild = $1 and ms.completed_at is null
+             order by s.id",
+        guild
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|s| ApiServer {
+        match_series: s.match_series,
+        team_one: s.team_one,
+        team_two: s.team_two,
+        gotv: format!("{}:{}", s.hostname, s.gotv_port),
+    })
+    .collect();
+    json_with_etag(&headers, &servers)
+}
@@ -0,0 +1,95 @@
+use crate::api::{json_with_etag, ApiResult};
+use crate::commands::config::GuildConfig;
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use serde::Serialize;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+/// Discord & Steam ids are strings, they do not fit into a JSON number
+#[derive(Serialize, ToSchema)]
+pub struct ApiTeamMember {
+    pub discord: String,
+    pub steam: Option<String>,
+}
+
+/// `captain` & `members` are only set when the server made its rosters public with
+/// `/config api rosters:true`
+#[derive(Serialize, ToSchema)]
+pub struct ApiTeam {
+    pub id: i32,
+    pub name: String,
+    pub role: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub captain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub members: Option<Vec<ApiTeamMember>>,
+}
+
+/// Active teams, with their rosters if the server made them public
+#[utoipa::path(
+    get,
+    path = "/api/guilds/{guild}/teams",
+    params(("guild" = i64, Path, description = "Discord server id")),
+    responses(
+        (status = 200, description = "Active teams", body = [ApiTeam]),
+        (status = 304, description = "Not modified")
+    )
+)]
+pub async fn teams(
+    State(pool): State<PgPool>,
+    Path(guild): Path<i64>,
+    headers: HeaderMap,
+) -> ApiResult {
+    let teams = sqlx::query!(
+        "select id, name, role, captain from teams
+             where guild = $1 and is_active is true
+             order by name",
+        guild
+    )
+    .fetch_all(&pool)
+    .await?;
+    if !GuildConfig::get(&pool, guild).await?.api_rosters {
+        let teams: Vec<ApiTeam> = teams
+            .into_iter()
+            .map(|t| ApiTeam {
+                id: t.id,
+                name: t.name,
+                role: t.role.to_string(),
+                captain: None,
+                members: None,
+            })
+            .collect();
+        return json_with_etag(&headers, &teams);
+    }
+    let members = sqlx::query!(
+        r#"select tm.team, tm.member, si.steam as "steam?"
+             from team_members tm
+                join teams t on t.id = tm.team
+                left join steam_ids si on si.discord = tm.member
+             where t.guild = $1 and t.is_active is true"#,
+        guild
+    )
+    .fetch_all(&pool)
+    .await?;
+    let teams: Vec<ApiTeam> = teams
+        .into_iter()
+        .map(|t| ApiTeam {
+            members: Some(
+                members
+                    .iter()
+                    .filter(|m| m.team == t.id)
+                    .map(|m| ApiTeamMember {
+                        discord: m.member.to_string(),
+                        steam: m.steam.map(|s| s.to_string()),
+                    })
+                    .collect(),
+            ),
+            id: t.id,
+            name: t.name,
+            role: t.role.to_string(),
+            captain: Some(t.captain.to_string()),
+        })
+        .collect();
+    json_with_etag(&headers, &teams)
+}
@@ -0,0 +1,102 @@
+use crate::api::{json_with_etag, ApiResult};
+use crate::commands::tournament::Standing;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use serde::Serialize;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiTournament {
+    pub id: i32,
+    pub name: String,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime)]
+    pub started_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub completed_at: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiStanding {
+    pub team: i32,
+    pub role: String,
+    pub series_won: i64,
+    pub series_lost: i64,
+    pub maps_won: i64,
+    pub maps_lost: i64,
+}
+
+/// All tournaments, newest first
+#[utoipa::path(
+    get,
+    path = "/api/guilds/{guild}/tournaments",
+    params(("guild" = i64, Path, description = "Discord server id")),
+    responses(
+        (status = 200, description = "Tournaments", body = [ApiTournament]),
+        (status = 304, description = "Not modified")
+    )
+)]
+pub async fn tournaments(
+    State(pool): State<PgPool>,
+    Path(guild): Path<i64>,
+    headers: HeaderMap,
+) -> ApiResult {
+    let tournaments = sqlx::query_as!(
+        ApiTournament,
+        "select id, name, started_at, completed_at from tournament
+             where guild = $1
+             order by started_at desc, id desc",
+        guild
+    )
+    .fetch_all(&pool)
+    .await?;
+    json_with_etag(&headers, &tournaments)
+}
+
+/// Series & map results of every team of a tournament
+#[utoipa::path(
+    get,
+    path = "/api/guilds/{guild}/tournaments/{tournament}/standings",
+    params(
+        ("guild" = i64, Path, description = "Discord server id"),
+        ("tournament" = i32, Path, description = "Tournament id")
+    ),
+    responses(
+        (status = 200, description = "Standings, best team first", body = [ApiStanding]),
+        (status = 304, description = "Not modified"),
+        (status = 404, description = "No such tournament")
+    )
+)]
+pub async fn standings(
+    State(pool): State<PgPool>,
+    Path((guild, tournament)): Path<(i64, i32)>,
+    headers: HeaderMap,
+) -> ApiResult {
+    let exists = sqlx::query_scalar!(
+        "select id from tournament where id = $1 and guild = $2",
+        tournament,
+        guild
+    )
+    .fetch_optional(&pool)
+    .await?;
+    if exists.is_none() {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    let standings: Vec<ApiStanding> = Standing::get_by_tournament(&pool, tournament)
+        .await?
+        .into_iter()
+        .map(|s| ApiStanding {
+            team: s.team,
+            role: s.role.to_string(),
+            series_won: s.series_won,
+            series_lost: s.series_lost,
+            maps_won: s.maps_won,
+            maps_lost: s.maps_lost,
+        })
+        .collect();
+    json_with_etag(&headers, &standings)
+}
@@ -1,55 +1,265 @@
 use std::str::FromStr;
 
 use super::super::Context;
-use anyhow::{Error, Result};
+use crate::commands::announcements::{announce, series_created_embed};
+use crate::commands::audit::{audit_log, AuditAction, AuditEntry};
+use crate::commands::config::guild_id;
+use crate::commands::gslt::{self, GsltToken};
+use crate::commands::locations::{regions, ServerPing};
+use crate::commands::maps::Map;
+use crate::commands::matches::create_series;
+use crate::commands::pagination::paginate;
+use crate::commands::permissions::{caster_check, organiser_check, referee_check};
+use crate::commands::self_hosted::{MatchPlugin, NewRconServer, RconServer};
+use crate::commands::setup::{start_series_server, take_failed_start, Setup};
+use crate::commands::tournament::{
+    active_tournaments, apply_role_cleanups, close_tournament, create_tournament,
+    format_registrations, get_active_by_name, is_guild_series, pick_tournament, ArchivedRoleAction,
+    Game, Registration, RegistrationSettings, RegistrationStatus, ServerSelection, SideSelection,
+    TournamentTeam, VetoStartRule,
+};
+use crate::config::{Config, RCON_PASSWORD_PREFIX};
+use anyhow::Result;
 use futures::{Stream, StreamExt};
-use matchbot_core::matches::{MatchSeries, SeriesType};
+use matchbot_core::matches::{MatchScore, MatchSeries, SeriesType};
 use poise::command;
-use poise::serenity_prelude::{CacheHttp, RoleId};
+use poise::serenity_prelude::{CacheHttp, User};
 use sqlx::types::time::OffsetDateTime;
 use strum::IntoEnumIterator;
 
 use matchbot_core::team::Team;
-use matchbot_core::tournament::*;
 use serenity::model::guild::Role;
 use sqlx::sqlx_macros::FromRow;
-use sqlx::PgExecutor;
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
 
 #[derive(Debug, FromRow, Clone)]
 pub struct ServerTemplates {
     pub location: String,
+    /// Dathost server id, empty for self-hosted locations
     pub server_id: String,
+    /// Location of self-hosted servers started over RCON
+    pub self_hosted: bool,
+    /// Region players close to the location are from
+    pub region: Option<String>,
+    /// Series that can be played at the location at once, unlimited if not set
+    pub capacity: Option<i32>,
 }
 
 impl ServerTemplates {
     async fn add(
         executor: impl PgExecutor<'_>,
+        guild: i64,
         location: String,
         server_id: String,
+        region: Option<String>,
+        capacity: Option<i32>,
     ) -> Result<bool> {
         let result = sqlx::query!(
-            "insert into server_templates (location, server_id) values ($1, $2)",
+            "insert into server_templates (guild, location, server_id, region, capacity)
+                 values ($1, $2, $3, $4, $5)",
+            guild,
             location,
             server_id,
+            region,
+            capacity,
         )
         .execute(executor)
         .await?;
         return Ok(result.rows_affected() == 1);
     }
-    async fn delete(executor: impl PgExecutor<'_>, location: String) -> Result<bool> {
-        let result = sqlx::query!("delete from server_templates where location = $1", location,)
-            .execute(executor)
-            .await?;
+    async fn delete(executor: impl PgExecutor<'_>, guild: i64, location: String) -> Result<bool> {
+        let result = sqlx::query!(
+            "delete from server_templates where guild = $1 and location = $2",
+            guild,
+            location,
+        )
+        .execute(executor)
+        .await?;
         return Ok(result.rows_affected() == 1);
     }
-    pub(crate) async fn get_all(executor: impl PgExecutor<'_>) -> Result<Vec<ServerTemplates>> {
+    pub(crate) async fn get_all(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+    ) -> Result<Vec<ServerTemplates>> {
+        Ok(sqlx::query_as!(
+            ServerTemplates,
+            r#"select location as "location!", server_id as "server_id!", false as "self_hosted!",
+                      region, capacity
+                 from server_templates
+                 where guild = $1
+               union
+               select location, '', true, max(region), count(*)::int4
+                 from rcon_servers
+                 where guild = $1
+                   and location not in (select location from server_templates where guild = $1)
+                 group by location
+               order by 1"#,
+            guild,
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+    /// Locations teams can pick for a series of `game`, self-hosted locations need a free server
+    /// running the game's plugin and Dathost only runs single CS2 maps. Locations with as many
+    /// live or reserved series as their capacity are left out
+    pub(crate) async fn get_for_game(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        game: Game,
+        series_type: SeriesType,
+    ) -> Result<Vec<ServerTemplates>> {
+        let dathost = game == Game::Csgo || series_type == SeriesType::Bo1;
         Ok(sqlx::query_as!(
             ServerTemplates,
-            "select * from server_templates order by location"
+            r#"select st.location as "location!", st.server_id as "server_id!",
+                      false as "self_hosted!", st.region, st.capacity
+                 from server_templates st
+                 where st.guild = $1 and $2
+                   and (st.capacity is null
+                        or st.capacity > (select count(*)
+                                            from match_series ms
+                                               join teams t on t.id = ms.team_one
+                                            where t.guild = $1
+                                              and lower(ms.server_location) = lower(st.location)
+                                              and ms.completed_at is null
+                                              and (ms.dathost_match is not null
+                                                   or ms.server_reserved_at > now() - interval '1 hour')))
+               union
+               select r.location, '', true, max(r.region), count(*)::int4
+                 from rcon_servers r
+                 where r.guild = $1 and r.plugin = $3
+                   and r.location not in (select location from server_templates where guild = $1 and $2)
+                   and (r.claimed_by_series is null
+                        or exists (select 1
+                                     from match_series ms
+                                     where ms.id = r.claimed_by_series and ms.completed_at is not null))
+                 group by r.location
+               order by 1"#,
+            guild,
+            dathost,
+            game.match_plugin() as MatchPlugin,
         )
         .fetch_all(executor)
         .await?)
     }
+    /// Reserves a slot of `location` for `match_series` for an hour or until its server runs,
+    /// `false` when the location is at capacity. Self-hosted locations always succeed, their
+    /// servers are claimed when the match is loaded
+    pub(crate) async fn reserve(
+        pool: &PgPool,
+        guild: i64,
+        match_series: i32,
+        location: &str,
+    ) -> Result<bool> {
+        let mut transaction = pool.begin().await?;
+        // locking the template makes concurrent reservations of the location wait for each other
+        let capacity = sqlx::query_scalar!(
+            "select capacity from server_templates
+                 where guild = $1 and lower(location) = lower($2)
+                 for update",
+            guild,
+            location,
+        )
+        .fetch_optional(&mut transaction)
+        .await?
+        .flatten();
+        if let Some(capacity) = capacity {
+            let taken = sqlx::query_scalar!(
+                r#"select count(*) as "count!"
+                     from match_series ms
+                        join teams t on t.id = ms.team_one
+                     where t.guild = $1
+                       and lower(ms.server_location) = lower($2)
+                       and ms.id <> $3
+                       and ms.completed_at is null
+                       and (ms.dathost_match is not null
+                            or ms.server_reserved_at > now() - interval '1 hour')"#,
+                guild,
+                location,
+                match_series,
+            )
+            .fetch_one(&mut transaction)
+            .await?;
+            if taken >= capacity as i64 {
+                return Ok(false);
+            }
+        }
+        sqlx::query!(
+            "update match_series set server_location = $2, server_reserved_at = now() where id = $1",
+            match_series,
+            location,
+        )
+        .execute(&mut transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(true)
+    }
+}
+
+/// Parses a `YYYY-MM-DD` date as midnight UTC
+pub(crate) fn parse_date(date: &str) -> Option<OffsetDateTime> {
+    let date_format = time::macros::format_description!(
+        "[year]-[month]-[day] [hour]:[minute]:[second] [offset_hour sign:mandatory]:[offset_minute]:[offset_second]"
+    );
+    OffsetDateTime::parse(format!("{} 00:00:00 +00:00:00", date).as_str(), date_format).ok()
+}
+
+/// Parses a `YYYY-MM-DD HH:MM` time as UTC
+pub(crate) fn parse_date_time(date_time: &str) -> Option<OffsetDateTime> {
+    let date_format = time::macros::format_description!(
+        "[year]-[month]-[day] [hour]:[minute]:[second] [offset_hour sign:mandatory]:[offset_minute]:[offset_second]"
+    );
+    OffsetDateTime::parse(
+        format!("{}:00 +00:00:00", date_time.trim()).as_str(),
+        date_format,
+    )
+    .ok()
+}
+
+/// Sets the score of the `map_number`th map of a series and marks the map completed,
+/// returns `false` if the series has no such map
+pub(crate) async fn set_map_score(
+    transaction: &mut Transaction<'_, Postgres>,
+    match_series: i32,
+    map_number: i64,
+    team_one_score: i32,
+    team_two_score: i32,
+) -> Result<bool> {
+    let match_id = sqlx::query_scalar!(
+        "select id from match where match_series = $1 order by id offset $2 limit 1",
+        match_series,
+        map_number - 1,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    let Some(match_id) = match_id else {
+        return Ok(false);
+    };
+    let updated = sqlx::query!(
+        "update match_scores set team_one_score = $2, team_two_score = $3 where match_id = $1",
+        match_id,
+        team_one_score,
+        team_two_score,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    if updated.rows_affected() == 0 {
+        sqlx::query!(
+            "insert into match_scores (match_id, team_one_score, team_two_score) values ($1, $2, $3)",
+            match_id,
+            team_one_score,
+            team_two_score,
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+    sqlx::query!(
+        "update match set completed_at = coalesce(completed_at, now()) where id = $1",
+        match_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    Ok(true)
 }
 
 async fn series_types<'a>(_ctx: Context<'_>, partial: &'a str) -> impl Stream<Item = String> + 'a {
@@ -60,12 +270,73 @@ async fn series_types<'a>(_ctx: Context<'_>, partial: &'a str) -> impl Stream<It
         .map(|name| name.to_string())
 }
 
+async fn veto_start_rules<'a>(
+    _ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Stream<Item = String> + 'a {
+    let rule_strings: Vec<String> = VetoStartRule::iter().map(|r| r.to_string()).collect();
+    futures::stream::iter(rule_strings)
+        .filter(move |name| futures::future::ready(name.starts_with(partial)))
+}
+
+async fn games<'a>(_ctx: Context<'_>, partial: &'a str) -> impl Stream<Item = String> + 'a {
+    let game_strings: Vec<String> = Game::iter().map(|g| g.to_string()).collect();
+    futures::stream::iter(game_strings)
+        .filter(move |name| futures::future::ready(name.starts_with(partial)))
+}
+
+async fn server_selections<'a>(
+    _ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Stream<Item = String> + 'a {
+    let selection_strings: Vec<String> = ServerSelection::iter().map(|s| s.to_string()).collect();
+    futures::stream::iter(selection_strings)
+        .filter(move |name| futures::future::ready(name.starts_with(partial)))
+}
+
+async fn archived_role_actions<'a>(
+    _ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Stream<Item = String> + 'a {
+    let action_strings: Vec<String> = ArchivedRoleAction::iter().map(|a| a.to_string()).collect();
+    futures::stream::iter(action_strings)
+        .filter(move |name| futures::future::ready(name.starts_with(partial)))
+}
+
+async fn match_plugins<'a>(_ctx: Context<'_>, partial: &'a str) -> impl Stream<Item = String> + 'a {
+    let plugin_strings: Vec<String> = MatchPlugin::iter().map(|p| p.to_string()).collect();
+    futures::stream::iter(plugin_strings)
+        .filter(move |name| futures::future::ready(name.starts_with(partial)))
+}
+
+async fn audit_actions<'a>(_ctx: Context<'_>, partial: &'a str) -> impl Stream<Item = String> + 'a {
+    let action_strings: Vec<String> = AuditAction::iter().map(|a| a.to_string()).collect();
+    futures::stream::iter(action_strings)
+        .filter(move |name| futures::future::ready(name.starts_with(partial)))
+}
+
+async fn side_selections<'a>(
+    _ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Stream<Item = String> + 'a {
+    let selection_strings: Vec<String> = SideSelection::iter().map(|s| s.to_string()).collect();
+    futures::stream::iter(selection_strings)
+        .filter(move |name| futures::future::ready(name.starts_with(partial)))
+}
+
 #[command(
     slash_command,
     guild_only,
     ephemeral,
-    default_member_permissions = "MODERATE_MEMBERS",
-    subcommands("matches", "servers", "tournament")
+    subcommands(
+        "matches",
+        "servers",
+        "tournament",
+        "maps",
+        "search_audit",
+        "config",
+        "gslt"
+    )
 )]
 pub(crate) async fn admin(_context: Context<'_>) -> Result<()> {
     Ok(())
@@ -75,8 +346,13 @@ pub(crate) async fn admin(_context: Context<'_>) -> Result<()> {
     slash_command,
     guild_only,
     ephemeral,
-    default_member_permissions = "MODERATE_MEMBERS",
-    subcommands("add_match", "delete_match")
+    subcommands(
+        "add_match",
+        "delete_match",
+        "series_side_selection",
+        "set_score",
+        "start_series"
+    )
 )]
 pub(crate) async fn matches(_context: Context<'_>) -> Result<()> {
     Ok(())
@@ -86,8 +362,18 @@ pub(crate) async fn matches(_context: Context<'_>) -> Result<()> {
     slash_command,
     guild_only,
     ephemeral,
-    default_member_permissions = "MODERATE_MEMBERS",
-    subcommands("add_tournament", "end_tournament")
+    subcommands(
+        "add_tournament",
+        "end_tournament",
+        "veto_start",
+        "side_selection",
+        "set_game",
+        "set_server_selection",
+        "registration",
+        "show_participants",
+        "add_participant",
+        "remove_participant"
+    )
 )]
 pub(crate) async fn tournament(_context: Context<'_>) -> Result<()> {
     Ok(())
@@ -97,8 +383,14 @@ pub(crate) async fn tournament(_context: Context<'_>) -> Result<()> {
     slash_command,
     guild_only,
     ephemeral,
-    default_member_permissions = "MODERATE_MEMBERS",
-    subcommands("add_server", "delete_server", "show_servers")
+    subcommands(
+        "add_server",
+        "delete_server",
+        "add_rcon_server",
+        "delete_rcon_server",
+        "set_server_ping",
+        "show_servers"
+    )
 )]
 pub(crate) async fn servers(_context: Context<'_>) -> Result<()> {
     Ok(())
@@ -108,17 +400,225 @@ pub(crate) async fn servers(_context: Context<'_>) -> Result<()> {
     slash_command,
     guild_only,
     ephemeral,
+    subcommands("add_map", "enable_map", "disable_map", "show_maps")
+)]
+pub(crate) async fn maps(_context: Context<'_>) -> Result<()> {
+    Ok(())
+}
+
+#[command(slash_command, guild_only, ephemeral, subcommands("reload_config"))]
+pub(crate) async fn config(_context: Context<'_>) -> Result<()> {
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    owners_only,
+    rename = "reload",
+    description_localized("en-US", "Reload the bot config file")
+)]
+pub(crate) async fn reload_config(context: Context<'_>) -> Result<()> {
+    let new = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            context
+                .say(format!("Config not reloaded: {:#}", err))
+                .await?;
+            return Ok(());
+        }
+    };
+    let changed = context.data().config.write().unwrap().reload(new);
+    // Environment variables still take precedence, but are not re-read
+    let pinned = match Config::env_pinned() {
+        pinned if pinned.is_empty() => String::new(),
+        pinned => format!(
+            "\n`{}` set by environment variables, restart the bot to change them",
+            pinned.join("`, `")
+        ),
+    };
+    if changed.is_empty() {
+        context
+            .say(format!("Config reloaded, nothing changed{}", pinned))
+            .await?;
+        return Ok(());
+    }
+    // Reloaded values may contain secrets, only record which keys changed
+    audit_log(
+        &context,
+        context.data().pool.begin().await?,
+        AuditAction::ConfigChanged,
+        "bot config",
+        None,
+        Some(changed.join(", ")),
+    )
+    .await?;
+    context
+        .say(format!(
+            "Config reloaded, changed: `{}`{}",
+            changed.join("`, `"),
+            pinned
+        ))
+        .await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    subcommands("list_gslt", "revoke_gslt")
+)]
+pub(crate) async fn gslt(_context: Context<'_>) -> Result<()> {
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    owners_only,
+    rename = "list",
+    description_localized(
+        "en-US",
+        "Show the game server login tokens of the bot's Steam account"
+    )
+)]
+pub(crate) async fn list_gslt(context: Context<'_>) -> Result<()> {
+    let tokens = GsltToken::get_all(&context.data().pool).await?;
+    if tokens.is_empty() {
+        context
+            .say("No game server login tokens created yet")
+            .await?;
+        return Ok(());
+    }
+    let lines: Vec<String> = tokens
+        .into_iter()
+        .map(|t| {
+            let mut s = format!("`{}` app `{}`", t.steamid, t.app_id);
+            if let Some(server_id) = &t.server_id {
+                s.push_str(format!(" server `{}`", server_id).as_str());
+            }
+            if let Some(match_series) = t.match_series {
+                s.push_str(format!(" match `#{}`", match_series).as_str());
+            }
+            if let Some(last_used_at) = t.last_used_at {
+                s.push_str(format!(" used <t:{}:R>", last_used_at.unix_timestamp()).as_str());
+            }
+            if t.in_use {
+                s.push_str(" _(in use)_");
+            }
+            s
+        })
+        .collect();
+    paginate(&context, "Game server login tokens", &lines).await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    owners_only,
+    rename = "revoke",
+    description_localized("en-US", "Delete a game server login token from the Steam account")
+)]
+pub(crate) async fn revoke_gslt(
+    context: Context<'_>,
+    #[description = "Steam id of the game server account"] steamid: String,
+) -> Result<()> {
+    let data = context.data();
+    let token = GsltToken::get_all(&data.pool)
+        .await?
+        .into_iter()
+        .find(|t| t.steamid == steamid);
+    let Some(token) = token else {
+        context
+            .say(format!("No game server account `{}` found", steamid))
+            .await?;
+        return Ok(());
+    };
+    if token.in_use {
+        context
+            .say(format!(
+                "`{}` is in use by match `#{}`",
+                steamid,
+                token.match_series.unwrap_or_default()
+            ))
+            .await?;
+        return Ok(());
+    }
+    let mut transaction = data.pool.begin().await?;
+    gslt::revoke_gslt(&mut transaction, data.steam.as_ref(), &token).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::GsltRevoked,
+        &steamid,
+        None,
+        None,
+    )
+    .await?;
+    context.say("Game server login token revoked").await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "organiser_check",
     rename = "add",
-    description_localized("en-US", "Add server template")
+    description_localized("en-US", "Add map to the map pool")
 )]
-pub(crate) async fn add_server(
+pub(crate) async fn add_map(
     context: Context<'_>,
-    #[description = "Location name"] location: String,
-    #[description = "Dathost server id"] server_id: String,
+    #[description = "Map name i.e. de_cache"] name: String,
+    #[description = "Name shown in veto menus"] display_name: Option<String>,
+    #[description = "Steam Workshop id"] workshop_id: Option<String>,
+    #[description = "Only in the map pool of this game (default both)"]
+    #[autocomplete = "games"]
+    game: Option<String>,
 ) -> Result<()> {
-    let pool = &context.data().pool;
-    ServerTemplates::add(pool, location, server_id).await?;
-    context.say("Server added").await?;
+    if let Some(workshop_id) = &workshop_id {
+        if workshop_id.is_empty() || !workshop_id.chars().all(|c| c.is_ascii_digit()) {
+            context
+                .say("Invalid workshop id, it must be the numeric id from the workshop url")
+                .await?;
+            return Ok(());
+        }
+    }
+    let game = match game {
+        Some(game) => match Game::from_str(&game) {
+            Ok(game) => Some(game),
+            Err(_) => {
+                context.say(format!("Invalid game `{}`", game)).await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+    let mut transaction = context.data().pool.begin().await?;
+    Map::add(
+        &mut transaction,
+        guild_id(&context)?,
+        &name,
+        display_name,
+        workshop_id,
+        game,
+    )
+    .await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::MapAdded,
+        &name,
+        None,
+        None,
+    )
+    .await?;
+    context.say("Map added").await?;
     Ok(())
 }
 
@@ -126,16 +626,63 @@ pub(crate) async fn add_server(
     slash_command,
     guild_only,
     ephemeral,
-    rename = "delete",
-    description_localized("en-US", "Delete server template")
+    check = "organiser_check",
+    rename = "enable",
+    description_localized("en-US", "Enable map in the map pool")
 )]
-pub(crate) async fn delete_server(
+pub(crate) async fn enable_map(
     context: Context<'_>,
-    #[description = "Location name"] location: String,
+    #[description = "Map name"] name: String,
 ) -> Result<()> {
-    let pool = &context.data().pool;
-    ServerTemplates::delete(pool, location).await?;
-    context.say("Server deleted").await?;
+    let mut transaction = context.data().pool.begin().await?;
+    if !Map::set_disabled(&mut transaction, guild_id(&context)?, &name, false).await? {
+        context
+            .say(format!("Could not find map `{}`", name))
+            .await?;
+        return Ok(());
+    }
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::MapEnabled,
+        &name,
+        None,
+        None,
+    )
+    .await?;
+    context.say("Map enabled").await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "organiser_check",
+    rename = "disable",
+    description_localized("en-US", "Disable map in the map pool")
+)]
+pub(crate) async fn disable_map(
+    context: Context<'_>,
+    #[description = "Map name"] name: String,
+) -> Result<()> {
+    let mut transaction = context.data().pool.begin().await?;
+    if !Map::set_disabled(&mut transaction, guild_id(&context)?, &name, true).await? {
+        context
+            .say(format!("Could not find map `{}`", name))
+            .await?;
+        return Ok(());
+    }
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::MapDisabled,
+        &name,
+        None,
+        None,
+    )
+    .await?;
+    context.say("Map disabled").await?;
     Ok(())
 }
 
@@ -143,17 +690,34 @@ pub(crate) async fn delete_server(
     slash_command,
     guild_only,
     ephemeral,
+    check = "caster_check",
     rename = "show",
-    description_localized("en-US", "Show all server templates")
+    description_localized("en-US", "Show all maps")
 )]
-pub(crate) async fn show_servers(context: Context<'_>) -> Result<()> {
+pub(crate) async fn show_maps(context: Context<'_>) -> Result<()> {
     let pool = &context.data().pool;
-    let servers = ServerTemplates::get_all(pool).await?;
-    let content: String = servers
+    let maps = Map::get_all(pool, guild_id(&context)?, false).await?;
+    if maps.is_empty() {
+        context.say("No maps have been added").await?;
+        return Ok(());
+    }
+    let lines: Vec<String> = maps
         .into_iter()
-        .map(|s| format!("id: `{}` server_id: `{}`\n", s.location, s.server_id))
+        .map(|m| {
+            let mut s = format!("`{}` {}", m.name, m.label());
+            if let Some(workshop_id) = &m.workshop_id {
+                s.push_str(format!(" workshop: `{}`", workshop_id).as_str());
+            }
+            if let Some(game) = m.game {
+                s.push_str(format!(" {} only", game.label()).as_str());
+            }
+            if m.disabled {
+                s.push_str(" _(disabled)_");
+            }
+            s
+        })
         .collect();
-    context.say(content).await?;
+    paginate(&context, "Maps", &lines).await?;
     Ok(())
 }
 
@@ -161,109 +725,224 @@ pub(crate) async fn show_servers(context: Context<'_>) -> Result<()> {
     slash_command,
     guild_only,
     ephemeral,
+    check = "organiser_check",
     rename = "add",
-    description_localized("en-US", "Add match to schedule")
+    description_localized("en-US", "Add server template")
 )]
-pub(crate) async fn add_match(
+pub(crate) async fn add_server(
     context: Context<'_>,
-    #[description = "Team One (Higher Seed)"] team_one: Role,
-    #[description = "Team Two (Lower Seed)"] team_two: Role,
-    #[autocomplete = "series_types"] series_type: String,
+    #[description = "Location name"] location: String,
+    #[description = "Dathost server id"] server_id: String,
+    #[description = "Region players close to the location are from, i.e. eu"]
+    #[autocomplete = "regions"]
+    region: Option<String>,
+    #[description = "Series that can be played at the location at once (default unlimited)"]
+    capacity: Option<u32>,
+) -> Result<()> {
+    let mut transaction = context.data().pool.begin().await?;
+    ServerTemplates::add(
+        &mut transaction,
+        guild_id(&context)?,
+        location.clone(),
+        server_id.clone(),
+        region,
+        capacity.map(|c| c as i32),
+    )
+    .await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ServerTemplateAdded,
+        &location,
+        None,
+        Some(server_id),
+    )
+    .await?;
+    context.say("Server added").await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "organiser_check",
+    rename = "delete",
+    description_localized("en-US", "Delete server template")
+)]
+pub(crate) async fn delete_server(
+    context: Context<'_>,
+    #[description = "Location name"] location: String,
 ) -> Result<()> {
     let pool = &context.data().pool;
-    let series_type_enum = SeriesType::from_str(&series_type).unwrap();
-    let team_one = Team::get_by_role(pool, team_one.id.0 as i64).await?;
-    let team_two = Team::get_by_role(pool, team_two.id.0 as i64).await?;
-    let Some(current_tournament) = Tournament::get_current(pool).await? else {
-        context
-            .say("There is no active tournament, use `/admin tournament new` to create one.")
-            .await?;
-        return Ok(());
-    };
+    let guild = guild_id(&context)?;
+    let before = ServerTemplates::get_all(pool, guild)
+        .await?
+        .into_iter()
+        .find(|s| s.location == location)
+        .map(|s| s.server_id);
+    let mut transaction = pool.begin().await?;
+    ServerTemplates::delete(&mut transaction, guild, location.clone()).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ServerTemplateDeleted,
+        &location,
+        before,
+        None,
+    )
+    .await?;
+    context.say("Server deleted").await?;
+    Ok(())
+}
 
-    let result = MatchSeries::create(
-        pool,
-        team_one.unwrap().id,
-        team_two.unwrap().id,
-        series_type_enum,
-        current_tournament,
-    );
-    if let Err(err) = result.await {
-        log::error!("{:#?}", err);
-        context.say("Error creating match").await?;
-        return Ok(());
+/// Splits `host:port` into its parts
+fn parse_address(address: &str) -> Option<(String, i32)> {
+    let (host, port) = address.trim().rsplit_once(':')?;
+    let port = port.parse::<u16>().ok()?;
+    if host.is_empty() {
+        return None;
     }
-    context.say("Match successfully created").await?;
-    return Ok(());
+    Some((host.to_string(), port as i32))
 }
 
 #[command(
     slash_command,
     guild_only,
     ephemeral,
-    rename = "delete",
-    description_localized("en-US", "Delete match from schedule")
+    owners_only,
+    rename = "add_rcon",
+    description_localized("en-US", "Add self-hosted server, matches are loaded over RCON")
 )]
-pub(crate) async fn delete_match(
+pub(crate) async fn add_rcon_server(
     context: Context<'_>,
-    #[description = "Match Id"] match_id: i32,
+    #[description = "Location name, servers of a location are used in turn"] location: String,
+    #[description = "Game & RCON address i.e. 203.0.113.7:27015"] address: String,
+    #[description = "GOTV port"] gotv_port: u16,
+    #[description = "Environment variable holding the RCON password, i.e. RCON_PASSWORD_LEAGUE"]
+    password_env: String,
+    #[description = "Match plugin (default matchzy)"]
+    #[autocomplete = "match_plugins"]
+    plugin: Option<String>,
+    #[description = "Region players close to the location are from, i.e. eu"]
+    #[autocomplete = "regions"]
+    region: Option<String>,
 ) -> Result<()> {
     let pool = &context.data().pool;
-    let result = MatchSeries::delete(pool, match_id).await;
-    if let Err(err) = result {
-        log::error!("{:#?}", err);
+    let Some((hostname, game_port)) = parse_address(&address) else {
         context
-            .say("Could not delete match, please provide a valid match id")
+            .say(format!(
+                "Invalid address `{}`, expected `host:port`",
+                address
+            ))
+            .await?;
+        return Ok(());
+    };
+    let plugin = match plugin {
+        Some(plugin) => match MatchPlugin::from_str(&plugin) {
+            Ok(plugin) => plugin,
+            Err(_) => {
+                context.say(format!("Invalid plugin `{}`", plugin)).await?;
+                return Ok(());
+            }
+        },
+        None => MatchPlugin::Matchzy,
+    };
+    // The password is sent to the given address, so only RCON passwords may be picked
+    if !password_env.starts_with(RCON_PASSWORD_PREFIX) {
+        context
+            .say(format!(
+                "The password variable has to start with `{}`",
+                RCON_PASSWORD_PREFIX
+            ))
             .await?;
         return Ok(());
     }
-    context.say("Match successfully deleted").await?;
-    return Ok(());
+    // Only bot owners, who set the variables on the bot host, can tell whether one exists
+    if std::env::var(&password_env).is_err() {
+        context
+            .say(format!(
+                "Environment variable `{}` is not set on the bot host",
+                password_env
+            ))
+            .await?;
+        return Ok(());
+    }
+    let server = NewRconServer {
+        guild: guild_id(&context)?,
+        location: location.clone(),
+        hostname,
+        game_port,
+        gotv_port: gotv_port as i32,
+        rcon_password_env: password_env,
+        plugin,
+        region,
+    };
+    let mut transaction = pool.begin().await?;
+    if !RconServer::add(&mut transaction, &server).await? {
+        context
+            .say(format!("Server `{}` has already been added", address))
+            .await?;
+        return Ok(());
+    }
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ServerTemplateAdded,
+        &location,
+        None,
+        Some(format!("{} ({})", address, plugin)),
+    )
+    .await?;
+    context.say("Server added").await?;
+    Ok(())
 }
 
 #[command(
     slash_command,
     guild_only,
     ephemeral,
-    rename = "new",
-    description_localized("en-US", "Create new tournament")
+    check = "organiser_check",
+    rename = "delete_rcon",
+    description_localized("en-US", "Delete self-hosted server")
 )]
-pub(crate) async fn add_tournament(
+pub(crate) async fn delete_rcon_server(
     context: Context<'_>,
-    #[description = "Name"] name: String,
-    #[description = "Start date YYYY-MM-DD"] start_date: String,
+    #[description = "Game & RCON address i.e. 203.0.113.7:27015"] address: String,
 ) -> Result<()> {
     let pool = &context.data().pool;
-    let current = Tournament::get_current(pool).await?;
-    if current.is_some() {
+    let Some((hostname, game_port)) = parse_address(&address) else {
         context
-            .say("There is an active tournament, use `/admin tournament end` to end it first.")
+            .say(format!(
+                "Invalid address `{}`, expected `host:port`",
+                address
+            ))
             .await?;
         return Ok(());
-    }
-    let date_format = time::macros::format_description!(
-        "[year]-[month]-[day] [hour]:[minute]:[second] [offset_hour sign:mandatory]:[offset_minute]:[offset_second]"
-    );
-    let started_at = OffsetDateTime::parse(
-        format!("{} 00:00:00 +00:00:00", &start_date).as_str(),
-        date_format,
-    );
-    let Ok(started_at) = started_at else {
+    };
+    let guild = guild_id(&context)?;
+    let before = RconServer::get_all(pool, guild)
+        .await?
+        .into_iter()
+        .find(|s| s.hostname == hostname && s.game_port == game_port)
+        .map(|s| s.location);
+    let mut transaction = pool.begin().await?;
+    if !RconServer::delete(&mut transaction, guild, &hostname, game_port).await? {
         context
-            .say("Invalid start date format, please use YYYY-MM-DD")
+            .say(format!("No server `{}` found", address))
             .await?;
         return Ok(());
-    };
-    let new_tournament = Tournament {
-        id: 0,
-        name,
-        started_at,
-        completed_at: None,
-    };
-
-    new_tournament.create(pool).await?;
-
-    context.say("Created new tournament.").await?;
+    }
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ServerTemplateDeleted,
+        &address,
+        before,
+        None,
+    )
+    .await?;
+    context.say("Server deleted").await?;
     Ok(())
 }
 
@@ -271,28 +950,1054 @@ pub(crate) async fn add_tournament(
     slash_command,
     guild_only,
     ephemeral,
-    rename = "end",
-    description_localized("en-US", "End active tournament")
+    check = "organiser_check",
+    rename = "ping",
+    description_localized(
+        "en-US",
+        "Set the expected ping from players of a region to a location"
+    )
 )]
-pub(crate) async fn end_tournament(context: Context<'_>) -> Result<()> {
+pub(crate) async fn set_server_ping(
+    context: Context<'_>,
+    #[description = "Location name"] location: String,
+    #[description = "Region of the players, i.e. eu"]
+    #[autocomplete = "regions"]
+    region: String,
+    #[description = "Ping in milliseconds"] ping: u16,
+) -> Result<()> {
     let pool = &context.data().pool;
-    let current = Tournament::get_current(pool).await?;
-    if current.is_none() {
+    let guild = guild_id(&context)?;
+    if !ServerTemplates::get_all(pool, guild)
+        .await?
+        .iter()
+        .any(|s| s.location.eq_ignore_ascii_case(&location))
+    {
         context
-            .say("There is no active tournament, use `/admin tournament new` to start a new tournament.")
+            .say(format!("No server location `{}` found", location))
             .await?;
         return Ok(());
     }
-    let guild = context
-        .guild_id()
-        .ok_or_else::<Error, _>(|| unreachable!())?;
-    let teams = Team::get_all(pool).await?;
-    for team in teams {
-        guild
-            .delete_role(context.http(), RoleId(team.role as u64))
+    let before = ServerPing::get_all(pool, guild)
+        .await?
+        .into_iter()
+        .find(|p| {
+            p.location.eq_ignore_ascii_case(&location) && p.region.eq_ignore_ascii_case(&region)
+        })
+        .map(|p| p.ping.to_string());
+    let mut transaction = pool.begin().await?;
+    ServerPing::set(&mut transaction, guild, &location, &region, ping as i32).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ServerPingChanged,
+        format!("{} ping from {}", location, region),
+        before,
+        Some(ping.to_string()),
+    )
+    .await?;
+    context.say("Ping set").await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "caster_check",
+    rename = "show",
+    description_localized("en-US", "Show all server templates")
+)]
+pub(crate) async fn show_servers(context: Context<'_>) -> Result<()> {
+    let pool = &context.data().pool;
+    let guild = guild_id(&context)?;
+    let servers = ServerTemplates::get_all(pool, guild).await?;
+    if servers.is_empty() {
+        context.say("No server templates have been added").await?;
+        return Ok(());
+    }
+    let mut lines: Vec<String> = servers
+        .into_iter()
+        .filter(|s| !s.self_hosted)
+        .map(|s| {
+            format!(
+                "id: `{}` server_id: `{}` region: `{}` capacity: `{}`",
+                s.location,
+                s.server_id,
+                s.region.as_deref().unwrap_or("-"),
+                s.capacity
+                    .map_or("unlimited".to_string(), |c| c.to_string())
+            )
+        })
+        .collect();
+    lines.extend(
+        RconServer::get_all(pool, guild)
+            .await?
+            .into_iter()
+            .map(|s| {
+                format!(
+                    "id: `{}` self-hosted: `{}` plugin: `{}` region: `{}`",
+                    s.location,
+                    s.address(),
+                    s.plugin,
+                    s.region.as_deref().unwrap_or("-")
+                )
+            }),
+    );
+    lines.extend(
+        ServerPing::get_all(pool, guild)
+            .await?
+            .into_iter()
+            .map(|p| {
+                format!(
+                    "id: `{}` ping from `{}`: `{}ms`",
+                    p.location, p.region, p.ping
+                )
+            }),
+    );
+    paginate(&context, "Server templates", &lines).await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "referee_check",
+    rename = "add",
+    description_localized("en-US", "Add match to schedule")
+)]
+pub(crate) async fn add_match(
+    context: Context<'_>,
+    #[description = "Team One (Higher Seed)"] team_one: Role,
+    #[description = "Team Two (Lower Seed)"] team_two: Role,
+    #[autocomplete = "series_types"] series_type: String,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+    #[description = "Scheduled time in UTC, i.e. 2023-06-01 18:00"] scheduled: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let series_type_enum = SeriesType::from_str(&series_type).unwrap();
+    let scheduled_at = match scheduled.as_deref().map(parse_date_time) {
+        Some(None) => {
+            context
+                .say("Invalid time format, use `YYYY-MM-DD HH:MM` (UTC)")
+                .await?;
+            return Ok(());
+        }
+        Some(scheduled_at) => scheduled_at,
+        None => None,
+    };
+    let team_one = Team::get_by_role(pool, team_one.id.0 as i64).await?;
+    let team_two = Team::get_by_role(pool, team_two.id.0 as i64).await?;
+    let Some(current_tournament) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let (Some(team_one), Some(team_two)) = (team_one, team_two) else {
+        context.say("Both roles must belong to a team").await?;
+        return Ok(());
+    };
+    let settings = RegistrationSettings::get(pool, current_tournament.id).await?;
+    if settings.uses_registration() {
+        for team in [&team_one, &team_two] {
+            let registration = Registration::get(pool, current_tournament.id, team.id).await?;
+            if registration.map(|r| r.status) != Some(RegistrationStatus::Registered) {
+                context
+                    .say(format!(
+                        "<@&{}> is not a registered participant of {}",
+                        team.role, current_tournament.name
+                    ))
+                    .await?;
+                return Ok(());
+            }
+        }
+    }
+    let mut transaction = pool.begin().await?;
+    TournamentTeam::add(&mut transaction, current_tournament.id, team_one.id).await?;
+    TournamentTeam::add(&mut transaction, current_tournament.id, team_two.id).await?;
+
+    let target = format!(
+        "{} vs {} ({})",
+        team_one.name, team_two.name, current_tournament.name
+    );
+    let embed = series_created_embed(
+        &team_one,
+        &team_two,
+        series_type_enum,
+        &current_tournament.name,
+        scheduled_at,
+    );
+    let result = create_series(
+        &mut transaction,
+        team_one.id,
+        team_two.id,
+        series_type_enum,
+        current_tournament.id,
+        scheduled_at,
+    );
+    let series = match result.await {
+        Ok(series) => series,
+        Err(err) => {
+            log::error!("{:#?}", err);
+            context.say("Error creating match").await?;
+            return Ok(());
+        }
+    };
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::MatchCreated,
+        format!("#{} {}", series, target),
+        None,
+        Some(series_type),
+    )
+    .await?;
+    if let Err(err) = announce(
+        context.serenity_context(),
+        pool,
+        guild_id(&context)?,
+        embed,
+        None,
+    )
+    .await
+    {
+        log::error!("{:#?}", err);
+    }
+    context.say("Match successfully created").await?;
+    return Ok(());
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "referee_check",
+    rename = "delete",
+    description_localized("en-US", "Delete match from schedule")
+)]
+pub(crate) async fn delete_match(
+    context: Context<'_>,
+    #[description = "Match Id"] match_id: i32,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let series = match is_guild_series(pool, guild_id(&context)?, match_id).await? {
+        true => MatchSeries::get(pool, match_id).await?,
+        false => None,
+    };
+    let Some(series) = series else {
+        context
+            .say("Could not delete match, please provide a valid match id")
+            .await?;
+        return Ok(());
+    };
+    let team_one = Team::get(pool, series.team_one).await?;
+    let team_two = Team::get(pool, series.team_two).await?;
+    let mut transaction = pool.begin().await?;
+    let result = MatchSeries::delete(&mut transaction, match_id).await;
+    if let Err(err) = result {
+        log::error!("{:#?}", err);
+        context
+            .say("Could not delete match, please provide a valid match id")
+            .await?;
+        return Ok(());
+    }
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::MatchDeleted,
+        format!("#{}", match_id),
+        Some(format!(
+            "{} vs {} ({})",
+            team_one.name, team_two.name, series.series_type
+        )),
+        None,
+    )
+    .await?;
+    context.say("Match successfully deleted").await?;
+    return Ok(());
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "organiser_check",
+    rename = "new",
+    description_localized("en-US", "Create new tournament")
+)]
+pub(crate) async fn add_tournament(
+    context: Context<'_>,
+    #[description = "Name"] name: String,
+    #[description = "Start date YYYY-MM-DD"] start_date: String,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let guild = guild_id(&context)?;
+    if get_active_by_name(pool, guild, &name).await?.is_some() {
+        context
+            .say(format!(
+                "There is already an active tournament named `{}`",
+                name
+            ))
+            .await?;
+        return Ok(());
+    }
+    let Some(started_at) = parse_date(&start_date) else {
+        context
+            .say("Invalid start date format, please use YYYY-MM-DD")
+            .await?;
+        return Ok(());
+    };
+    let mut transaction = pool.begin().await?;
+    create_tournament(&mut transaction, guild, &name, started_at).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::TournamentCreated,
+        &name,
+        None,
+        Some(start_date),
+    )
+    .await?;
+
+    context.say("Created new tournament.").await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "organiser_check",
+    rename = "end",
+    description_localized("en-US", "End an active tournament")
+)]
+pub(crate) async fn end_tournament(
+    context: Context<'_>,
+    #[description = "What to do with team roles (default delete)"]
+    #[autocomplete = "archived_role_actions"]
+    roles: Option<String>,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let role_action = match roles {
+        Some(roles) => match ArchivedRoleAction::from_str(&roles) {
+            Ok(role_action) => role_action,
+            Err(_) => {
+                context
+                    .say(format!("Invalid role option `{}`", roles))
+                    .await?;
+                return Ok(());
+            }
+        },
+        None => ArchivedRoleAction::Delete,
+    };
+    let guild = guild_id(&context)?;
+    let mut transaction = pool.begin().await?;
+    let archived = close_tournament(&mut transaction, &current, guild, role_action).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::TournamentEnded,
+        &current.name,
+        None,
+        Some(format!(
+            "{} teams archived, roles: {}",
+            archived, role_action
+        )),
+    )
+    .await?;
+    let failed = apply_role_cleanups(context.http(), pool, Some(guild)).await?;
+    let mut content = format!(
+        "{} ended, {} teams archived. Captains can use `/team carryover` to bring their team into the next tournament.",
+        current.name, archived
+    );
+    if failed > 0 {
+        content.push_str(
+            format!(
+                "\n⚠️ {} role changes failed, they are retried every 15 minutes.",
+                failed
+            )
+            .as_str(),
+        );
+    }
+    context.say(content).await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "organiser_check",
+    description_localized("en-US", "Set which team starts the map veto")
+)]
+pub(crate) async fn veto_start(
+    context: Context<'_>,
+    #[description = "Veto start rule"]
+    #[autocomplete = "veto_start_rules"]
+    rule: String,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Ok(rule) = VetoStartRule::from_str(&rule) else {
+        context
+            .say(format!("Invalid veto start rule `{}`", rule))
+            .await?;
+        return Ok(());
+    };
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let mut transaction = pool.begin().await?;
+    VetoStartRule::set(&mut transaction, current.id, rule).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::TournamentSettingsChanged,
+        format!("{} veto start", current.name),
+        None,
+        Some(rule.to_string()),
+    )
+    .await?;
+    context
+        .say(format!("Veto start rule set to `{}`", rule))
+        .await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "organiser_check",
+    rename = "game",
+    description_localized("en-US", "Set whether the tournament is played in CS:GO or CS2")
+)]
+pub(crate) async fn set_game(
+    context: Context<'_>,
+    #[description = "Game"]
+    #[autocomplete = "games"]
+    game: String,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Ok(game) = Game::from_str(&game) else {
+        context.say(format!("Invalid game `{}`", game)).await?;
+        return Ok(());
+    };
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let mut transaction = pool.begin().await?;
+    if !Game::set(&mut transaction, current.id, game).await? {
+        context
+            .say(format!(
+                "`{}` already has matches, the game can only be changed before the first one",
+                current.name
+            ))
+            .await?;
+        return Ok(());
+    }
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::TournamentSettingsChanged,
+        format!("{} game", current.name),
+        None,
+        Some(game.to_string()),
+    )
+    .await?;
+    context
+        .say(format!("Game set to `{}`", game.label()))
+        .await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "organiser_check",
+    rename = "server_selection",
+    description_localized(
+        "en-US",
+        "Set whether teams ban server locations or the fairest location is picked automatically"
+    )
+)]
+pub(crate) async fn set_server_selection(
+    context: Context<'_>,
+    #[description = "Server selection"]
+    #[autocomplete = "server_selections"]
+    server_selection: String,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Ok(server_selection) = ServerSelection::from_str(&server_selection) else {
+        context
+            .say(format!("Invalid server selection `{}`", server_selection))
+            .await?;
+        return Ok(());
+    };
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let mut transaction = pool.begin().await?;
+    ServerSelection::set(&mut transaction, current.id, server_selection).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::TournamentSettingsChanged,
+        format!("{} server selection", current.name),
+        None,
+        Some(server_selection.to_string()),
+    )
+    .await?;
+    context
+        .say(format!("Server selection set to `{}`", server_selection))
+        .await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "organiser_check",
+    rename = "sides",
+    description_localized("en-US", "Set how starting sides are chosen, i.e. knife round")
+)]
+pub(crate) async fn side_selection(
+    context: Context<'_>,
+    #[description = "Side selection"]
+    #[autocomplete = "side_selections"]
+    side_selection: String,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Ok(side_selection) = SideSelection::from_str(&side_selection) else {
+        context
+            .say(format!("Invalid side selection `{}`", side_selection))
+            .await?;
+        return Ok(());
+    };
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let mut transaction = pool.begin().await?;
+    SideSelection::set(&mut transaction, current.id, side_selection).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::TournamentSettingsChanged,
+        format!("{} side selection", current.name),
+        None,
+        Some(side_selection.to_string()),
+    )
+    .await?;
+    context
+        .say(format!("Side selection set to `{}`", side_selection))
+        .await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "referee_check",
+    rename = "sides",
+    description_localized(
+        "en-US",
+        "Override how starting sides are chosen for a match, leave empty to use the tournament setting"
+    )
+)]
+pub(crate) async fn series_side_selection(
+    context: Context<'_>,
+    #[description = "Match Id"] match_id: i32,
+    #[description = "Side selection"]
+    #[autocomplete = "side_selections"]
+    side_selection: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let side_selection = match side_selection {
+        Some(side_selection) => match SideSelection::from_str(&side_selection) {
+            Ok(side_selection) => Some(side_selection),
+            Err(_) => {
+                context
+                    .say(format!("Invalid side selection `{}`", side_selection))
+                    .await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+    if !is_guild_series(pool, guild_id(&context)?, match_id).await? {
+        context
+            .say(format!("Could not find match with id: `{}`", match_id))
+            .await?;
+        return Ok(());
+    }
+    let before = SideSelection::get_by_series(pool, match_id).await?;
+    let mut transaction = pool.begin().await?;
+    if !SideSelection::set_for_series(&mut transaction, match_id, side_selection).await? {
+        context
+            .say(format!("Could not find match with id: `{}`", match_id))
+            .await?;
+        return Ok(());
+    }
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::MatchSidesChanged,
+        format!("#{}", match_id),
+        Some(before.to_string()),
+        Some(
+            side_selection
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| String::from("tournament default")),
+        ),
+    )
+    .await?;
+    match side_selection {
+        Some(side_selection) => {
+            context
+                .say(format!(
+                    "Side selection for match `{}` set to `{}`",
+                    match_id, side_selection
+                ))
+                .await?
+        }
+        None => {
+            context
+                .say(format!(
+                    "Match `{}` now uses the tournament side selection",
+                    match_id
+                ))
+                .await?
+        }
+    };
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "referee_check",
+    rename = "score",
+    description_localized(
+        "en-US",
+        "Enter the score of a map, i.e. when the game server did not report it"
+    )
+)]
+pub(crate) async fn set_score(
+    context: Context<'_>,
+    #[description = "Match Id"] match_id: i32,
+    #[description = "Map number in the series"] map_number: i64,
+    #[description = "Rounds won by team one"] team_one_score: i32,
+    #[description = "Rounds won by team two"] team_two_score: i32,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let series = match is_guild_series(pool, guild_id(&context)?, match_id).await? {
+        true => MatchSeries::get(pool, match_id).await?,
+        false => None,
+    };
+    let Some(series) = series else {
+        context
+            .say(format!("Could not find match with id: `{}`", match_id))
+            .await?;
+        return Ok(());
+    };
+    let mut transaction = pool.begin().await?;
+    if map_number < 1
+        || !set_map_score(
+            &mut transaction,
+            series.id,
+            map_number,
+            team_one_score,
+            team_two_score,
+        )
+        .await?
+    {
+        context
+            .say(format!(
+                "Match `{}` has no map {}, maps are added once the veto is completed",
+                match_id, map_number
+            ))
+            .await?;
+        return Ok(());
+    }
+    let scores = MatchScore::get_by_series(&mut transaction, series.id).await?;
+    let team_one_wins = scores
+        .iter()
+        .filter(|s| s.team_one_score > s.team_two_score)
+        .count();
+    let team_two_wins = scores
+        .iter()
+        .filter(|s| s.team_two_score > s.team_one_score)
+        .count();
+    let wins_needed = match series.series_type {
+        SeriesType::Bo1 => 1,
+        SeriesType::Bo3 => 2,
+        SeriesType::Bo5 => 3,
+    };
+    let mut content = format!(
+        "Map {} of match `{}` set to `{}` - `{}`",
+        map_number, match_id, team_one_score, team_two_score
+    );
+    if team_one_wins >= wins_needed || team_two_wins >= wins_needed {
+        sqlx::query!(
+            "update match_series set completed_at = coalesce(completed_at, now()) where id = $1",
+            series.id
+        )
+        .execute(&mut transaction)
+        .await?;
+        content.push_str(
+            format!(
+                ", series completed `{}` - `{}`",
+                team_one_wins, team_two_wins
+            )
+            .as_str(),
+        );
+    } else {
+        // The corrected scores no longer decide the series, its result is announced again
+        // once they do
+        let reopened = sqlx::query!(
+            "update match_series set completed_at = null, result_announced_at = null
+                 where id = $1 and completed_at is not null",
+            series.id
+        )
+        .execute(&mut transaction)
+        .await?;
+        if reopened.rows_affected() > 0 {
+            content.push_str(", the series is no longer completed");
+        }
+    }
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ScoreEntered,
+        format!("#{} map {}", match_id, map_number),
+        None,
+        Some(format!("{}-{}", team_one_score, team_two_score)),
+    )
+    .await?;
+    context.say(content).await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    check = "referee_check",
+    rename = "start_server",
+    description_localized(
+        "en-US",
+        "Retry starting the server of a match whose server start failed"
+    )
+)]
+pub(crate) async fn start_series(
+    context: Context<'_>,
+    #[description = "Match Id"] match_id: i32,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let guild = guild_id(&context)?;
+    let series = match is_guild_series(pool, guild, match_id).await? {
+        true => MatchSeries::get(pool, match_id).await?,
+        false => None,
+    };
+    let Some(mut series) = series else {
+        context
+            .say(format!("Could not find match with id: `{}`", match_id))
+            .await?;
+        return Ok(());
+    };
+    // taking the failed start keeps the retry button of the setup from starting it as well, it
+    // is only given up once the saved veto could be loaded
+    let mut transaction = pool.begin().await?;
+    if !take_failed_start(&mut transaction, match_id).await? {
+        context
+            .say(format!("Match `{}` has no failed server start", match_id))
             .await?;
-        team.set_inactive(pool).await?;
+        return Ok(());
+    }
+    let mut setup = Setup::from_saved_veto(pool, guild, &series).await?;
+    transaction.commit().await?;
+    let mut msg = context
+        .say("Starting server...")
+        .await?
+        .into_message()
+        .await?;
+    start_series_server(&context, pool, &mut msg, &mut setup, &mut series).await
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "organiser_check",
+    description_localized("en-US", "Configure team registration and check-in")
+)]
+pub(crate) async fn registration(
+    context: Context<'_>,
+    #[description = "Registration opens YYYY-MM-DD"] opens: String,
+    #[description = "Registration closes YYYY-MM-DD"] closes: String,
+    #[description = "Maximum number of teams, further teams are waitlisted"]
+    #[min = 2]
+    max_teams: Option<i32>,
+    #[description = "Minimum roster size (default 5)"]
+    #[min = 1]
+    min_roster: Option<i32>,
+    #[description = "Check-in opens this many hours before the tournament starts (1-168)"]
+    #[min = 1]
+    #[max = 168]
+    check_in_hours: Option<i64>,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let (Some(opens_at), Some(closes_at)) = (parse_date(&opens), parse_date(&closes)) else {
+        context
+            .say("Invalid date format, please use YYYY-MM-DD")
+            .await?;
+        return Ok(());
+    };
+    if closes_at <= opens_at {
+        context
+            .say("Registration must close after it opens")
+            .await?;
+        return Ok(());
+    }
+    let check_in_opens_at = check_in_hours.map(|h| current.started_at - time::Duration::hours(h));
+    let mut transaction = pool.begin().await?;
+    RegistrationSettings::set(
+        &mut transaction,
+        current.id,
+        opens_at,
+        closes_at,
+        check_in_opens_at,
+        max_teams,
+        min_roster.unwrap_or(5),
+    )
+    .await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::TournamentSettingsChanged,
+        format!("{} registration", current.name),
+        None,
+        Some(format!("{} - {}", opens, closes)),
+    )
+    .await?;
+    context.say("Registration settings updated.").await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "caster_check",
+    rename = "participants",
+    description_localized("en-US", "Show registered, waitlisted & dropped teams")
+)]
+pub(crate) async fn show_participants(
+    context: Context<'_>,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let registrations = Registration::get_all(pool, current.id).await?;
+    if registrations.is_empty() {
+        context.say("No teams have registered yet.").await?;
+        return Ok(());
+    }
+    paginate(
+        &context,
+        &format!("{} participants", current.name),
+        &format_registrations(&registrations),
+    )
+    .await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "organiser_check",
+    rename = "add_participant",
+    description_localized("en-US", "Register a team, bypassing the registration checks")
+)]
+pub(crate) async fn add_participant(
+    context: Context<'_>,
+    #[description = "Team"] team: Role,
+    #[description = "Mark team as checked in"] checked_in: Option<bool>,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let Some(team) = Team::get_by_role(pool, team.id.0 as i64).await? else {
+        context.say("Role is not associated with a team").await?;
+        return Ok(());
+    };
+    let mut transaction = pool.begin().await?;
+    Registration::add(
+        &mut transaction,
+        current.id,
+        team.id,
+        RegistrationStatus::Registered,
+    )
+    .await?;
+    TournamentTeam::add(&mut transaction, current.id, team.id).await?;
+    if checked_in.unwrap_or(false) {
+        Registration::check_in(&mut transaction, current.id, team.id).await?;
+    }
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ParticipantAdded,
+        format!("{} in {}", team.name, current.name),
+        None,
+        None,
+    )
+    .await?;
+    context
+        .say(format!("<@&{}> added to {}", team.role, current.name))
+        .await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "organiser_check",
+    rename = "remove_participant",
+    description_localized("en-US", "Drop a team from the tournament")
+)]
+pub(crate) async fn remove_participant(
+    context: Context<'_>,
+    #[description = "Team"] team: Role,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let Some(team) = Team::get_by_role(pool, team.id.0 as i64).await? else {
+        context.say("Role is not associated with a team").await?;
+        return Ok(());
+    };
+    let registration = Registration::get(pool, current.id, team.id).await?;
+    let Some(registration) = registration else {
+        context
+            .say(format!("<@&{}> is not registered", team.role))
+            .await?;
+        return Ok(());
+    };
+    let mut transaction = pool.begin().await?;
+    Registration::set_status(
+        &mut transaction,
+        current.id,
+        team.id,
+        RegistrationStatus::Dropped,
+    )
+    .await?;
+    TournamentTeam::remove(&mut transaction, current.id, team.id).await?;
+    let promoted = match registration.status {
+        RegistrationStatus::Registered => {
+            Registration::promote_waitlisted(&mut transaction, current.id).await?
+        }
+        _ => None,
+    };
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ParticipantRemoved,
+        format!("{} in {}", team.name, current.name),
+        Some(registration.status.to_string()),
+        None,
+    )
+    .await?;
+    let mut content = format!("<@&{}> dropped from {}", team.role, current.name);
+    if let Some(promoted) = promoted {
+        let promoted = Team::get(pool, promoted).await?;
+        content.push_str(format!(", <@&{}> moved up from the waitlist", promoted.role).as_str());
+    }
+    context.say(content).await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    check = "organiser_check",
+    rename = "audit",
+    description_localized("en-US", "Search the audit log")
+)]
+pub(crate) async fn search_audit(
+    context: Context<'_>,
+    #[description = "Only actions by this user"] user: Option<User>,
+    #[description = "Only this action"]
+    #[autocomplete = "audit_actions"]
+    action: Option<String>,
+    #[description = "Target contains, i.e. a team name or match id"] target: Option<String>,
+    #[description = "Number of entries (default 50, max 200)"] limit: Option<i64>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let action = match action {
+        Some(action) => match AuditAction::from_str(&action) {
+            Ok(action) => Some(action),
+            Err(_) => {
+                context.say(format!("Invalid action `{}`", action)).await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+    let entries = AuditEntry::search(
+        pool,
+        guild_id(&context)?,
+        user.map(|u| u.id.0 as i64),
+        action,
+        target,
+        limit.unwrap_or(50).clamp(1, 200),
+    )
+    .await?;
+    if entries.is_empty() {
+        context.say("No audit log entries found").await?;
+        return Ok(());
     }
-    context.say("Tournament ended.").await?;
+    let lines: Vec<String> = entries.iter().map(|e| e.format()).collect();
+    paginate(&context, "Audit log", &lines).await?;
     Ok(())
 }
@@ -0,0 +1,273 @@
+use crate::commands::config::GuildConfig;
+use crate::commands::demos::{create_demo_link_row, StoredDemo};
+use crate::commands::maps::Map;
+use crate::commands::matches::get_series_score;
+use crate::config::SharedConfig;
+use anyhow::Result;
+use matchbot_core::matches::SeriesType::Bo1;
+use matchbot_core::matches::{Match, MatchScore, MatchSeries, SeriesType};
+use matchbot_core::team::Team;
+use serenity::builder::{CreateActionRow, CreateEmbed};
+use serenity::http::Http;
+use serenity::model::id::ChannelId;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::{FromRow, PgExecutor, PgPool};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, FromRow)]
+pub struct ScheduledSeries {
+    pub id: i32,
+    pub series_type: String,
+    pub scheduled_at: OffsetDateTime,
+    pub team_one_role: i64,
+    pub team_two_role: i64,
+    pub tournament: String,
+}
+
+impl ScheduledSeries {
+    /// Unfinished series of `guild` scheduled for the current UTC day
+    pub async fn get_today(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+    ) -> Result<Vec<ScheduledSeries>> {
+        Ok(sqlx::query_as!(
+            ScheduledSeries,
+            r#"select ms.id,
+                      ms.series_type::text as "series_type!",
+                      ms.scheduled_at as "scheduled_at!",
+                      t1.role as team_one_role,
+                      t2.role as team_two_role,
+                      tr.name as tournament
+                 from match_series ms
+                    join teams t1 on t1.id = ms.team_one
+                    join teams t2 on t2.id = ms.team_two
+                    join tournament tr on tr.id = ms.tournament
+                 where t1.guild = $1
+                   and ms.completed_at is null
+                   and (ms.scheduled_at at time zone 'utc')::date = (now() at time zone 'utc')::date
+                 order by ms.scheduled_at, ms.id"#,
+            guild
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+}
+
+/// Posts `embed` to the announcement channel of `guild`, does nothing when none is set
+pub async fn announce(
+    http: impl AsRef<Http>,
+    pool: &PgPool,
+    guild: i64,
+    embed: CreateEmbed,
+    row: Option<CreateActionRow>,
+) -> Result<()> {
+    let Some(channel) = GuildConfig::get(pool, guild).await?.announcement_channel else {
+        return Ok(());
+    };
+    ChannelId(channel as u64)
+        .send_message(http, |m| {
+            m.set_embed(embed);
+            if let Some(row) = row {
+                m.components(|c| c.add_action_row(row));
+            }
+            m
+        })
+        .await?;
+    Ok(())
+}
+
+pub fn series_created_embed(
+    team_one: &Team,
+    team_two: &Team,
+    series_type: SeriesType,
+    tournament: &str,
+    scheduled_at: Option<OffsetDateTime>,
+) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    embed
+        .title(format!("New match: {} vs {}", team_one.name, team_two.name))
+        .description(format!("<@&{}> vs <@&{}>", team_one.role, team_two.role))
+        .field("Format", series_type.to_string(), true)
+        .field("Tournament", tournament, true);
+    if let Some(scheduled_at) = scheduled_at {
+        embed.field(
+            "Scheduled",
+            format!("<t:{}:F>", scheduled_at.unix_timestamp()),
+            true,
+        );
+    }
+    embed
+}
+
+/// Final score, map breakdown & demo links of a finished series
+pub async fn series_result_embed(
+    pool: &PgPool,
+    guild: i64,
+    series: &MatchSeries,
+    demo_url: Option<&str>,
+) -> Result<(CreateEmbed, Option<CreateActionRow>)> {
+    let team_one = Team::get(pool, series.team_one).await?;
+    let team_two = Team::get(pool, series.team_two).await?;
+    let matches = Match::get_by_series(pool, series.id).await?;
+    let maps = Map::get_all(pool, guild, false).await?;
+    let scores = MatchScore::get_by_series(pool, series.id).await?;
+    let (team_one_score, team_two_score) = get_series_score(&scores, series.series_type);
+    let mut s = String::new();
+    for (i, m) in matches.iter().enumerate() {
+        let Some(score) = scores.iter().find(|s| s.match_id == m.id) else {
+            continue;
+        };
+        if score.team_one_score == 0 && score.team_two_score == 0 {
+            continue;
+        }
+        let Some(map) = maps.iter().find(|map| map.id == m.map) else {
+            continue;
+        };
+        s.push_str(format!("{}. `{}`", i + 1, map.label()).as_str());
+        if series.series_type != Bo1 {
+            s.push_str(
+                format!(
+                    " **`{}`** - **`{}`**",
+                    score.team_one_score, score.team_two_score
+                )
+                .as_str(),
+            );
+        }
+        s.push('\n');
+    }
+    let winner = if team_one_score > team_two_score {
+        &team_one
+    } else {
+        &team_two
+    };
+    let mut embed = CreateEmbed::default();
+    embed
+        .title(format!(
+            "{} {} - {} {}",
+            team_one.name, team_one_score, team_two_score, team_two.name
+        ))
+        .description(format!("<@&{}> won `#{}`\n\n{}", winner.role, series.id, s));
+    let demos = StoredDemo::get_by_series(pool, series.id).await?;
+    let row = create_demo_link_row(demo_url, &demos);
+    Ok((embed, row))
+}
+
+pub fn digest_embed(scheduled: &[ScheduledSeries]) -> CreateEmbed {
+    let mut s = String::new();
+    for series in scheduled {
+        let line = format!(
+            "<t:{}:t> <@&{}> vs <@&{}> `{}` - {} (`#{}`)\n",
+            series.scheduled_at.unix_timestamp(),
+            series.team_one_role,
+            series.team_two_role,
+            series.series_type,
+            series.tournament,
+            series.id
+        );
+        // Embed descriptions are limited to 4096 characters
+        if s.len() + line.len() > 4000 {
+            s.push('…');
+            break;
+        }
+        s.push_str(line.as_str());
+    }
+    let mut embed = CreateEmbed::default();
+    embed.title("Today's matches").description(s);
+    embed
+}
+
+/// Marks finished series as announced and posts their results
+/// Claims the earliest finished series whose result was not announced yet, returns its id & guild.
+/// Claimed before posting so a result is never posted twice
+async fn claim_result(pool: &PgPool) -> Result<Option<(i32, Option<i64>)>> {
+    let claimed = sqlx::query!(
+        r#"update match_series ms
+               set result_announced_at = now()
+               from teams t
+               where t.id = ms.team_one
+                 and ms.id = (select id
+                                from match_series
+                                where completed_at is not null
+                                  and result_announced_at is null
+                                order by completed_at, id
+                                limit 1 for update skip locked)
+               returning ms.id as "id!", t.guild as "guild?""#
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(claimed.map(|c| (c.id, c.guild)))
+}
+
+async fn announce_result(
+    http: &Http,
+    pool: &PgPool,
+    config: &SharedConfig,
+    series: i32,
+    guild: i64,
+) -> Result<()> {
+    let Some(series) = MatchSeries::get(pool, series).await? else {
+        return Ok(());
+    };
+    let demo_url = config.read().unwrap().demo_url();
+    let (embed, row) = series_result_embed(pool, guild, &series, demo_url.as_deref()).await?;
+    announce(http, pool, guild, embed, row).await
+}
+
+/// Announces finished series one at a time, a failed announcement does not hold up the others
+async fn announce_results(http: &Http, pool: &PgPool, config: &SharedConfig) -> Result<()> {
+    while let Some((series, guild)) = claim_result(pool).await? {
+        let Some(guild) = guild else {
+            continue;
+        };
+        if let Err(err) = announce_result(http, pool, config, series, guild).await {
+            log::error!("{:#?}", err);
+        }
+    }
+    Ok(())
+}
+
+/// Posts the digest once a day, at the configured hour, to every guild with an announcement channel
+async fn post_digests(http: &Http, pool: &PgPool) -> Result<()> {
+    let due = sqlx::query_scalar!(
+        "update guild_config
+             set digest_posted_at = now()
+             where announcement_channel is not null
+               and extract(hour from now() at time zone 'utc') >= digest_hour
+               and (digest_posted_at is null
+                 or (digest_posted_at at time zone 'utc')::date < (now() at time zone 'utc')::date)
+             returning guild"
+    )
+    .fetch_all(pool)
+    .await?;
+    for guild in due {
+        let scheduled = match ScheduledSeries::get_today(pool, guild).await {
+            Ok(scheduled) => scheduled,
+            Err(err) => {
+                log::error!("{:#?}", err);
+                continue;
+            }
+        };
+        if scheduled.is_empty() {
+            continue;
+        }
+        if let Err(err) = announce(http, pool, guild, digest_embed(&scheduled), None).await {
+            log::error!("{:#?}", err);
+        }
+    }
+    Ok(())
+}
+
+/// Periodically announces finished series and posts the daily digest of scheduled matches
+pub async fn announcement_task(http: Arc<Http>, pool: PgPool, config: SharedConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        if let Err(err) = announce_results(&http, &pool, &config).await {
+            log::error!("{:#?}", err);
+        }
+        if let Err(err) = post_digests(&http, &pool).await {
+            log::error!("{:#?}", err);
+        }
+    }
+}
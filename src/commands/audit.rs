@@ -0,0 +1,164 @@
+use crate::commands::config::{guild_id, GuildConfig};
+use crate::Context;
+use anyhow::Result;
+use serenity::model::id::ChannelId;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::{FromRow, PgExecutor, Postgres, Transaction};
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// Mutating actions recorded in the audit log
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum AuditAction {
+    MatchCreated,
+    MatchDeleted,
+    MatchSidesChanged,
+    ScoreEntered,
+    TournamentCreated,
+    TournamentEnded,
+    TournamentSettingsChanged,
+    ParticipantAdded,
+    ParticipantRemoved,
+    MapAdded,
+    MapEnabled,
+    MapDisabled,
+    ServerTemplateAdded,
+    ServerTemplateDeleted,
+    ServerPingChanged,
+    TeamCreated,
+    TeamCarriedOver,
+    TeamDisbanded,
+    TeamLeft,
+    TeamJoined,
+    TeamKicked,
+    TeamCaptainTransferred,
+    TeamRegistered,
+    TeamWithdrawn,
+    TeamRegionChanged,
+    SteamIdChanged,
+    ConfigChanged,
+    GsltRevoked,
+}
+
+#[derive(Debug, FromRow, Clone)]
+pub struct AuditEntry {
+    pub id: i32,
+    pub guild: i64,
+    pub actor: i64,
+    pub action: String,
+    pub target: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub created_at: OffsetDateTime,
+}
+
+impl AuditEntry {
+    pub async fn add(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        actor: i64,
+        action: AuditAction,
+        target: &str,
+        before: Option<String>,
+        after: Option<String>,
+    ) -> Result<AuditEntry> {
+        Ok(sqlx::query_as!(
+            AuditEntry,
+            "insert into audit_log (guild, actor, action, target, before, after)
+                 values ($1, $2, $3, $4, $5, $6)
+                 returning *",
+            guild,
+            actor,
+            action.to_string(),
+            target,
+            before,
+            after,
+        )
+        .fetch_one(executor)
+        .await?)
+    }
+    /// Latest entries of `guild`, newest first, `target` matches case-insensitively on a substring
+    pub async fn search(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        actor: Option<i64>,
+        action: Option<AuditAction>,
+        target: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>> {
+        Ok(sqlx::query_as!(
+            AuditEntry,
+            "select *
+                 from audit_log
+                 where guild = $1
+                   and ($2::int8 is null or actor = $2)
+                   and ($3::text is null or action = $3)
+                   and ($4::text is null or target ilike '%' || $4 || '%')
+                 order by created_at desc, id desc
+                 limit $5",
+            guild,
+            actor,
+            action.map(|a| a.to_string()),
+            target,
+            limit,
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+    pub fn format(&self) -> String {
+        let mut s = format!(
+            "<t:{}:f> `{}` by <@{}>: {}",
+            self.created_at.unix_timestamp(),
+            self.action,
+            self.actor,
+            self.target
+        );
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => {
+                s.push_str(format!(" (`{}` → `{}`)", before, after).as_str())
+            }
+            (Some(before), None) => s.push_str(format!(" (was `{}`)", before).as_str()),
+            (None, Some(after)) => s.push_str(format!(" (`{}`)", after).as_str()),
+            (None, None) => {}
+        }
+        s
+    }
+}
+
+/// Records an action of the command author in the transaction that made it, commits the
+/// transaction and posts the entry to the guild's audit channel if set
+pub(crate) async fn audit_log(
+    context: &Context<'_>,
+    mut transaction: Transaction<'_, Postgres>,
+    action: AuditAction,
+    target: impl AsRef<str>,
+    before: Option<String>,
+    after: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let guild = guild_id(context)?;
+    let entry = AuditEntry::add(
+        &mut transaction,
+        guild,
+        context.author().id.0 as i64,
+        action,
+        target.as_ref(),
+        before,
+        after,
+    )
+    .await?;
+    transaction.commit().await?;
+    if let Some(channel) = GuildConfig::get(pool, guild).await?.audit_channel {
+        // The action already happened, a missing channel permission should not fail the command
+        if let Err(err) = ChannelId(channel as u64)
+            .send_message(context.serenity_context(), |m| {
+                m.content(entry.format())
+                    .allowed_mentions(|mentions| mentions.empty_parse())
+            })
+            .await
+        {
+            log::error!("{:#?}", err);
+        }
+    }
+    Ok(())
+}
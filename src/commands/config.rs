@@ -0,0 +1,654 @@
+use crate::commands::audit::{audit_log, AuditAction};
+use crate::commands::permissions::{BotRole, GuildRole};
+use crate::commands::setup::DathostConfig;
+use crate::config::{dathost_password_env, Config};
+use crate::Context;
+use anyhow::{anyhow, bail, Error, Result};
+use futures::{Stream, StreamExt};
+use poise::command;
+use serenity::model::channel::Channel;
+use serenity::model::guild::Role;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::{FromRow, PgExecutor, PgPool};
+use std::env;
+use std::str::FromStr;
+use strum::IntoEnumIterator;
+
+/// Settings of a single Discord guild, unset values fall back to the bot config
+#[derive(Debug, FromRow, Clone, Default)]
+pub struct GuildConfig {
+    pub guild: i64,
+    pub dathost_user: Option<String>,
+    /// Name of the environment variable holding the Dathost password
+    pub dathost_password_env: Option<String>,
+    pub match_end_webhook_url: Option<String>,
+    pub round_end_webhook_url: Option<String>,
+    pub series_end_webhook_url: Option<String>,
+    pub announcement_channel: Option<i64>,
+    pub audit_channel: Option<i64>,
+    /// Hour (UTC) the daily digest of scheduled matches is posted
+    pub digest_hour: i32,
+    pub digest_posted_at: Option<OffsetDateTime>,
+    /// Whether the API serves the Discord & Steam ids of team members
+    pub api_rosters: bool,
+}
+
+impl GuildConfig {
+    pub async fn get(executor: impl PgExecutor<'_>, guild: i64) -> Result<GuildConfig> {
+        let config = sqlx::query_as!(
+            GuildConfig,
+            "select * from guild_config where guild = $1",
+            guild
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(config.unwrap_or(GuildConfig {
+            guild,
+            ..Default::default()
+        }))
+    }
+    pub async fn set_dathost(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        user: String,
+        password_env: String,
+    ) -> Result<()> {
+        sqlx::query!(
+            "insert into guild_config (guild, dathost_user, dathost_password_env) values ($1, $2, $3)
+                 on conflict (guild) do update set dathost_user         = excluded.dathost_user,
+                                                   dathost_password_env = excluded.dathost_password_env",
+            guild,
+            user,
+            password_env,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+    pub async fn set_webhooks(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        match_end: Option<String>,
+        round_end: Option<String>,
+        series_end: Option<String>,
+    ) -> Result<()> {
+        sqlx::query!(
+            "insert into guild_config (guild, match_end_webhook_url, round_end_webhook_url, series_end_webhook_url)
+                 values ($1, $2, $3, $4)
+                 on conflict (guild) do update set match_end_webhook_url  = excluded.match_end_webhook_url,
+                                                   round_end_webhook_url  = excluded.round_end_webhook_url,
+                                                   series_end_webhook_url = excluded.series_end_webhook_url",
+            guild,
+            match_end,
+            round_end,
+            series_end,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+    pub async fn set_announcement_channel(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        channel: Option<i64>,
+        digest_hour: Option<i32>,
+    ) -> Result<()> {
+        sqlx::query!(
+            "insert into guild_config (guild, announcement_channel, digest_hour) values ($1, $2, coalesce($3, 9))
+                 on conflict (guild) do update set announcement_channel = excluded.announcement_channel,
+                                                   digest_hour          = coalesce($3, guild_config.digest_hour)",
+            guild,
+            channel,
+            digest_hour,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+    pub async fn set_audit_channel(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        channel: Option<i64>,
+    ) -> Result<()> {
+        sqlx::query!(
+            "insert into guild_config (guild, audit_channel) values ($1, $2)
+                 on conflict (guild) do update set audit_channel = excluded.audit_channel",
+            guild,
+            channel,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+    pub async fn set_api_rosters(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        api_rosters: bool,
+    ) -> Result<()> {
+        sqlx::query!(
+            "insert into guild_config (guild, api_rosters) values ($1, $2)
+                 on conflict (guild) do update set api_rosters = excluded.api_rosters",
+            guild,
+            api_rosters,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+    /// Whether the guild may fall back to the bot's `[dathost]` account
+    pub fn uses_default_dathost(&self, config: &Config) -> bool {
+        self.dathost_user.is_none() && config.dathost.guilds.contains(&(self.guild as u64))
+    }
+    /// Dathost credentials of the guild, the bot's `[dathost]` settings are only used for
+    /// guilds listed in `dathost.guilds`
+    pub fn dathost_config(&self, config: &Config) -> Result<DathostConfig> {
+        if let (Some(user), Some(password_env)) = (&self.dathost_user, &self.dathost_password_env) {
+            let expected = dathost_password_env(self.guild);
+            if password_env != &expected {
+                bail!(
+                    "Dathost password variable has to be `{}`, use `/config dathost`",
+                    expected
+                );
+            }
+            let password = env::var(&expected)
+                .map_err(|_| anyhow!("missing Dathost password variable `{}`", expected))?;
+            return Ok(DathostConfig {
+                user: user.clone(),
+                password,
+            });
+        }
+        match (&config.dathost.user, &config.dathost.password) {
+            (Some(user), Some(password)) if self.uses_default_dathost(config) => {
+                Ok(DathostConfig {
+                    user: user.clone(),
+                    password: password.clone(),
+                })
+            }
+            _ => bail!("no Dathost account configured, use `/config dathost`"),
+        }
+    }
+    pub fn match_end_webhook_url(&self, config: &Config) -> String {
+        webhook_url(&self.match_end_webhook_url, &config.webhooks.match_end)
+    }
+    pub fn round_end_webhook_url(&self, config: &Config) -> String {
+        webhook_url(&self.round_end_webhook_url, &config.webhooks.round_end)
+    }
+    pub fn series_end_webhook_url(&self, config: &Config) -> String {
+        webhook_url(&self.series_end_webhook_url, &config.webhooks.series_end)
+    }
+}
+
+fn webhook_url(configured: &Option<String>, default: &Option<String>) -> String {
+    configured
+        .clone()
+        .or_else(|| default.clone())
+        .unwrap_or_default()
+}
+
+/// Guild the command was used in, only valid for `guild_only` commands
+pub(crate) fn guild_id(context: &Context<'_>) -> Result<i64> {
+    Ok(context
+        .guild_id()
+        .ok_or_else::<Error, _>(|| unreachable!())?
+        .0 as i64)
+}
+
+/// Assigns data created before multi-guild support to `guild`
+pub async fn claim_unowned(pool: &PgPool, guild: i64) -> Result<()> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query!("update teams set guild = $1 where guild is null", guild)
+        .execute(&mut transaction)
+        .await?;
+    sqlx::query!("update maps set guild = $1 where guild is null", guild)
+        .execute(&mut transaction)
+        .await?;
+    sqlx::query!(
+        "update tournament set guild = $1 where guild is null and id <> 0",
+        guild
+    )
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query!(
+        "update server_templates set guild = $1 where guild is null",
+        guild
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands(
+        "show",
+        "dathost",
+        "webhooks",
+        "announcements",
+        "audit",
+        "api",
+        "roles"
+    )
+)]
+pub(crate) async fn config(_context: Context<'_>) -> Result<()> {
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("en-US", "Show the settings of this server")
+)]
+pub(crate) async fn show(context: Context<'_>) -> Result<()> {
+    let pool = &context.data().pool;
+    let config = GuildConfig::get(pool, guild_id(&context)?).await?;
+    let or_default = |value: &Option<String>| match value {
+        Some(value) => format!("`{}`", value),
+        None => String::from("_default_"),
+    };
+    let default_dathost = config.uses_default_dathost(&context.data().config.read().unwrap());
+    let mut s = String::new();
+    if config.dathost_user.is_some() || default_dathost {
+        s.push_str(format!("Dathost user: {}\n", or_default(&config.dathost_user)).as_str());
+        s.push_str(
+            format!(
+                "Dathost password variable: {}\n",
+                or_default(&config.dathost_password_env)
+            )
+            .as_str(),
+        );
+    } else {
+        s.push_str("Dathost user: _none_\n");
+    }
+    s.push_str(
+        format!(
+            "Match end webhook: {}\n",
+            or_default(&config.match_end_webhook_url)
+        )
+        .as_str(),
+    );
+    s.push_str(
+        format!(
+            "Round end webhook: {}\n",
+            or_default(&config.round_end_webhook_url)
+        )
+        .as_str(),
+    );
+    s.push_str(
+        format!(
+            "Series end webhook: {}\n",
+            or_default(&config.series_end_webhook_url)
+        )
+        .as_str(),
+    );
+    match config.announcement_channel {
+        Some(channel) => s.push_str(
+            format!(
+                "Announcement channel: <#{}>, daily digest at {:02}:00 UTC\n",
+                channel, config.digest_hour
+            )
+            .as_str(),
+        ),
+        None => s.push_str("Announcement channel: _none_\n"),
+    }
+    match config.audit_channel {
+        Some(channel) => s.push_str(format!("Audit log channel: <#{}>\n", channel).as_str()),
+        None => s.push_str("Audit log channel: _none_\n"),
+    }
+    s.push_str(
+        format!(
+            "API team rosters: {}\n",
+            if config.api_rosters {
+                "public"
+            } else {
+                "hidden"
+            }
+        )
+        .as_str(),
+    );
+    context.say(s).await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("en-US", "Set the Dathost account used to start servers")
+)]
+pub(crate) async fn dathost(
+    context: Context<'_>,
+    #[description = "Dathost account email"] user: String,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let guild = guild_id(&context)?;
+    // the password is read from a variable tied to the guild, so admins can't point at another
+    // guild's account or probe which variables are set
+    let password_env = dathost_password_env(guild);
+    let before = GuildConfig::get(pool, guild).await?.dathost_user;
+    let mut transaction = pool.begin().await?;
+    GuildConfig::set_dathost(&mut transaction, guild, user.clone(), password_env.clone()).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ConfigChanged,
+        "dathost account",
+        before,
+        Some(user),
+    )
+    .await?;
+    context
+        .say(format!(
+            "Dathost account updated, the password is read from `{}` on the bot host",
+            password_env
+        ))
+        .await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("en-US", "Set the webhooks called by game servers")
+)]
+pub(crate) async fn webhooks(
+    context: Context<'_>,
+    #[description = "Match end webhook url"] match_end: Option<String>,
+    #[description = "Round end webhook url"] round_end: Option<String>,
+    #[description = "Series end webhook url"] series_end: Option<String>,
+) -> Result<()> {
+    let mut transaction = context.data().pool.begin().await?;
+    GuildConfig::set_webhooks(
+        &mut transaction,
+        guild_id(&context)?,
+        match_end,
+        round_end,
+        series_end,
+    )
+    .await?;
+    // Webhook urls may contain secrets, only record that they changed
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ConfigChanged,
+        "webhooks",
+        None,
+        None,
+    )
+    .await?;
+    context.say("Webhooks updated").await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("en-US", "Set the channel for tournament announcements")
+)]
+pub(crate) async fn announcements(
+    context: Context<'_>,
+    #[description = "Channel, leave empty to disable announcements"] channel: Option<Channel>,
+    #[description = "Hour (UTC) the daily digest of scheduled matches is posted, defaults to 9"]
+    #[min = 0]
+    #[max = 23]
+    digest_hour: Option<i32>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let channel = channel.map(|c| c.id().0 as i64);
+    let guild = guild_id(&context)?;
+    let before = GuildConfig::get(pool, guild).await?.announcement_channel;
+    let mut transaction = pool.begin().await?;
+    GuildConfig::set_announcement_channel(&mut transaction, guild, channel, digest_hour).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ConfigChanged,
+        "announcement channel",
+        before.map(|c| c.to_string()),
+        channel.map(|c| c.to_string()),
+    )
+    .await?;
+    match channel {
+        Some(channel) => {
+            context
+                .say(format!("Announcements will be posted in <#{}>", channel))
+                .await?
+        }
+        None => context.say("Announcements disabled").await?,
+    };
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("en-US", "Set the channel the audit log is posted to")
+)]
+pub(crate) async fn audit(
+    context: Context<'_>,
+    #[description = "Channel, leave empty to only keep the log in the database"] channel: Option<
+        Channel,
+    >,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let channel = channel.map(|c| c.id().0 as i64);
+    let guild = guild_id(&context)?;
+    let before = GuildConfig::get(pool, guild).await?.audit_channel;
+    let mut transaction = pool.begin().await?;
+    GuildConfig::set_audit_channel(&mut transaction, guild, channel).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ConfigChanged,
+        "audit log channel",
+        before.map(|c| c.to_string()),
+        channel.map(|c| c.to_string()),
+    )
+    .await?;
+    match channel {
+        Some(channel) => {
+            context
+                .say(format!("The audit log will be posted in <#{}>", channel))
+                .await?
+        }
+        None => context.say("Audit log channel removed").await?,
+    };
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized(
+        "en-US",
+        "Set whether the API serves the Discord & Steam ids of team members"
+    )
+)]
+pub(crate) async fn api(
+    context: Context<'_>,
+    #[description = "Serve team rosters with Discord & Steam ids (default off)"] rosters: bool,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let guild = guild_id(&context)?;
+    let before = GuildConfig::get(pool, guild).await?.api_rosters;
+    let mut transaction = pool.begin().await?;
+    GuildConfig::set_api_rosters(&mut transaction, guild, rosters).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ConfigChanged,
+        "api rosters",
+        Some(before.to_string()),
+        Some(rosters.to_string()),
+    )
+    .await?;
+    match rosters {
+        true => context.say("The API now serves team rosters").await?,
+        false => context.say("Team rosters are hidden from the API").await?,
+    };
+    Ok(())
+}
+
+async fn bot_roles<'a>(_ctx: Context<'_>, partial: &'a str) -> impl Stream<Item = String> + 'a {
+    let role_strings: Vec<String> = BotRole::iter().map(|r| r.to_string()).collect();
+    futures::stream::iter(role_strings)
+        .filter(move |name| futures::future::ready(name.starts_with(partial)))
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    subcommands("add_role", "remove_role", "show_roles")
+)]
+pub(crate) async fn roles(_context: Context<'_>) -> Result<()> {
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    rename = "add",
+    description_localized("en-US", "Grant a bot role to members of a Discord role")
+)]
+pub(crate) async fn add_role(
+    context: Context<'_>,
+    #[description = "Bot role"]
+    #[autocomplete = "bot_roles"]
+    bot_role: String,
+    #[description = "Discord role"] role: Role,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Ok(bot_role) = BotRole::from_str(&bot_role) else {
+        context
+            .say(format!("Invalid bot role `{}`", bot_role))
+            .await?;
+        return Ok(());
+    };
+    let mut transaction = pool.begin().await?;
+    if !GuildRole::add(
+        &mut transaction,
+        guild_id(&context)?,
+        role.id.0 as i64,
+        bot_role,
+    )
+    .await?
+    {
+        context
+            .say(format!(
+                "<@&{}> already has the `{}` bot role",
+                role.id, bot_role
+            ))
+            .await?;
+        return Ok(());
+    }
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ConfigChanged,
+        format!("{} bot role", bot_role),
+        None,
+        Some(format!("+{}", role.name)),
+    )
+    .await?;
+    context
+        .say(format!(
+            "<@&{}> granted the `{}` bot role",
+            role.id, bot_role
+        ))
+        .await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    rename = "remove",
+    description_localized("en-US", "Revoke a bot role from a Discord role")
+)]
+pub(crate) async fn remove_role(
+    context: Context<'_>,
+    #[description = "Bot role"]
+    #[autocomplete = "bot_roles"]
+    bot_role: String,
+    #[description = "Discord role"] role: Role,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Ok(bot_role) = BotRole::from_str(&bot_role) else {
+        context
+            .say(format!("Invalid bot role `{}`", bot_role))
+            .await?;
+        return Ok(());
+    };
+    let mut transaction = pool.begin().await?;
+    if !GuildRole::remove(
+        &mut transaction,
+        guild_id(&context)?,
+        role.id.0 as i64,
+        bot_role,
+    )
+    .await?
+    {
+        context
+            .say(format!(
+                "<@&{}> does not have the `{}` bot role",
+                role.id, bot_role
+            ))
+            .await?;
+        return Ok(());
+    }
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::ConfigChanged,
+        format!("{} bot role", bot_role),
+        None,
+        Some(format!("-{}", role.name)),
+    )
+    .await?;
+    context
+        .say(format!(
+            "`{}` bot role revoked from <@&{}>",
+            bot_role, role.id
+        ))
+        .await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    rename = "show",
+    description_localized("en-US", "Show which Discord roles hold which bot roles")
+)]
+pub(crate) async fn show_roles(context: Context<'_>) -> Result<()> {
+    let pool = &context.data().pool;
+    let guild_roles = GuildRole::get_all(pool, guild_id(&context)?).await?;
+    let mut s = String::new();
+    for bot_role in BotRole::iter() {
+        let roles: Vec<String> = guild_roles
+            .iter()
+            .filter(|r| r.bot_role == bot_role)
+            .map(|r| format!("<@&{}>", r.role))
+            .collect();
+        if roles.is_empty() {
+            s.push_str(format!("`{}`: _none_\n", bot_role).as_str());
+        } else {
+            s.push_str(format!("`{}`: {}\n", bot_role, roles.join(", ")).as_str());
+        }
+    }
+    s.push_str("Server administrators can always use every command.");
+    context.say(s).await?;
+    Ok(())
+}
@@ -0,0 +1,321 @@
+use crate::commands::config::GuildConfig;
+use crate::commands::tournament::Game;
+use crate::config::SharedConfig;
+use crate::metrics::track_request;
+use crate::storage::{demo_client, DemoStorage, DemoStream};
+use anyhow::{anyhow, Result};
+use futures::TryStreamExt;
+use serenity::builder::{CreateActionRow, CreateButton};
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::channel::ReactionType;
+use sqlx::{FromRow, PgExecutor, PgPool};
+use std::sync::Arc;
+use std::time::Duration;
+use urlencoding::encode;
+
+/// Uploads tried per demo before it is given up on, the server may have been deleted
+const MAX_DEMO_ATTEMPTS: i32 = 5;
+
+/// Demo of a played map that was uploaded to the demo storage
+#[derive(Debug, FromRow, Clone)]
+pub struct StoredDemo {
+    pub match_id: i32,
+    pub map_name: String,
+    pub demo_key: String,
+}
+
+impl StoredDemo {
+    /// Stored demos of the series in map order
+    pub async fn get_by_series(
+        executor: impl PgExecutor<'_>,
+        match_series: i32,
+    ) -> Result<Vec<StoredDemo>> {
+        Ok(sqlx::query_as!(
+            StoredDemo,
+            r#"select m.id as match_id, mp.name as map_name, m.demo_key as "demo_key!"
+                 from match m
+                    join maps mp on mp.id = m.map
+                 where m.match_series = $1 and m.demo_key is not null
+                 order by m.id"#,
+            match_series
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+    pub(crate) async fn set_key(
+        executor: impl PgExecutor<'_>,
+        match_id: i32,
+        demo_key: &str,
+    ) -> Result<()> {
+        sqlx::query!(
+            "update match set demo_key = $2 where id = $1",
+            match_id,
+            demo_key
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Demo buttons of the stored demos, `None` when there are none
+pub(crate) fn create_demo_link_row(
+    demo_url: Option<&str>,
+    demos: &[StoredDemo],
+) -> Option<CreateActionRow> {
+    let demo_url = demo_url?;
+    if demos.is_empty() {
+        return None;
+    }
+    let mut ar = CreateActionRow::default();
+    for demo in demos {
+        let mut demo_button = CreateButton::default();
+        demo_button.label(&demo.map_name);
+        demo_button.style(ButtonStyle::Link);
+        demo_button.emoji(ReactionType::Unicode("📺".parse().unwrap()));
+        demo_button.url(format!(
+            "{}/{}",
+            demo_url.trim_end_matches('/'),
+            demo.demo_key
+        ));
+        ar.add_button(demo_button);
+    }
+    Some(ar)
+}
+
+/// Played map of a finished Dathost series whose demo has not been stored yet
+struct PendingDemo {
+    match_id: i32,
+    match_series: i32,
+    guild: i64,
+    game: Game,
+    dathost_match: String,
+    server_id: String,
+    bo1: bool,
+    map_number: i32,
+}
+
+async fn get_pending(executor: impl PgExecutor<'_>) -> Result<Vec<PendingDemo>> {
+    Ok(sqlx::query_as!(
+        PendingDemo,
+        r#"select p.match_id as "match_id!", p.match_series as "match_series!", p.guild as "guild!",
+                  p.game as "game!: Game", p.dathost_match as "dathost_match!",
+                  p.server_id as "server_id!", p.bo1 as "bo1!", p.map_number as "map_number!"
+             from (select m.id as match_id, m.match_series, m.demo_key, m.demo_attempts, t.guild,
+                          tr.game, ms.dathost_match, ms.series_type = 'bo1' as bo1,
+                          (select s.server_id
+                             from servers s
+                             where s.match_series = ms.id
+                             order by s.id desc
+                             limit 1) as server_id,
+                          row_number() over (partition by m.match_series order by m.id)::int4 as map_number
+                     from match m
+                        join match_series ms on ms.id = m.match_series
+                        join tournament tr on tr.id = ms.tournament
+                        join teams t on t.id = ms.team_one
+                        join match_scores sc on sc.match_id = m.id
+                     where ms.completed_at is not null
+                       and ms.dathost_match is not null
+                       and (sc.team_one_score > 0 or sc.team_two_score > 0)) p
+             where p.demo_key is null
+               and p.demo_attempts < $1
+               and p.server_id not like 'rcon:%'
+             order by p.match_id
+             limit 20"#,
+        MAX_DEMO_ATTEMPTS
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Downloads the demo from the Dathost server and uploads it, returns the object key
+async fn store_demo(
+    pool: &PgPool,
+    config: &SharedConfig,
+    storage: &dyn DemoStorage,
+    demo: &PendingDemo,
+) -> Result<String> {
+    let config = config.read().unwrap().clone();
+    let dathost_config = GuildConfig::get(pool, demo.guild)
+        .await?
+        .dathost_config(&config)?;
+    let map_number = (!demo.bo1).then_some(demo.map_number as usize);
+    let file = demo.game.demo_name(&demo.dathost_match, map_number);
+    let url = format!(
+        "https://dathost.net/api/0.1/game-servers/{}/files/{}",
+        encode(&demo.server_id),
+        encode(&file)
+    );
+    let response = track_request(
+        "dathost",
+        demo_client()?
+            .get(url)
+            .basic_auth(&dathost_config.user, Some(&dathost_config.password)),
+    )
+    .await?
+    .error_for_status()?;
+    let length = response
+        .content_length()
+        .ok_or_else(|| anyhow!("Dathost sent the demo without its length"))?;
+    let body = response.bytes_stream().map_err(anyhow::Error::from);
+    upload_demo(
+        storage,
+        demo.match_series,
+        demo.match_id,
+        length,
+        Box::pin(body),
+    )
+    .await
+}
+
+/// Uploads the demo of a map, returns the object key
+pub(crate) async fn upload_demo(
+    storage: &dyn DemoStorage,
+    match_series: i32,
+    match_id: i32,
+    length: u64,
+    body: DemoStream,
+) -> Result<String> {
+    let key = format!("{}/{}.dem", match_series, match_id);
+    storage.put(&key, length, body).await?;
+    Ok(key)
+}
+
+async fn store_pending_demos(
+    pool: &PgPool,
+    config: &SharedConfig,
+    storage: &dyn DemoStorage,
+) -> Result<()> {
+    for demo in get_pending(pool).await? {
+        match store_demo(pool, config, storage, &demo).await {
+            Ok(key) => {
+                StoredDemo::set_key(pool, demo.match_id, &key).await?;
+                log::info!("Stored demo of match {} as {}", demo.match_id, key);
+            }
+            Err(err) => {
+                sqlx::query!(
+                    "update match set demo_attempts = demo_attempts + 1 where id = $1",
+                    demo.match_id
+                )
+                .execute(pool)
+                .await?;
+                log::warn!("Storing demo of match {} failed: {:#}", demo.match_id, err);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Uploads demos of finished series to the demo storage
+pub async fn demo_task(pool: PgPool, config: SharedConfig, storage: Arc<dyn DemoStorage>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(5 * 60));
+    loop {
+        interval.tick().await;
+        if let Err(err) = store_pending_demos(&pool, &config, storage.as_ref()).await {
+            log::error!("{:#?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connect::tests::create_series;
+    use crate::storage::tests::{demo_stream, MemoryStorage};
+
+    /// Played map of `series` with a score, `None` for a map that was not played
+    async fn add_map(pool: &PgPool, series: i32, score: Option<(i32, i32)>) -> i32 {
+        let match_id: i32 = sqlx::query_scalar(
+            "insert into match (match_series, map, picked_by, start_ct_team, start_t_team)
+                 select ms.id, (select id from maps limit 1), ms.team_one, ms.team_one, ms.team_two
+                   from match_series ms
+                   where ms.id = $1
+                 returning id",
+        )
+        .bind(series)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let (team_one_score, team_two_score) = score.unwrap_or((0, 0));
+        sqlx::query(
+            "insert into match_scores (match_id, team_one_score, team_two_score)
+                 values ($1, $2, $3)",
+        )
+        .bind(match_id)
+        .bind(team_one_score)
+        .bind(team_two_score)
+        .execute(pool)
+        .await
+        .unwrap();
+        match_id
+    }
+
+    async fn finish_series(pool: &PgPool, series: i32, server_id: &str) {
+        sqlx::query(
+            "update match_series set completed_at = now(), dathost_match = 'dathost-match'
+                 where id = $1",
+        )
+        .bind(series)
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "insert into servers (match_series, server_id, hostname, game_port, gotv_port)
+                 values ($1, $2, '1.2.3.4', 27015, 27020)",
+        )
+        .bind(series)
+        .bind(server_id)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn pending_demos_are_played_maps_of_finished_dathost_series(pool: PgPool) {
+        let series = create_series(&pool).await;
+        let first = add_map(&pool, series, Some((16, 10))).await;
+        let second = add_map(&pool, series, Some((16, 14))).await;
+        let unplayed = add_map(&pool, series, None).await;
+        let stored = add_map(&pool, series, Some((10, 16))).await;
+        StoredDemo::set_key(&pool, stored, "1/1.dem").await.unwrap();
+        let running = create_series(&pool).await;
+        add_map(&pool, running, Some((16, 0))).await;
+        let self_hosted = create_series(&pool).await;
+        add_map(&pool, self_hosted, Some((16, 0))).await;
+        finish_series(&pool, series, "dathost-id").await;
+        finish_series(&pool, self_hosted, "rcon:1").await;
+        sqlx::query("update teams set guild = 1")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let pending = get_pending(&pool).await.unwrap();
+        let ids: Vec<i32> = pending.iter().map(|d| d.match_id).collect();
+        // self-hosted servers upload their demos themselves
+        assert_eq!(ids, vec![first, second]);
+        assert!(!ids.contains(&unplayed));
+        assert_eq!(pending[1].map_number, 2);
+        assert_eq!(pending[1].server_id, "dathost-id");
+        assert_eq!(pending[1].dathost_match, "dathost-match");
+
+        sqlx::query("update match set demo_attempts = $2 where id = $1")
+            .bind(first)
+            .bind(MAX_DEMO_ATTEMPTS)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let pending = get_pending(&pool).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].match_id, second);
+    }
+
+    #[tokio::test]
+    async fn uploads_demos_in_chunks() {
+        let storage = MemoryStorage::default();
+        let key = upload_demo(&storage, 12, 34, 8, demo_stream(&[b"HL2D", b"EMO\0"]))
+            .await
+            .unwrap();
+        assert_eq!(key, "12/34.dem");
+        assert_eq!(storage.get(&key).await.unwrap(), b"HL2DEMO\0");
+    }
+}
@@ -0,0 +1,532 @@
+use crate::steam::{GameServerAccount, GameServerAccounts};
+use anyhow::{bail, Result};
+use sqlx::types::time::OffsetDateTime;
+use sqlx::{FromRow, PgExecutor, PgPool};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Idle tokens unused for this long are deleted from the Steam account
+const STALE_AFTER_DAYS: i32 = 30;
+/// Memo of the accounts the pool creates, accounts created per match before the pool carry
+/// the series id instead
+const ACCOUNT_MEMO: &str = "matchbot";
+
+/// Game server login token, in use while its series is unfinished
+#[derive(Debug, FromRow, Clone)]
+pub struct GsltToken {
+    pub id: i32,
+    pub steamid: String,
+    pub app_id: i32,
+    pub login_token: String,
+    pub server_id: Option<String>,
+    pub match_series: Option<i32>,
+    pub last_used_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+    pub in_use: bool,
+}
+
+impl GsltToken {
+    pub async fn get_all(executor: impl PgExecutor<'_>) -> Result<Vec<GsltToken>> {
+        Ok(sqlx::query_as!(
+            GsltToken,
+            r#"select g.id, g.steamid, g.app_id, g.login_token, g.server_id, g.match_series,
+                      g.last_used_at, g.created_at,
+                      (ms.id is not null and ms.completed_at is null) as "in_use!"
+                 from gslt_tokens g
+                    left join match_series ms on ms.id = g.match_series
+                 order by g.last_used_at desc nulls last, g.id"#
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+    async fn add(
+        executor: impl PgExecutor<'_>,
+        steamid: &str,
+        app_id: i32,
+        login_token: &str,
+        server_id: &str,
+        match_series: i32,
+    ) -> Result<()> {
+        sqlx::query!(
+            "insert into gslt_tokens (steamid, app_id, login_token, server_id, match_series, last_used_at)
+                 values ($1, $2, $3, $4, $5, now())",
+            steamid,
+            app_id,
+            login_token,
+            server_id,
+            match_series,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+    /// Takes an account the bot created before the pool existed into it, in use when its series
+    /// is unfinished
+    async fn adopt(
+        executor: impl PgExecutor<'_>,
+        account: &GameServerAccount,
+        match_series: Option<i32>,
+    ) -> Result<()> {
+        sqlx::query!(
+            "insert into gslt_tokens (steamid, app_id, login_token, match_series)
+                 values ($1, $2, $3, (select id from match_series where id = $4))
+                 on conflict (steamid) do nothing",
+            account.steamid,
+            account.appid as i32,
+            account.login_token,
+            match_series,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+    /// Token already assigned to the server
+    async fn get_by_server(
+        executor: impl PgExecutor<'_>,
+        server_id: &str,
+    ) -> Result<Option<String>> {
+        Ok(sqlx::query_scalar!(
+            "select login_token from gslt_tokens where server_id = $1 order by id limit 1",
+            server_id,
+        )
+        .fetch_optional(executor)
+        .await?)
+    }
+    /// Assigns the least recently used idle token of `app_id` to the server
+    async fn claim_idle(
+        executor: impl PgExecutor<'_>,
+        app_id: i32,
+        server_id: &str,
+        match_series: i32,
+    ) -> Result<Option<String>> {
+        Ok(sqlx::query_scalar!(
+            "update gslt_tokens
+                 set server_id = $2, match_series = $3, last_used_at = now()
+                 where id = (select g.id
+                               from gslt_tokens g
+                                  left join match_series ms on ms.id = g.match_series
+                               where g.app_id = $1
+                                 and (g.match_series is null or ms.completed_at is not null)
+                               order by g.last_used_at nulls first
+                               limit 1
+                               for update of g skip locked)
+                 returning login_token",
+            app_id,
+            server_id,
+            match_series,
+        )
+        .fetch_optional(executor)
+        .await?)
+    }
+    async fn set_login_token(
+        executor: impl PgExecutor<'_>,
+        steamid: &str,
+        login_token: &str,
+    ) -> Result<()> {
+        sqlx::query!(
+            "update gslt_tokens set login_token = $2 where steamid = $1",
+            steamid,
+            login_token,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+    pub async fn delete(executor: impl PgExecutor<'_>, steamid: &str) -> Result<bool> {
+        let result = sqlx::query!("delete from gslt_tokens where steamid = $1", steamid)
+            .execute(executor)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    /// Whether the token has been idle longer than `STALE_AFTER_DAYS`
+    fn is_stale(&self) -> bool {
+        let last_used = self.last_used_at.unwrap_or(self.created_at);
+        !self.in_use
+            && OffsetDateTime::now_utc() - last_used > time::Duration::days(STALE_AFTER_DAYS.into())
+    }
+}
+
+/// Token for the server of `match_series`, an idle token is reused before a new one is created.
+/// A retry gets the token the server was already assigned
+pub async fn assign_gslt(
+    pool: &PgPool,
+    steam: &dyn GameServerAccounts,
+    app_id: u32,
+    server_id: &str,
+    match_series: i32,
+) -> Result<String> {
+    if let Some(token) = GsltToken::get_by_server(pool, server_id).await? {
+        return Ok(token);
+    }
+    if let Some(token) = GsltToken::claim_idle(pool, app_id as i32, server_id, match_series).await?
+    {
+        return Ok(token);
+    }
+    let account = steam.create_account(app_id, ACCOUNT_MEMO).await?;
+    GsltToken::add(
+        pool,
+        &account.steamid,
+        app_id as i32,
+        &account.login_token,
+        server_id,
+        match_series,
+    )
+    .await?;
+    log::info!("Created game server account {}", account.steamid);
+    Ok(account.login_token)
+}
+
+/// Makes the token of a server that failed to start idle again
+pub async fn release_gslt(executor: impl PgExecutor<'_>, server_id: &str) -> Result<()> {
+    sqlx::query!(
+        "update gslt_tokens set server_id = null, match_series = null where server_id = $1",
+        server_id,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Deletes the account of an idle token from Steam and the pool
+pub async fn revoke_gslt(
+    executor: impl PgExecutor<'_>,
+    steam: &dyn GameServerAccounts,
+    token: &GsltToken,
+) -> Result<()> {
+    steam.delete_account(&token.steamid).await?;
+    GsltToken::delete(executor, &token.steamid).await?;
+    Ok(())
+}
+
+/// Change to the pool found by comparing it with the accounts listed by Steam
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SyncAction {
+    /// The account was deleted on Steam, only the pool entry is removed
+    Forget(String),
+    /// The idle account is stale and deleted from Steam & the pool
+    Revoke(String),
+    /// The idle account's token expired and gets a new one
+    Reset(String),
+    /// Account created by the bot that the pool doesn't know yet, with the series it was created for
+    Adopt(GameServerAccount, Option<i32>),
+}
+
+/// Series id of an account created per match before the pool existed, `Some(None)` for pool
+/// accounts and `None` for accounts the bot didn't create
+fn bot_account_series(account: &GameServerAccount) -> Option<Option<i32>> {
+    if account.memo == ACCOUNT_MEMO {
+        return Some(None);
+    }
+    if !account.memo.is_empty() && account.memo.chars().all(|c| c.is_ascii_digit()) {
+        return Some(account.memo.parse().ok());
+    }
+    None
+}
+
+/// Compares the pool with the accounts listed by Steam. Fails instead of forgetting every token
+/// when Steam lists no accounts at all, which an API hiccup looks the same as
+fn sync_actions(tokens: &[GsltToken], accounts: &[GameServerAccount]) -> Result<Vec<SyncAction>> {
+    if accounts.is_empty() && !tokens.is_empty() {
+        bail!(
+            "Steam listed no game server accounts while the pool has {}, not syncing",
+            tokens.len()
+        );
+    }
+    let by_steamid: HashMap<&str, &GameServerAccount> =
+        accounts.iter().map(|a| (a.steamid.as_str(), a)).collect();
+    let mut actions = Vec::new();
+    for token in tokens {
+        let steamid = token.steamid.clone();
+        match by_steamid.get(token.steamid.as_str()) {
+            None => actions.push(SyncAction::Forget(steamid)),
+            Some(account) if account.is_deleted => actions.push(SyncAction::Forget(steamid)),
+            Some(_) if token.is_stale() => actions.push(SyncAction::Revoke(steamid)),
+            Some(account) if account.is_expired && !token.in_use => {
+                actions.push(SyncAction::Reset(steamid))
+            }
+            Some(_) => {}
+        }
+    }
+    let known: HashSet<&str> = tokens.iter().map(|t| t.steamid.as_str()).collect();
+    for account in accounts {
+        if account.is_deleted || known.contains(account.steamid.as_str()) {
+            continue;
+        }
+        if let Some(match_series) = bot_account_series(account) {
+            actions.push(SyncAction::Adopt(account.clone(), match_series));
+        }
+    }
+    Ok(actions)
+}
+
+/// Syncs the pool with the Steam account: forgets deleted accounts, resets expired tokens,
+/// deletes stale ones and adopts accounts the bot created before the pool, which are deleted
+/// once stale like any other
+async fn maintain_gslt_pool(pool: &PgPool, steam: &dyn GameServerAccounts) -> Result<()> {
+    let accounts = steam.list_accounts().await?;
+    let tokens = GsltToken::get_all(pool).await?;
+    for action in sync_actions(&tokens, &accounts)? {
+        match action {
+            SyncAction::Forget(steamid) => {
+                GsltToken::delete(pool, &steamid).await?;
+                log::info!("Removed game server account {} deleted on Steam", steamid);
+            }
+            SyncAction::Revoke(steamid) => {
+                steam.delete_account(&steamid).await?;
+                GsltToken::delete(pool, &steamid).await?;
+                log::info!("Deleted stale game server account {}", steamid);
+            }
+            SyncAction::Reset(steamid) => {
+                let login_token = steam.reset_login_token(&steamid).await?;
+                GsltToken::set_login_token(pool, &steamid, &login_token).await?;
+                log::info!("Reset expired login token of {}", steamid);
+            }
+            SyncAction::Adopt(account, match_series) => {
+                GsltToken::adopt(pool, &account, match_series).await?;
+                log::info!("Adopted game server account {}", account.steamid);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn gslt_task(pool: PgPool, steam: Arc<dyn GameServerAccounts>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+    loop {
+        interval.tick().await;
+        if let Err(err) = maintain_gslt_pool(&pool, steam.as_ref()).await {
+            log::error!("{:#?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Steam account kept in memory
+    #[derive(Default)]
+    struct FakeAccounts {
+        accounts: Mutex<Vec<GameServerAccount>>,
+        /// Makes `list_accounts` answer without any account, like a failed response would
+        list_nothing: bool,
+    }
+
+    impl FakeAccounts {
+        fn with(accounts: Vec<GameServerAccount>) -> FakeAccounts {
+            FakeAccounts {
+                accounts: Mutex::new(accounts),
+                list_nothing: false,
+            }
+        }
+        fn steamids(&self) -> Vec<String> {
+            let accounts = self.accounts.lock().unwrap();
+            accounts.iter().map(|a| a.steamid.clone()).collect()
+        }
+    }
+
+    #[async_trait]
+    impl GameServerAccounts for FakeAccounts {
+        async fn create_account(&self, app_id: u32, memo: &str) -> Result<GameServerAccount> {
+            let mut accounts = self.accounts.lock().unwrap();
+            let n = accounts.len() + 1;
+            let created = account(&format!("created-{}", n), memo);
+            let created = GameServerAccount {
+                appid: app_id,
+                ..created
+            };
+            accounts.push(created.clone());
+            Ok(created)
+        }
+        async fn list_accounts(&self) -> Result<Vec<GameServerAccount>> {
+            if self.list_nothing {
+                return Ok(vec![]);
+            }
+            Ok(self.accounts.lock().unwrap().clone())
+        }
+        async fn reset_login_token(&self, steamid: &str) -> Result<String> {
+            let mut accounts = self.accounts.lock().unwrap();
+            let Some(account) = accounts.iter_mut().find(|a| a.steamid == steamid) else {
+                bail!("unknown account {}", steamid);
+            };
+            account.login_token = format!("{}-reset", account.login_token);
+            account.is_expired = false;
+            Ok(account.login_token.clone())
+        }
+        async fn delete_account(&self, steamid: &str) -> Result<()> {
+            self.accounts
+                .lock()
+                .unwrap()
+                .retain(|a| a.steamid != steamid);
+            Ok(())
+        }
+    }
+
+    fn account(steamid: &str, memo: &str) -> GameServerAccount {
+        GameServerAccount {
+            steamid: steamid.to_string(),
+            appid: 730,
+            login_token: format!("token-{}", steamid),
+            memo: memo.to_string(),
+            is_deleted: false,
+            is_expired: false,
+        }
+    }
+
+    fn token(steamid: &str, idle_days: i64, in_use: bool) -> GsltToken {
+        let last_used_at = OffsetDateTime::now_utc() - time::Duration::days(idle_days);
+        GsltToken {
+            id: 1,
+            steamid: steamid.to_string(),
+            app_id: 730,
+            login_token: format!("token-{}", steamid),
+            server_id: None,
+            match_series: None,
+            last_used_at: Some(last_used_at),
+            created_at: last_used_at,
+            in_use,
+        }
+    }
+
+    #[test]
+    fn refuses_to_sync_against_an_empty_list() {
+        assert!(sync_actions(&[token("1", 0, false)], &[]).is_err());
+        assert_eq!(sync_actions(&[], &[]).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn syncs_tokens_with_their_accounts() {
+        let tokens = [
+            token("fresh", 1, false),
+            token("missing", 1, false),
+            token("deleted", 1, false),
+            token("stale", 40, false),
+            token("expired", 1, false),
+            token("expired-in-use", 1, true),
+        ];
+        let accounts = [
+            account("fresh", ACCOUNT_MEMO),
+            GameServerAccount {
+                is_deleted: true,
+                ..account("deleted", ACCOUNT_MEMO)
+            },
+            account("stale", ACCOUNT_MEMO),
+            GameServerAccount {
+                is_expired: true,
+                ..account("expired", ACCOUNT_MEMO)
+            },
+            GameServerAccount {
+                is_expired: true,
+                ..account("expired-in-use", ACCOUNT_MEMO)
+            },
+        ];
+        assert_eq!(
+            sync_actions(&tokens, &accounts).unwrap(),
+            vec![
+                SyncAction::Forget(String::from("missing")),
+                SyncAction::Forget(String::from("deleted")),
+                SyncAction::Revoke(String::from("stale")),
+                SyncAction::Reset(String::from("expired")),
+            ]
+        );
+    }
+
+    #[test]
+    fn adopts_only_accounts_created_by_the_bot() {
+        let accounts = [
+            account("pool", ACCOUNT_MEMO),
+            account("per-match", "42"),
+            account("personal", "my community server"),
+            account("unnamed", ""),
+            GameServerAccount {
+                is_deleted: true,
+                ..account("deleted", "43")
+            },
+        ];
+        assert_eq!(
+            sync_actions(&[], &accounts).unwrap(),
+            vec![
+                SyncAction::Adopt(accounts[0].clone(), None),
+                SyncAction::Adopt(accounts[1].clone(), Some(42)),
+            ]
+        );
+    }
+
+    #[sqlx::test]
+    async fn reuses_released_tokens(pool: PgPool) {
+        let steam = FakeAccounts::default();
+        let series = create_series(&pool).await;
+        let first = assign_gslt(&pool, &steam, 730, "server-1", series)
+            .await
+            .unwrap();
+        let second = assign_gslt(&pool, &steam, 730, "server-2", series)
+            .await
+            .unwrap();
+        assert_ne!(first, second);
+        release_gslt(&pool, "server-1").await.unwrap();
+        let third = assign_gslt(&pool, &steam, 730, "server-3", series)
+            .await
+            .unwrap();
+        assert_eq!(third, first);
+        assert_eq!(steam.steamids().len(), 2);
+    }
+
+    #[sqlx::test]
+    async fn maintains_the_pool(pool: PgPool) {
+        let steam = FakeAccounts::with(vec![
+            account("old-match", "999999"),
+            account("personal", "my community server"),
+        ]);
+        let series = create_series(&pool).await;
+        let login_token = assign_gslt(&pool, &steam, 730, "server-1", series)
+            .await
+            .unwrap();
+        steam.accounts.lock().unwrap()[2].is_expired = true;
+        release_gslt(&pool, "server-1").await.unwrap();
+
+        maintain_gslt_pool(&pool, &steam).await.unwrap();
+        let tokens = GsltToken::get_all(&pool).await.unwrap();
+        let mut steamids: Vec<&str> = tokens.iter().map(|t| t.steamid.as_str()).collect();
+        steamids.sort();
+        assert_eq!(steamids, vec!["created-3", "old-match"]);
+        let reset = tokens.iter().find(|t| t.steamid == "created-3").unwrap();
+        assert_eq!(reset.login_token, format!("{}-reset", login_token));
+        // the series of the old account's memo doesn't exist
+        let adopted = tokens.iter().find(|t| t.steamid == "old-match").unwrap();
+        assert_eq!(adopted.match_series, None);
+
+        let failing = FakeAccounts {
+            list_nothing: true,
+            ..FakeAccounts::default()
+        };
+        assert!(maintain_gslt_pool(&pool, &failing).await.is_err());
+        assert_eq!(GsltToken::get_all(&pool).await.unwrap().len(), 2);
+    }
+
+    async fn create_series(pool: &PgPool) -> i32 {
+        let team_one: i32 = sqlx::query_scalar(
+            "insert into teams (role, name, captain) values (1, 'one', 1) returning id",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let team_two: i32 = sqlx::query_scalar(
+            "insert into teams (role, name, captain) values (2, 'two', 2) returning id",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        sqlx::query_scalar(
+            "insert into match_series (team_one, team_two, series_type, created_at)
+                 values ($1, $2, 'bo1', now()) returning id",
+        )
+        .bind(team_one)
+        .bind(team_two)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+}
@@ -0,0 +1,323 @@
+use crate::demo_parser::{analyse, DemoAnalysis, DemoParser, ParsedDemo};
+use crate::storage::DemoStorage;
+use anyhow::Result;
+use serenity::builder::CreateEmbed;
+use serenity::http::Http;
+use serenity::model::id::ChannelId;
+use sqlx::{PgExecutor, PgPool};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Parses tried per demo before it is given up on
+const MAX_PARSE_ATTEMPTS: i32 = 3;
+
+/// Stored demo that has not been parsed yet
+struct UnparsedDemo {
+    match_id: i32,
+    demo_key: String,
+    map_name: String,
+    thread: Option<i64>,
+}
+
+async fn get_unparsed(executor: impl PgExecutor<'_>) -> Result<Vec<UnparsedDemo>> {
+    Ok(sqlx::query_as!(
+        UnparsedDemo,
+        r#"select m.id as match_id, m.demo_key as "demo_key!", mp.name as map_name, ms.thread
+             from match m
+                join match_series ms on ms.id = m.match_series
+                join maps mp on mp.id = m.map
+             where m.demo_key is not null
+               and m.demo_parsed_at is null
+               and m.demo_parse_attempts < $1
+             order by m.id
+             limit 5"#,
+        MAX_PARSE_ATTEMPTS
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Writes rounds, kills & player stats of the match and marks its demo as parsed
+async fn save_demo_stats(
+    pool: &PgPool,
+    match_id: i32,
+    demo: &ParsedDemo,
+    analysis: &DemoAnalysis,
+) -> Result<()> {
+    let mut transaction = pool.begin().await?;
+    for round in demo.unique_rounds() {
+        let clutch = analysis.clutches.iter().find(|c| c.round == round.number);
+        sqlx::query!(
+            "insert into demo_rounds (match_id, round, winner_side, reason, clutch_steamid, clutch_opponents)
+                 values ($1, $2, $3, $4, $5, $6)
+                 on conflict (match_id, round) do update
+                     set winner_side = excluded.winner_side, reason = excluded.reason,
+                         clutch_steamid = excluded.clutch_steamid,
+                         clutch_opponents = excluded.clutch_opponents",
+            match_id,
+            round.number,
+            round.winner.as_str(),
+            round.reason,
+            clutch.map(|c| c.steamid as i64),
+            clutch.map(|c| c.opponents),
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+    for kill in &demo.kills {
+        sqlx::query!(
+            "insert into demo_kills (match_id, round, tick, attacker, victim, weapon, headshot)
+                 values ($1, $2, $3, $4, $5, $6, $7)",
+            match_id,
+            kill.round,
+            kill.tick,
+            kill.attacker.map(|a| a as i64),
+            kill.victim as i64,
+            kill.weapon,
+            kill.headshot,
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+    for (steamid, stats) in &analysis.players {
+        sqlx::query!(
+            "insert into demo_player_stats
+                 (match_id, steamid, kills, deaths, headshots, triples, quads, aces, clutches)
+                 values ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            match_id,
+            *steamid as i64,
+            stats.kills,
+            stats.deaths,
+            stats.headshots,
+            stats.triples,
+            stats.quads,
+            stats.aces,
+            stats.clutches,
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+    sqlx::query!(
+        "update match set demo_parsed_at = now() where id = $1",
+        match_id
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Discord mentions of players that linked their SteamID
+async fn player_mentions(
+    executor: impl PgExecutor<'_>,
+    analysis: &DemoAnalysis,
+) -> Result<HashMap<u64, String>> {
+    let steamids: Vec<i64> = analysis.players.keys().map(|s| *s as i64).collect();
+    Ok(sqlx::query!(
+        "select steam, discord from steam_ids where steam = any($1)",
+        &steamids
+    )
+    .fetch_all(executor)
+    .await?
+    .into_iter()
+    .map(|row| (row.steam as u64, format!("<@{}>", row.discord)))
+    .collect())
+}
+
+/// Top fragger, aces & clutches of a map, `None` when nobody got a kill
+pub fn highlights_embed(
+    map_name: &str,
+    analysis: &DemoAnalysis,
+    mentions: &HashMap<u64, String>,
+) -> Option<CreateEmbed> {
+    let name = |steamid: &u64| {
+        mentions
+            .get(steamid)
+            .cloned()
+            .unwrap_or_else(|| format!("`{}`", steamid))
+    };
+    let (top_fragger, top_stats) = analysis
+        .players
+        .iter()
+        .filter(|(_, stats)| stats.kills > 0)
+        .max_by_key(|(_, stats)| (stats.kills, -stats.deaths))?;
+    let mut s = format!(
+        "🏆 Top fragger: {} `{}-{}` ({} headshots)\n",
+        name(top_fragger),
+        top_stats.kills,
+        top_stats.deaths,
+        top_stats.headshots
+    );
+    for ace in &analysis.aces {
+        s.push_str(format!("⭐ Ace: {} in round {}\n", name(&ace.steamid), ace.round).as_str());
+    }
+    for clutch in &analysis.clutches {
+        s.push_str(
+            format!(
+                "🔥 Clutch: {} 1v{} in round {}\n",
+                name(&clutch.steamid),
+                clutch.opponents,
+                clutch.round
+            )
+            .as_str(),
+        );
+    }
+    let mut embed = CreateEmbed::default();
+    embed
+        .title(format!("Match highlights - {}", map_name))
+        .description(s);
+    Some(embed)
+}
+
+/// Parses the stored demo and saves its stats
+async fn parse_and_save(
+    pool: &PgPool,
+    storage: &dyn DemoStorage,
+    parser: &dyn DemoParser,
+    demo: &UnparsedDemo,
+) -> Result<DemoAnalysis> {
+    // a file of its own per parse, which is removed when dropped
+    let file = tempfile::Builder::new()
+        .prefix("matchbot-")
+        .suffix(".dem")
+        .tempfile()?;
+    tokio::fs::write(file.path(), storage.get(&demo.demo_key).await?).await?;
+    let parsed = parser.parse(file.path()).await?;
+    let analysis = analyse(&parsed);
+    save_demo_stats(pool, demo.match_id, &parsed, &analysis).await?;
+    Ok(analysis)
+}
+
+async fn parse_demo(
+    http: &Http,
+    pool: &PgPool,
+    storage: &dyn DemoStorage,
+    parser: &dyn DemoParser,
+    demo: &UnparsedDemo,
+) -> Result<()> {
+    let analysis = parse_and_save(pool, storage, parser, demo).await?;
+    let Some(thread) = demo.thread else {
+        return Ok(());
+    };
+    let mentions = player_mentions(pool, &analysis).await?;
+    if let Some(embed) = highlights_embed(&demo.map_name, &analysis, &mentions) {
+        // the stats are saved, a deleted thread should not get the demo parsed again
+        if let Err(err) = ChannelId(thread as u64)
+            .send_message(http, |m| m.set_embed(embed))
+            .await
+        {
+            log::error!("{:#?}", err);
+        }
+    }
+    Ok(())
+}
+
+async fn parse_stored_demos(
+    http: &Http,
+    pool: &PgPool,
+    storage: &dyn DemoStorage,
+    parser: &dyn DemoParser,
+) -> Result<()> {
+    for demo in get_unparsed(pool).await? {
+        if let Err(err) = parse_demo(http, pool, storage, parser, &demo).await {
+            sqlx::query!(
+                "update match set demo_parse_attempts = demo_parse_attempts + 1 where id = $1",
+                demo.match_id
+            )
+            .execute(pool)
+            .await?;
+            log::warn!("Parsing demo of match {} failed: {:#}", demo.match_id, err);
+        }
+    }
+    Ok(())
+}
+
+/// Parses stored demos into round, kill & player stats and posts the highlights in the
+/// series thread
+pub async fn highlights_task(
+    http: Arc<Http>,
+    pool: PgPool,
+    storage: Arc<dyn DemoStorage>,
+    parser: Arc<dyn DemoParser>,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(5 * 60));
+    loop {
+        interval.tick().await;
+        if let Err(err) = parse_stored_demos(&http, &pool, storage.as_ref(), parser.as_ref()).await
+        {
+            log::error!("{:#?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connect::tests::create_series;
+    use crate::demo_parser::tests::{kill, round, CannedParser};
+    use crate::demo_parser::Side;
+    use crate::storage::tests::{demo_stream, MemoryStorage};
+
+    async fn stored_demo(pool: &PgPool, storage: &MemoryStorage) -> i32 {
+        let series = create_series(pool).await;
+        let match_id: i32 = sqlx::query_scalar(
+            "insert into match (match_series, map, picked_by, start_ct_team, start_t_team, demo_key)
+                 select ms.id, (select id from maps limit 1), ms.team_one, ms.team_one, ms.team_two,
+                        ms.id || '/demo.dem'
+                   from match_series ms
+                   where ms.id = $1
+                 returning id",
+        )
+        .bind(series)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        storage
+            .put(&format!("{}/demo.dem", series), 4, demo_stream(&[b"HL2D"]))
+            .await
+            .unwrap();
+        match_id
+    }
+
+    #[sqlx::test]
+    async fn saves_the_stats_of_unparsed_demos(pool: PgPool) {
+        let storage = MemoryStorage::default();
+        let match_id = stored_demo(&pool, &storage).await;
+        let parser = CannedParser(ParsedDemo {
+            rounds: vec![
+                round(1, Side::T, "ct_eliminated"),
+                // replayed after a backup was restored
+                round(1, Side::Ct, "t_eliminated"),
+                round(2, Side::Ct, "bomb_defused"),
+            ],
+            kills: vec![kill(1, 10, 1, 6), kill(2, 10, 7, 2)],
+        });
+
+        let unparsed = get_unparsed(&pool).await.unwrap();
+        assert_eq!(unparsed.len(), 1);
+        let analysis = parse_and_save(&pool, &storage, &parser, &unparsed[0])
+            .await
+            .unwrap();
+        assert_eq!(analysis.players[&1].kills, 1);
+
+        let rounds: Vec<(i32, String)> = sqlx::query_as(
+            "select round, winner_side from demo_rounds where match_id = $1 order by round",
+        )
+        .bind(match_id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            rounds,
+            vec![(1, String::from("ct")), (2, String::from("ct"))]
+        );
+        let kills: i64 = sqlx::query_scalar("select count(*) from demo_kills where match_id = $1")
+            .bind(match_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(kills, 2);
+        assert!(get_unparsed(&pool).await.unwrap().is_empty());
+    }
+}
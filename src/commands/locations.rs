@@ -0,0 +1,140 @@
+use super::super::Context;
+use crate::commands::admin::ServerTemplates;
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use sqlx::{FromRow, PgExecutor};
+
+/// Assumed ping from a location to players of its own region when no hint has been added
+const SAME_REGION_PING: i32 = 20;
+/// Assumed ping from a location to players of any other region
+const OTHER_REGION_PING: i32 = 100;
+
+/// Expected ping from players of `region` to `location`
+#[derive(Debug, FromRow, Clone)]
+pub struct ServerPing {
+    pub location: String,
+    pub region: String,
+    pub ping: i32,
+}
+
+impl ServerPing {
+    pub async fn get_all(executor: impl PgExecutor<'_>, guild: i64) -> Result<Vec<ServerPing>> {
+        Ok(sqlx::query_as!(
+            ServerPing,
+            "select location, region, ping
+                 from server_pings
+                 where guild = $1
+                 order by location, region",
+            guild,
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+    /// Locations & regions are matched case-insensitively, the latest spelling is kept
+    pub async fn set(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        location: &str,
+        region: &str,
+        ping: i32,
+    ) -> Result<()> {
+        sqlx::query!(
+            "insert into server_pings (guild, location, region, ping) values ($1, $2, $3, $4)
+                 on conflict (guild, lower(location), lower(region))
+                 do update set location = excluded.location, region = excluded.region, ping = excluded.ping",
+            guild,
+            location,
+            region,
+            ping,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Regions of server locations and ping hints in `guild`
+pub async fn get_regions(executor: impl PgExecutor<'_>, guild: i64) -> Result<Vec<String>> {
+    Ok(sqlx::query_scalar!(
+        r#"select region as "region!" from server_templates where guild = $1 and region is not null
+           union
+           select region from rcon_servers where guild = $1 and region is not null
+           union
+           select region from server_pings where guild = $1
+           order by 1"#,
+        guild,
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+pub(crate) async fn regions<'a>(
+    context: Context<'_>,
+    partial: &'a str,
+) -> impl Stream<Item = String> + 'a {
+    let guild = context.guild_id().map_or(0, |g| g.0 as i64);
+    let regions = match get_regions(&context.data().pool, guild).await {
+        Ok(regions) => regions,
+        Err(err) => {
+            log::error!("{:#?}", err);
+            vec![]
+        }
+    };
+    futures::stream::iter(regions).filter(move |name| {
+        futures::future::ready(name.to_lowercase().starts_with(&partial.to_lowercase()))
+    })
+}
+
+pub async fn get_team_region(executor: impl PgExecutor<'_>, team: i32) -> Result<Option<String>> {
+    Ok(
+        sqlx::query_scalar!("select region from team_regions where team = $1", team)
+            .fetch_optional(executor)
+            .await?,
+    )
+}
+
+pub async fn set_team_region(executor: impl PgExecutor<'_>, team: i32, region: &str) -> Result<()> {
+    sqlx::query!(
+        "insert into team_regions (team, region) values ($1, $2)
+             on conflict (team) do update set region = excluded.region",
+        team,
+        region,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Expected ping from players of `region` to `server`, ping hints win over the region guess
+fn expected_ping(server: &ServerTemplates, pings: &[ServerPing], region: &str) -> i32 {
+    pings
+        .iter()
+        .find(|p| {
+            p.location.eq_ignore_ascii_case(&server.location)
+                && p.region.eq_ignore_ascii_case(region)
+        })
+        .map(|p| p.ping)
+        .unwrap_or_else(|| match &server.region {
+            Some(r) if r.eq_ignore_ascii_case(region) => SAME_REGION_PING,
+            _ => OTHER_REGION_PING,
+        })
+}
+
+/// The location where the expected pings of both teams differ the least, ties go to the lowest
+/// ping and then the order of `servers`. Teams without a home region are left out
+pub fn pick_fairest<'a>(
+    servers: &'a [ServerTemplates],
+    pings: &[ServerPing],
+    regions: [Option<&str>; 2],
+) -> Option<&'a ServerTemplates> {
+    servers.iter().min_by_key(|server| {
+        let expected: Vec<i32> = regions
+            .iter()
+            .flatten()
+            .map(|region| expected_ping(server, pings, region))
+            .collect();
+        let highest = expected.iter().copied().max().unwrap_or_default();
+        let lowest = expected.iter().copied().min().unwrap_or_default();
+        (highest - lowest, highest)
+    })
+}
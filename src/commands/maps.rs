@@ -1,32 +1,129 @@
+use crate::commands::tournament::Game;
 use anyhow::Result;
 use sqlx::{FromRow, PgExecutor};
 
+/// Map pool of guilds that have not set up their own maps yet
+const DEFAULT_MAPS: [&str; 7] = [
+    "de_inferno",
+    "de_vertigo",
+    "de_overpass",
+    "de_nuke",
+    "de_anubis",
+    "de_mirage",
+    "de_ancient",
+];
+
 #[derive(Debug, FromRow, Clone)]
 pub struct Map {
     pub id: i32,
     pub name: String,
     pub disabled: bool,
+    pub display_name: Option<String>,
+    pub workshop_id: Option<String>,
+    /// Only in the map pool of this game, both games when unset
+    pub game: Option<Game>,
 }
 
 impl Map {
     pub async fn get(executor: impl PgExecutor<'_>, map_id: i32) -> Result<Map> {
-        Ok(
-            sqlx::query_as!(Map, "select * from maps where id = $1", map_id,)
-                .fetch_one(executor)
-                .await?,
+        Ok(sqlx::query_as!(
+            Map,
+            r#"select id, name, disabled, display_name, workshop_id, game as "game: Game"
+                 from maps where id = $1"#,
+            map_id,
         )
+        .fetch_one(executor)
+        .await?)
     }
-    pub async fn get_all(executor: impl PgExecutor<'_>, only_enabled: bool) -> Result<Vec<Map>> {
+    pub async fn get_all(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        only_enabled: bool,
+    ) -> Result<Vec<Map>> {
         if only_enabled {
-            Ok(
-                sqlx::query_as!(Map, "select * from maps where disabled is false",)
-                    .fetch_all(executor)
-                    .await?,
+            Ok(sqlx::query_as!(
+                Map,
+                r#"select id, name, disabled, display_name, workshop_id, game as "game: Game"
+                     from maps where guild = $1 and disabled is false"#,
+                guild,
             )
+            .fetch_all(executor)
+            .await?)
         } else {
-            Ok(sqlx::query_as!(Map, "select * from maps",)
-                .fetch_all(executor)
-                .await?)
+            Ok(sqlx::query_as!(
+                Map,
+                r#"select id, name, disabled, display_name, workshop_id, game as "game: Game"
+                     from maps where guild = $1"#,
+                guild,
+            )
+            .fetch_all(executor)
+            .await?)
+        }
+    }
+    pub async fn add(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        name: &str,
+        display_name: Option<String>,
+        workshop_id: Option<String>,
+        game: Option<Game>,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            "insert into maps (guild, name, display_name, workshop_id, game) values ($1, $2, $3, $4, $5)",
+            guild,
+            name,
+            display_name,
+            workshop_id,
+            game as Option<Game>,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    pub async fn set_disabled(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        name: &str,
+        disabled: bool,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            "update maps set disabled = $3 where guild = $1 and name = $2",
+            guild,
+            name,
+            disabled,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    /// Adds the default map pool for a guild without any maps
+    pub async fn add_defaults(executor: impl PgExecutor<'_>, guild: i64) -> Result<u64> {
+        let names: Vec<String> = DEFAULT_MAPS.iter().map(|m| m.to_string()).collect();
+        let result = sqlx::query!(
+            "insert into maps (guild, name)
+                 select $1, m.name
+                 from unnest($2::text[]) as m(name)
+                 where not exists (select 1 from maps where guild = $1)",
+            guild,
+            &names,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected())
+    }
+    /// Whether the map is in the map pool of `game`
+    pub fn in_pool(&self, game: Game) -> bool {
+        self.game.map_or(true, |g| g == game)
+    }
+    /// Name shown to players in veto menus and setup messages
+    pub fn label(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.name)
+    }
+    /// Map value sent to the game server provider, workshop maps are loaded by id
+    pub fn server_map(&self) -> String {
+        match &self.workshop_id {
+            Some(workshop_id) => format!("workshop/{}", workshop_id),
+            None => self.name.clone(),
         }
     }
 }
@@ -1,23 +1,52 @@
+use crate::commands::config::guild_id;
+use crate::commands::demos::{create_demo_link_row, StoredDemo};
+use crate::commands::maps::Map;
+use crate::commands::pagination::{paginate, paginate_pages, PAGE_ITEMS};
+use crate::commands::timeline::{MapTimeline, MatchRound};
+use crate::commands::tournament::{
+    active_tournaments, count_completed_series, get_completed_series_ids, get_guild_series_ids,
+    get_user_series_ids, is_guild_series, pick_tournament, Game,
+};
 use crate::Context;
 use anyhow::Result;
-use matchbot_core::maps::*;
 use matchbot_core::matches::SeriesType::Bo1;
 use matchbot_core::matches::SeriesType::Bo3;
 use matchbot_core::matches::SeriesType::Bo5;
 use matchbot_core::matches::*;
 use matchbot_core::team::*;
 use poise::command;
-use serenity::builder::{CreateActionRow, CreateButton};
-use serenity::model::application::component::ButtonStyle;
-use serenity::model::channel::ReactionType;
-use sqlx::PgPool;
-use std::env;
+use serenity::model::channel::AttachmentType;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::{PgExecutor, PgPool};
 use std::i32;
 
+/// Schedules a new match series and returns its id
+pub(crate) async fn create_series(
+    executor: impl PgExecutor<'_>,
+    team_one: i32,
+    team_two: i32,
+    series_type: SeriesType,
+    tournament: i32,
+    scheduled_at: Option<OffsetDateTime>,
+) -> Result<i32> {
+    Ok(sqlx::query_scalar!(
+        "insert into match_series (team_one, team_two, series_type, tournament, scheduled_at, created_at)
+             values ($1, $2, cast(lower($3) as series_type), $4, $5, now())
+             returning id",
+        team_one,
+        team_two,
+        series_type.to_string(),
+        tournament,
+        scheduled_at,
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
 #[command(
     slash_command,
     guild_only,
-    subcommands("scheduled", "inprogress", "completed", "info")
+    subcommands("scheduled", "inprogress", "completed", "info", "timeline")
 )]
 pub(crate) async fn matches(_context: Context<'_>) -> Result<()> {
     Ok(())
@@ -29,15 +58,38 @@ pub(crate) async fn matches(_context: Context<'_>) -> Result<()> {
     ephemeral,
     description_localized("en-US", "Show your scheduled matches")
 )]
-pub(crate) async fn scheduled(context: Context<'_>) -> Result<()> {
+pub(crate) async fn scheduled(
+    context: Context<'_>,
+    #[description = "Only show matches of this tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
     let pool = &context.data().pool;
-    let matches = MatchSeries::get_all_by_user(pool, 20, context.author().id.0, false).await?;
+    let tournament = match tournament {
+        Some(_) => match pick_tournament(&context, tournament).await? {
+            Some(tournament) => Some(tournament.id),
+            None => return Ok(()),
+        },
+        None => None,
+    };
+    let series_ids = get_user_series_ids(
+        pool,
+        guild_id(&context)?,
+        context.author().id.0 as i64,
+        tournament,
+        20,
+    )
+    .await?;
+    let mut matches = Vec::new();
+    for id in series_ids {
+        matches.extend(MatchSeries::get(pool, id).await?);
+    }
     if matches.is_empty() {
         context.say("No matches were found").await?;
         return Ok(());
     }
     let teams = Team::get_all(pool).await?;
-    let match_info: String = matches
+    let match_info: Vec<String> = matches
         .into_iter()
         .map(|m| {
             let mut s = String::new();
@@ -47,11 +99,10 @@ pub(crate) async fn scheduled(context: Context<'_>) -> Result<()> {
             s.push_str(format!("{}", &team_one_name).as_str());
             s.push_str(" vs ");
             s.push_str(format!("{}", &team_two_name).as_str());
-            s.push_str("\n");
             s
         })
         .collect();
-    context.say(match_info).await?;
+    paginate(&context, "Scheduled matches", &match_info).await?;
     Ok(())
 }
 
@@ -63,21 +114,25 @@ pub(crate) async fn scheduled(context: Context<'_>) -> Result<()> {
 )]
 pub(crate) async fn inprogress(context: Context<'_>) -> Result<()> {
     let pool = &context.data().pool;
-    let match_series = MatchSeries::get_in_progress(pool).await?;
+    let mut match_series = MatchSeries::get_in_progress(pool).await?;
+    let series_ids: Vec<i32> = match_series.iter().map(|m| m.id).collect();
+    let guild_series = get_guild_series_ids(pool, guild_id(&context)?, &series_ids).await?;
+    match_series.retain(|m| guild_series.contains(&m.id));
     if match_series.is_empty() {
         context.say("No matches in progress were found").await?;
         return Ok(());
     }
     let servers = Server::get_live(pool).await?;
-    let mut resp_str = String::new();
+    let mut series_info = Vec::new();
     for series in &match_series {
-        match series.series_type {
-            Bo1 => resp_str.push_str(&match_inprogress_info(pool, series, &servers).await?),
-            Bo3 => resp_str.push_str(&series_inprogress_info(pool, series, &servers).await?),
-            Bo5 => resp_str.push_str(&series_inprogress_info(pool, series, &servers).await?),
-        }
+        let info = match series.series_type {
+            Bo1 => match_inprogress_info(pool, series, &servers).await?,
+            Bo3 => series_inprogress_info(pool, series, &servers).await?,
+            Bo5 => series_inprogress_info(pool, series, &servers).await?,
+        };
+        series_info.push(info.trim_end().to_string());
     }
-    context.say(resp_str).await?;
+    paginate(&context, "Matches in progress", &series_info).await?;
     Ok(())
 }
 
@@ -89,29 +144,41 @@ pub(crate) async fn inprogress(context: Context<'_>) -> Result<()> {
 )]
 pub(crate) async fn completed(context: Context<'_>) -> Result<()> {
     let pool = &context.data().pool;
-    let matches = MatchSeries::get_all(pool, 20, true, None).await?;
-    if matches.is_empty() {
+    let guild = guild_id(&context)?;
+    let count = count_completed_series(pool, guild).await? as usize;
+    if count == 0 {
         context.say("No matches were found").await?;
         return Ok(());
     }
-    let teams = Team::get_all(pool).await?;
+    let page_count = count.div_ceil(PAGE_ITEMS);
+    paginate_pages(&context, "Completed matches", page_count, |page| {
+        completed_page(pool, guild, page)
+    })
+    .await?;
+    Ok(())
+}
+
+/// Page `page` of the completed matches of `guild`, queried one page at a time
+async fn completed_page(pool: &PgPool, guild: i64, page: usize) -> Result<String> {
+    let series_ids =
+        get_completed_series_ids(pool, guild, PAGE_ITEMS as i64, (page * PAGE_ITEMS) as i64)
+            .await?;
     let mut s = String::new();
-    for m in matches {
+    for id in series_ids {
+        let Some(m) = MatchSeries::get(pool, id).await? else {
+            continue;
+        };
         let scores = MatchScore::get_by_series(pool, m.id).await?;
         let (team_one_score, team_two_score) = get_series_score(&scores, m.series_type);
-        if team_one_score == 0 && team_two_score == 0 {
-            continue;
-        }
-        let team_one_name = &teams.iter().find(|t| t.id == m.team_one).unwrap().name;
-        let team_two_name = &teams.iter().find(|t| t.id == m.team_two).unwrap().name;
+        // Teams from past tournaments are archived, so look them up by id
+        let team_one_name = Team::get(pool, m.team_one).await?.name;
+        let team_two_name = Team::get(pool, m.team_two).await?.name;
         s.push_str(format!("`#{}` ", m.id).as_str());
         s.push_str(format!("{} **`{}`**", &team_one_name, team_one_score).as_str());
         s.push_str(" - ");
-        s.push_str(format!("**`{}`** {}", team_two_score, &team_two_name).as_str());
-        s.push_str("\n");
+        s.push_str(format!("**`{}`** {}\n", team_two_score, &team_two_name).as_str());
     }
-    context.say(s).await?;
-    Ok(())
+    Ok(s)
 }
 
 #[command(
@@ -125,7 +192,11 @@ pub(crate) async fn info(
     #[description = "Match number"] match_id: i32,
 ) -> Result<()> {
     let pool = &context.data().pool;
-    let series = MatchSeries::get(pool, match_id).await?;
+    let guild = guild_id(&context)?;
+    let series = match is_guild_series(pool, guild, match_id).await? {
+        true => MatchSeries::get(pool, match_id).await?,
+        false => None,
+    };
     let Some(series) = series else {
         context
             .say(format!("Could not find match with id: `{}`", match_id))
@@ -135,7 +206,7 @@ pub(crate) async fn info(
     let team_one = Team::get(pool, series.team_one).await?;
     let team_two = Team::get(pool, series.team_two).await?;
     let matches = Match::get_by_series(pool, match_id).await?;
-    let maps = Map::get_all(pool, false).await?;
+    let maps = Map::get_all(pool, guild, false).await?;
     let scores = MatchScore::get_by_series(pool, match_id).await?;
     let (team_one_score, team_two_score) = get_series_score(&scores, series.series_type);
     let mut s = format!("**{}** `{}`", &team_one.name, team_one_score);
@@ -143,7 +214,6 @@ pub(crate) async fn info(
     s.push_str(format!("`{}` **{}**", team_two_score, &team_two.name).as_str());
     s.push_str("\n\n");
     let mut played_match_ids: Vec<i32> = Vec::new();
-    let mut map_names = Vec::new();
     for (i, m) in matches.iter().enumerate() {
         let picked_by = Team::get(pool, m.picked_by).await?;
         let score = scores.iter().find(|i| i.match_id == m.id).unwrap();
@@ -153,7 +223,6 @@ pub(crate) async fn info(
         played_match_ids.push(m.id);
         let map_name = &maps.iter().find(|map| map.id == m.map).unwrap().name;
         s.push_str(format!("{}. `{}` ", i + 1, map_name,).as_str());
-        map_names.push(map_name);
         if series.series_type != Bo1 {
             s.push_str(format!("**`{}`**", score.team_one_score).as_str());
             s.push_str(" - ");
@@ -162,19 +231,21 @@ pub(crate) async fn info(
         s.push_str(format!(" - picked by: **{}**\n", &picked_by.name,).as_str())
     }
     s.push_str(series.veto_info(pool, None).await?.as_str());
+    if MatchRound::exists_for_series(pool, series.id).await? {
+        s.push_str(
+            format!(
+                "\nRound timeline: `/matches timeline match_id:{}`",
+                series.id
+            )
+            .as_str(),
+        );
+    }
+    let demo_url = context.data().config.read().unwrap().demo_url();
     let components = match series.completed_at {
-        Some(_) => match &series.series_type {
-            Bo1 => {
-                let map_name = maps
-                    .iter()
-                    .find(|map| &map.id == &matches[0].map)
-                    .unwrap()
-                    .name
-                    .clone();
-                create_demo_link_row_bo1(series.dathost_match.unwrap(), &map_name)
-            }
-            _ => create_demo_link_row_series(&series.dathost_match.unwrap(), map_names),
-        },
+        Some(_) => create_demo_link_row(
+            demo_url.as_deref(),
+            &StoredDemo::get_by_series(pool, series.id).await?,
+        ),
         None => None,
     };
     context
@@ -190,54 +261,83 @@ pub(crate) async fn info(
     Ok(())
 }
 
-fn create_demo_link_row_bo1(dathost_id: String, map_name: &str) -> Option<CreateActionRow> {
-    let Ok(bucket_url) = env::var("BUCKET_URL") else {
-        return None;
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("en-US", "Show the round-by-round timeline of a match")
+)]
+pub(crate) async fn timeline(
+    context: Context<'_>,
+    #[description = "Match number"] match_id: i32,
+    #[description = "Only show this map"] map: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let guild = guild_id(&context)?;
+    let series = match is_guild_series(pool, guild, match_id).await? {
+        true => MatchSeries::get(pool, match_id).await?,
+        false => None,
     };
-    let mut ar = CreateActionRow::default();
-    let link_btn = get_demo_btn(map_name.to_string(), bucket_url, dathost_id);
-    ar.add_button(link_btn);
-    Some(ar)
-}
-
-fn create_demo_link_row_series(
-    series_id: &String,
-    map_names: Vec<&String>,
-) -> Option<CreateActionRow> {
-    let Ok(bucket_url) = env::var("BUCKET_URL") else {
-        return None;
+    let Some(series) = series else {
+        context
+            .say(format!("Could not find match with id: `{}`", match_id))
+            .await?;
+        return Ok(());
     };
-    let mut ar = CreateActionRow::default();
-    for (i, m) in map_names.iter().enumerate() {
-        let link_btn = get_series_demo_btn(&m, bucket_url.to_string(), series_id, i + 1);
-        ar.add_button(link_btn);
+    let team_one = Team::get(pool, series.team_one).await?;
+    let team_two = Team::get(pool, series.team_two).await?;
+    let half_rounds = Game::get_by_series(pool, series.id).await?.half_rounds();
+    let maps = Map::get_all(pool, guild, false).await?;
+    let mut timelines = Vec::new();
+    for m in Match::get_by_series(pool, series.id).await? {
+        let map_name = &maps.iter().find(|map| map.id == m.map).unwrap().name;
+        if let Some(map) = &map {
+            if !map_name.eq_ignore_ascii_case(map.trim()) {
+                continue;
+            }
+        }
+        let mut rounds = MatchRound::get_by_match(pool, m.id).await?;
+        if rounds.is_empty() {
+            rounds = MatchRound::get_from_demo(pool, m.id, half_rounds).await?;
+        }
+        if rounds.is_empty() {
+            continue;
+        }
+        timelines.push(MapTimeline::new(
+            m.id,
+            map_name.clone(),
+            (team_one.name.clone(), team_two.name.clone()),
+            rounds,
+            half_rounds,
+        ));
     }
-    Some(ar)
-}
-
-fn get_demo_btn(map_name: String, bucket_url: String, dathost_id: String) -> CreateButton {
-    let mut conn_button = CreateButton::default();
-    conn_button.label(map_name);
-    conn_button.style(ButtonStyle::Link);
-    conn_button.emoji(ReactionType::Unicode("📺".parse().unwrap()));
-    let url = format!("{}/{}.dem", bucket_url, dathost_id);
-    conn_button.url(url);
-    conn_button
-}
-
-fn get_series_demo_btn(
-    map_name: &String,
-    bucket_url: String,
-    series_id: &String,
-    index: usize,
-) -> CreateButton {
-    let mut conn_button = CreateButton::default();
-    conn_button.label(map_name);
-    conn_button.style(ButtonStyle::Link);
-    conn_button.emoji(ReactionType::Unicode("📺".parse().unwrap()));
-    let url = format!("{}/{}_{}.dem", bucket_url, series_id, index);
-    conn_button.url(url);
-    conn_button
+    if timelines.is_empty() {
+        context
+            .say(format!("No rounds were recorded for match `{}`", match_id))
+            .await?;
+        return Ok(());
+    }
+    let mut content = timelines
+        .iter()
+        .map(|t| t.chart())
+        .collect::<Vec<String>>()
+        .join("\n");
+    if content.chars().count() > 2000 {
+        content =
+            String::from("The timeline is too long to show, pick a map or see the attached JSON");
+    }
+    let json = serde_json::to_vec_pretty(&timelines)?;
+    context
+        .send(|b| {
+            b.ephemeral(true);
+            b.content(content);
+            b.attachment(AttachmentType::Bytes {
+                data: json.into(),
+                filename: format!("match-{}-timeline.json", match_id),
+            })
+        })
+        .await?;
+    Ok(())
 }
 
 pub fn get_series_score(scores: &Vec<MatchScore>, series_type: SeriesType) -> (i32, i32) {
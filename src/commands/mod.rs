@@ -1,6 +1,19 @@
 pub(crate) mod admin;
+pub(crate) mod announcements;
+pub(crate) mod audit;
+pub(crate) mod config;
+pub(crate) mod demos;
+pub(crate) mod gslt;
+pub(crate) mod highlights;
+pub(crate) mod locations;
 pub(crate) mod maps;
 pub(crate) mod matches;
+pub(crate) mod pagination;
+pub(crate) mod permissions;
+pub(crate) mod self_hosted;
 pub(crate) mod setup;
+pub(crate) mod stats;
 pub(crate) mod steamid;
 pub(crate) mod team;
+pub(crate) mod timeline;
+pub(crate) mod tournament;
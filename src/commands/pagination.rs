@@ -0,0 +1,186 @@
+use crate::Context;
+use anyhow::Result;
+use poise::futures_util::StreamExt;
+use serenity::builder::{CreateActionRow, CreateButton, CreateEmbed, CreateSelectMenu};
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::prelude::interaction::InteractionResponseType;
+use std::future::{ready, Future};
+use std::time::Duration;
+
+use crate::commands::setup::create_menu_option;
+
+pub const PAGE_ITEMS: usize = 10;
+/// Embed descriptions are limited to 4096 characters
+const PAGE_CHARS: usize = 4000;
+/// Discord select menus hold at most 25 options
+const JUMP_OPTIONS: usize = 25;
+const TIMEOUT: Duration = Duration::from_secs(60 * 5);
+
+/// Groups `items` into pages of at most `PAGE_ITEMS` entries that fit into an embed
+pub fn split_pages(items: &[String]) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    let mut count = 0;
+    for item in items {
+        let item = if item.len() > PAGE_CHARS {
+            let mut item: String = item.chars().take(PAGE_CHARS / 2).collect();
+            item.push('…');
+            item
+        } else {
+            item.clone()
+        };
+        if count == PAGE_ITEMS || (!page.is_empty() && page.len() + item.len() + 1 > PAGE_CHARS) {
+            pages.push(std::mem::take(&mut page));
+            count = 0;
+        }
+        page.push_str(item.as_str());
+        page.push('\n');
+        count += 1;
+    }
+    if !page.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+fn page_embed(title: &str, page: &str, index: usize, page_count: usize) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    embed.title(title).description(page);
+    if page_count > 1 {
+        embed.footer(|f| f.text(format!("Page {}/{}", index + 1, page_count)));
+    }
+    embed
+}
+
+fn page_button(custom_id: &str, label: &str, disabled: bool) -> CreateButton {
+    let mut button = CreateButton::default();
+    button.custom_id(custom_id);
+    button.label(label);
+    button.style(ButtonStyle::Secondary);
+    button.disabled(disabled);
+    button
+}
+
+fn page_buttons_row(page_count: usize, index: usize) -> CreateActionRow {
+    let mut ar = CreateActionRow::default();
+    ar.add_button(page_button("page_first", "⏮", index == 0));
+    ar.add_button(page_button("page_prev", "◀", index == 0));
+    ar.add_button(page_button("page_next", "▶", index + 1 == page_count));
+    ar.add_button(page_button("page_last", "⏭", index + 1 == page_count));
+    ar
+}
+
+/// Select menu of the pages around the current one
+fn page_jump_row(page_count: usize, index: usize) -> CreateActionRow {
+    let start = index
+        .saturating_sub(JUMP_OPTIONS / 2)
+        .min(page_count.saturating_sub(JUMP_OPTIONS));
+    let mut options = Vec::new();
+    for page in start..page_count.min(start + JUMP_OPTIONS) {
+        let mut option = create_menu_option(&format!("Page {}", page + 1), &page.to_string());
+        option.default_selection(page == index);
+        options.push(option);
+    }
+    let mut ar = CreateActionRow::default();
+    let mut menu = CreateSelectMenu::default();
+    menu.custom_id("page_jump");
+    menu.placeholder("Jump to page");
+    menu.options(|f| f.set_options(options));
+    ar.add_select_menu(menu);
+    ar
+}
+
+/// Replies with `items` as an embed, split into pages with navigation controls
+/// that stop responding after `TIMEOUT`
+pub async fn paginate(context: &Context<'_>, title: &str, items: &[String]) -> Result<()> {
+    let pages = split_pages(items);
+    paginate_pages(context, title, pages.len(), |index| {
+        ready(Ok(pages[index].clone()))
+    })
+    .await
+}
+
+/// Replies with page `0` of `page_count` pages, `load_page` is called for each page shown so
+/// long lists can be queried a page at a time. Only the command author can turn the pages
+pub async fn paginate_pages<F, Fut>(
+    context: &Context<'_>,
+    title: &str,
+    page_count: usize,
+    load_page: F,
+) -> Result<()>
+where
+    F: Fn(usize) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    if page_count == 0 {
+        return Ok(());
+    }
+    let mut index = 0;
+    let mut page = load_page(index).await?;
+    let reply = context
+        .send(|b| {
+            b.embed(|e| {
+                *e = page_embed(title, &page, index, page_count);
+                e
+            });
+            if page_count > 1 {
+                b.components(|c| {
+                    c.add_action_row(page_buttons_row(page_count, index))
+                        .add_action_row(page_jump_row(page_count, index))
+                });
+            }
+            b
+        })
+        .await?;
+    if page_count == 1 {
+        return Ok(());
+    }
+    let message = reply.message().await?;
+    let mut cib = message
+        .await_component_interactions(context.serenity_context())
+        .author_id(context.author().id)
+        .timeout(TIMEOUT)
+        .build();
+    while let Some(mci) = cib.next().await {
+        let next = match mci.data.custom_id.as_str() {
+            "page_first" => 0,
+            "page_prev" => index.saturating_sub(1),
+            "page_next" => (index + 1).min(page_count - 1),
+            "page_last" => page_count - 1,
+            "page_jump" => mci
+                .data
+                .values
+                .first()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(index)
+                .min(page_count - 1),
+            _ => index,
+        };
+        if next != index {
+            page = load_page(next).await?;
+            index = next;
+        }
+        mci.create_interaction_response(context.serenity_context(), |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| {
+                    d.set_embed(page_embed(title, &page, index, page_count))
+                        .components(|c| {
+                            c.add_action_row(page_buttons_row(page_count, index))
+                                .add_action_row(page_jump_row(page_count, index))
+                        })
+                })
+        })
+        .await?;
+    }
+    // Remove the controls once the collector timed out
+    reply
+        .edit(*context, |b| {
+            b.embed(|e| {
+                *e = page_embed(title, &page, index, page_count);
+                e
+            })
+            .components(|c| c)
+        })
+        .await?;
+    Ok(())
+}
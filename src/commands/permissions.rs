@@ -0,0 +1,148 @@
+use crate::Context;
+use anyhow::Result;
+use serenity::model::guild::Member;
+use sqlx::{FromRow, PgExecutor};
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// Bot level roles, mapped to Discord roles per guild with `/config roles`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, sqlx::Type, Display, EnumString, EnumIter)]
+#[sqlx(type_name = "bot_role", rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum BotRole {
+    /// Manages tournaments, maps & servers and everything a referee can do
+    Organiser,
+    /// Manages matches: scheduling, score entry & the setup admin controls
+    Referee,
+    /// Read-only access to the admin overviews
+    Caster,
+}
+
+impl BotRole {
+    /// Bot roles that include the permissions of `self`
+    fn granted_by(self) -> &'static [BotRole] {
+        match self {
+            BotRole::Organiser => &[BotRole::Organiser],
+            BotRole::Referee => &[BotRole::Organiser, BotRole::Referee],
+            BotRole::Caster => &[BotRole::Organiser, BotRole::Referee, BotRole::Caster],
+        }
+    }
+}
+
+#[derive(Debug, FromRow, Clone)]
+pub struct GuildRole {
+    pub guild: i64,
+    pub role: i64,
+    pub bot_role: BotRole,
+}
+
+impl GuildRole {
+    pub async fn get_all(executor: impl PgExecutor<'_>, guild: i64) -> Result<Vec<GuildRole>> {
+        Ok(sqlx::query_as!(
+            GuildRole,
+            r#"select guild, role, bot_role as "bot_role: BotRole"
+                 from guild_roles
+                 where guild = $1
+                 order by bot_role, role"#,
+            guild
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+    pub async fn add(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        role: i64,
+        bot_role: BotRole,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            "insert into guild_roles (guild, role, bot_role) values ($1, $2, $3)
+                 on conflict (guild, role, bot_role) do nothing",
+            guild,
+            role,
+            bot_role as BotRole,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    pub async fn remove(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        role: i64,
+        bot_role: BotRole,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            "delete from guild_roles where guild = $1 and role = $2 and bot_role = $3",
+            guild,
+            role,
+            bot_role as BotRole,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    /// Bot roles granted by any of the Discord `roles`
+    pub async fn get_bot_roles(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        roles: &[i64],
+    ) -> Result<Vec<BotRole>> {
+        Ok(sqlx::query_scalar!(
+            r#"select distinct bot_role as "bot_role!: BotRole"
+                 from guild_roles
+                 where guild = $1 and role = any($2)"#,
+            guild,
+            roles
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+}
+
+/// Whether `member` holds `required` or a bot role including it,
+/// guild administrators are always allowed
+pub async fn has_bot_role(
+    executor: impl PgExecutor<'_>,
+    member: &Member,
+    required: BotRole,
+) -> Result<bool> {
+    if member.permissions.map_or(false, |p| p.administrator()) {
+        return Ok(true);
+    }
+    let roles: Vec<i64> = member.roles.iter().map(|r| r.0 as i64).collect();
+    let bot_roles = GuildRole::get_bot_roles(executor, member.guild_id.0 as i64, &roles).await?;
+    Ok(required
+        .granted_by()
+        .iter()
+        .any(|role| bot_roles.contains(role)))
+}
+
+async fn check(context: Context<'_>, required: BotRole) -> Result<bool> {
+    let allowed = match context.author_member().await {
+        Some(member) => has_bot_role(&context.data().pool, &member, required).await?,
+        None => false,
+    };
+    if !allowed {
+        context
+            .send(|m| {
+                m.ephemeral(true).content(format!(
+                    "You need the `{}` bot role to use this command",
+                    required
+                ))
+            })
+            .await?;
+    }
+    Ok(allowed)
+}
+
+pub(crate) async fn organiser_check(context: Context<'_>) -> Result<bool> {
+    check(context, BotRole::Organiser).await
+}
+
+pub(crate) async fn referee_check(context: Context<'_>) -> Result<bool> {
+    check(context, BotRole::Referee).await
+}
+
+pub(crate) async fn caster_check(context: Context<'_>) -> Result<bool> {
+    check(context, BotRole::Caster).await
+}
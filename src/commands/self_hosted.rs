@@ -0,0 +1,396 @@
+use crate::config::RCON_PASSWORD_PREFIX;
+use crate::rcon::Rcon;
+use anyhow::{anyhow, bail, Result};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::Serialize;
+use sqlx::{FromRow, PgExecutor, PgPool};
+use std::collections::BTreeMap;
+use std::env;
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// Match plugin running on a self-hosted server
+#[derive(Debug, Copy, Clone, PartialEq, Eq, sqlx::Type, Display, EnumString, EnumIter)]
+#[sqlx(type_name = "match_plugin", rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum MatchPlugin {
+    Matchzy,
+    Get5,
+}
+
+impl MatchPlugin {
+    /// Console command that makes the plugin download & load the match config at `url`
+    pub fn load_command(&self, url: &str) -> String {
+        match self {
+            MatchPlugin::Matchzy => format!("matchzy_loadmatch_url \"{}\"", url),
+            MatchPlugin::Get5 => format!("get5_loadmatch_url \"{}\"", url),
+        }
+    }
+    /// Cvar of the url the plugin posts its match events to
+    pub fn remote_log_cvar(&self) -> &'static str {
+        match self {
+            MatchPlugin::Matchzy => "matchzy_remote_log_url",
+            MatchPlugin::Get5 => "get5_remote_log_url",
+        }
+    }
+    /// Cvar of the url the plugin uploads the demo of each map to
+    pub fn demo_upload_cvar(&self) -> &'static str {
+        match self {
+            MatchPlugin::Matchzy => "matchzy_demo_upload_url",
+            MatchPlugin::Get5 => "get5_demo_upload_url",
+        }
+    }
+    /// Number of the round of a `round_end` event counted from 1, get5 counts from 0 and
+    /// MatchZy from 1
+    pub fn round_number(&self, round_number: i32) -> i32 {
+        match self {
+            MatchPlugin::Matchzy => round_number,
+            MatchPlugin::Get5 => round_number + 1,
+        }
+    }
+    /// Name of the `reason` of a `round_end` event. get5 sends SourceMod's `CSRoundEndReason`,
+    /// MatchZy the same reasons counted from 1
+    pub fn round_end_reason(&self, reason: i32) -> String {
+        let reason = match self {
+            MatchPlugin::Matchzy => reason - 1,
+            MatchPlugin::Get5 => reason,
+        };
+        match reason {
+            0 => "target_bombed",
+            6 => "bomb_defused",
+            7 => "t_eliminated",
+            8 => "ct_eliminated",
+            9 => "draw",
+            11 => "target_saved",
+            16 => "t_surrender",
+            17 => "ct_surrender",
+            _ => return format!("reason_{}", reason),
+        }
+        .to_string()
+    }
+}
+
+/// Game server run by the league, matches are loaded over RCON instead of through Dathost
+#[derive(Debug, FromRow, Clone)]
+pub struct RconServer {
+    pub id: i32,
+    pub guild: i64,
+    pub location: String,
+    pub hostname: String,
+    pub game_port: i32,
+    pub gotv_port: i32,
+    /// Name of the environment variable holding the RCON password, starts with
+    /// `RCON_PASSWORD_`
+    pub rcon_password_env: String,
+    pub plugin: MatchPlugin,
+    pub region: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewRconServer {
+    pub guild: i64,
+    pub location: String,
+    pub hostname: String,
+    pub game_port: i32,
+    pub gotv_port: i32,
+    pub rcon_password_env: String,
+    pub plugin: MatchPlugin,
+    pub region: Option<String>,
+}
+
+impl RconServer {
+    pub async fn add(executor: impl PgExecutor<'_>, server: &NewRconServer) -> Result<bool> {
+        let result = sqlx::query!(
+            "insert into rcon_servers (guild, location, hostname, game_port, gotv_port, rcon_password_env, plugin, region)
+                 values ($1, $2, $3, $4, $5, $6, $7, $8)
+                 on conflict do nothing",
+            server.guild,
+            server.location,
+            server.hostname,
+            server.game_port,
+            server.gotv_port,
+            server.rcon_password_env,
+            server.plugin as MatchPlugin,
+            server.region,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    pub async fn delete(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        hostname: &str,
+        game_port: i32,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            "delete from rcon_servers where guild = $1 and hostname = $2 and game_port = $3",
+            guild,
+            hostname,
+            game_port,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    pub async fn get_all(executor: impl PgExecutor<'_>, guild: i64) -> Result<Vec<RconServer>> {
+        Ok(sqlx::query_as!(
+            RconServer,
+            r#"select id, guild, location, hostname, game_port, gotv_port, rcon_password_env,
+                      plugin as "plugin: MatchPlugin", region
+                 from rcon_servers
+                 where guild = $1
+                 order by location, hostname, game_port"#,
+            guild,
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+    /// Claims a server at `location` running `plugin` that no unfinished series is using for
+    /// `match_series`. The claim is stored on the server with an update that only succeeds while
+    /// the server still has the claim it was picked with, so concurrent setups never get the same
+    /// one, and claiming again is a no-op
+    pub async fn claim(
+        pool: &PgPool,
+        guild: i64,
+        location: &str,
+        plugin: MatchPlugin,
+        match_series: i32,
+    ) -> Result<Option<RconServer>> {
+        // a retried claim gets the server that was already claimed for the series
+        let claimed = sqlx::query_as!(
+            RconServer,
+            r#"select id, guild, location, hostname, game_port, gotv_port, rcon_password_env,
+                      plugin as "plugin: MatchPlugin", region
+                 from rcon_servers
+                 where claimed_by_series = $1
+                 limit 1"#,
+            match_series,
+        )
+        .fetch_optional(pool)
+        .await?;
+        if claimed.is_some() {
+            return Ok(claimed);
+        }
+        // servers whose series completed are free, even when the end of the series wasn't reported
+        let candidates = sqlx::query!(
+            "select r.id, r.claimed_by_series
+                 from rcon_servers r
+                    left join match_series ms on ms.id = r.claimed_by_series
+                 where r.guild = $1 and lower(r.location) = lower($2) and r.plugin = $3
+                   and (r.claimed_by_series is null or ms.completed_at is not null)
+                 order by r.id",
+            guild,
+            location,
+            plugin as MatchPlugin,
+        )
+        .fetch_all(pool)
+        .await?;
+        for candidate in candidates {
+            let mut transaction = pool.begin().await?;
+            let server = sqlx::query_as!(
+                RconServer,
+                r#"update rcon_servers
+                     set claimed_by_series = $2
+                     where id = $1 and claimed_by_series is not distinct from $3
+                     returning id, guild, location, hostname, game_port, gotv_port,
+                               rcon_password_env, plugin as "plugin: MatchPlugin", region"#,
+                candidate.id,
+                match_series,
+                candidate.claimed_by_series,
+            )
+            .fetch_optional(&mut transaction)
+            .await?;
+            // another setup claimed it first
+            let Some(server) = server else {
+                continue;
+            };
+            sqlx::query!(
+                "insert into servers (match_series, server_id, hostname, game_port, gotv_port)
+                     values ($1, $2, $3, $4, $5)",
+                match_series,
+                server.server_id(),
+                server.hostname,
+                server.game_port,
+                server.gotv_port,
+            )
+            .execute(&mut transaction)
+            .await?;
+            transaction.commit().await?;
+            return Ok(Some(server));
+        }
+        Ok(None)
+    }
+    /// Frees the server again after the match could not be loaded on it
+    pub async fn release(&self, pool: &PgPool, match_series: i32) -> Result<()> {
+        let mut transaction = pool.begin().await?;
+        sqlx::query!(
+            "update rcon_servers set claimed_by_series = null where id = $1 and claimed_by_series = $2",
+            self.id,
+            match_series,
+        )
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query!(
+            "delete from servers where match_series = $1 and server_id = $2",
+            match_series,
+            self.server_id(),
+        )
+        .execute(&mut transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+    /// Id the server is recorded under in `servers`
+    pub fn server_id(&self) -> String {
+        format!("rcon:{}", self.id)
+    }
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.hostname, self.game_port)
+    }
+    /// Tells the match plugin to load the config served at `url`
+    pub async fn load_match(&self, url: &str) -> Result<String> {
+        // Servers may only read RCON passwords, never other secrets of the bot host
+        if !self.rcon_password_env.starts_with(RCON_PASSWORD_PREFIX) {
+            bail!(
+                "RCON password variable `{}` does not start with `{}`, add the server again",
+                self.rcon_password_env,
+                RCON_PASSWORD_PREFIX
+            );
+        }
+        let password = env::var(&self.rcon_password_env).map_err(|_| {
+            anyhow!(
+                "missing RCON password variable `{}`",
+                self.rcon_password_env
+            )
+        })?;
+        let mut rcon = Rcon::connect(&self.address(), &password).await?;
+        rcon.exec(&self.plugin.load_command(url)).await
+    }
+}
+
+/// Team of a get5/MatchZy match config
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchConfigTeam {
+    pub name: String,
+    /// SteamID64 to player name, an empty name keeps the player's Steam name
+    pub players: BTreeMap<String, String>,
+}
+
+/// Match config in the get5 format, which MatchZy reads as well
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchConfig {
+    pub matchid: String,
+    pub num_maps: usize,
+    pub maplist: Vec<String>,
+    /// `team1_ct`, `team2_ct` or `knife` per map
+    pub map_sides: Vec<String>,
+    pub skip_veto: bool,
+    pub clinch_series: bool,
+    pub team1: MatchConfigTeam,
+    pub team2: MatchConfigTeam,
+    pub cvars: BTreeMap<String, String>,
+}
+
+impl MatchConfig {
+    /// Random token the config and the match events of a series are served under
+    pub fn new_token() -> String {
+        Alphanumeric.sample_string(&mut rand::thread_rng(), 32)
+    }
+    /// Stores the config of `match_series` under `token`
+    pub async fn save(
+        &self,
+        executor: impl PgExecutor<'_>,
+        match_series: i32,
+        token: &str,
+    ) -> Result<()> {
+        sqlx::query!(
+            "insert into match_configs (match_series, token, config) values ($1, $2, $3)
+                 on conflict (match_series) do update set token = excluded.token, config = excluded.config, created_at = now()",
+            match_series,
+            token,
+            serde_json::to_string(self)?,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+    pub async fn get_by_token(
+        executor: impl PgExecutor<'_>,
+        token: &str,
+    ) -> Result<Option<String>> {
+        Ok(
+            sqlx::query_scalar!("select config from match_configs where token = $1", token)
+                .fetch_optional(executor)
+                .await?,
+        )
+    }
+    /// Series whose config was served under `token`
+    pub async fn get_series_by_token(
+        executor: impl PgExecutor<'_>,
+        token: &str,
+    ) -> Result<Option<i32>> {
+        Ok(sqlx::query_scalar!(
+            "select match_series from match_configs where token = $1",
+            token
+        )
+        .fetch_optional(executor)
+        .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connect::tests::create_series;
+
+    #[test]
+    fn round_end_reasons() {
+        assert_eq!(MatchPlugin::Get5.round_end_reason(7), "t_eliminated");
+        assert_eq!(MatchPlugin::Matchzy.round_end_reason(8), "t_eliminated");
+        assert_eq!(MatchPlugin::Get5.round_end_reason(0), "target_bombed");
+        assert_eq!(MatchPlugin::Matchzy.round_end_reason(1), "target_bombed");
+        assert_eq!(MatchPlugin::Matchzy.round_end_reason(12), "target_saved");
+        assert_eq!(MatchPlugin::Get5.round_end_reason(3), "reason_3");
+        assert_eq!(MatchPlugin::Matchzy.round_end_reason(4), "reason_3");
+    }
+
+    #[test]
+    fn round_numbers() {
+        assert_eq!(MatchPlugin::Get5.round_number(0), 1);
+        assert_eq!(MatchPlugin::Matchzy.round_number(1), 1);
+    }
+
+    #[sqlx::test]
+    async fn claims_each_server_once(pool: PgPool) {
+        sqlx::query(
+            "insert into rcon_servers (guild, location, hostname, game_port, gotv_port, rcon_password_env, plugin)
+                 values (1, 'eu', 'localhost', 27015, 27020, 'RCON_PASSWORD_EU', 'matchzy')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let first = create_series(&pool).await;
+        let next = create_series(&pool).await;
+        let claim = |series| RconServer::claim(&pool, 1, "EU", MatchPlugin::Matchzy, series);
+        let (a, b) = tokio::join!(claim(first), claim(next));
+        let claimed = [a.unwrap(), b.unwrap()];
+        assert_eq!(claimed.iter().filter(|s| s.is_some()).count(), 1);
+
+        let (owner, other) = match &claimed[0] {
+            Some(_) => (first, next),
+            None => (next, first),
+        };
+        let server = claim(owner).await.unwrap().unwrap();
+        assert!(claim(other).await.unwrap().is_none());
+        server.release(&pool, owner).await.unwrap();
+        assert!(claim(other).await.unwrap().is_some());
+
+        // the server of a completed series is free even without a reported end
+        sqlx::query("update match_series set completed_at = now() where id = $1")
+            .bind(other)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(claim(owner).await.unwrap().is_some());
+    }
+}
@@ -1,37 +1,56 @@
 use crate::Context;
-use anyhow::{Error, Result};
+use anyhow::{anyhow, bail, Error, Result};
 use matchbot_core::matches::SeriesType::{Bo1, Bo3, Bo5};
 use poise::command;
 use poise::futures_util::StreamExt;
-use reqwest::{Client, Response};
+use reqwest::{Client, RequestBuilder, Response};
 use serde::{Deserialize, Serialize};
-use serenity::builder::{CreateActionRow, CreateButton, CreateSelectMenu, CreateSelectMenuOption};
+use serde_json::json;
+use serenity::builder::{
+    CreateActionRow, CreateButton, CreateEmbed, CreateSelectMenu, CreateSelectMenuOption,
+};
 use serenity::model::application::component::ButtonStyle;
 use serenity::model::application::interaction::message_component::MessageComponentInteraction;
-use serenity::model::channel::{ChannelType, ReactionType};
-use std::collections::HashMap;
-use std::env;
+use serenity::model::channel::{ChannelType, Message, ReactionType};
+use serenity::model::id::{ChannelId, MessageId, UserId};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
 
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use serenity::model::prelude::interaction::InteractionResponseType;
 
 use crate::commands::admin::ServerTemplates;
+use crate::commands::announcements::announce;
+use crate::commands::config::{guild_id, GuildConfig};
+use crate::commands::gslt::{assign_gslt, release_gslt};
+use crate::commands::locations::{get_team_region, pick_fairest, ServerPing};
 use crate::commands::maps::Map;
+use crate::commands::permissions::{has_bot_role, BotRole};
+use crate::commands::self_hosted::{MatchConfig, MatchConfigTeam, RconServer};
 use crate::commands::steamid::SteamUser;
+use crate::commands::team::get_guild_team;
+use crate::commands::tournament::{
+    active_tournaments, next_user_series, pick_tournament, Game, ServerSelection, SideSelection,
+    VetoStartRule,
+};
+use crate::connect::{ConnectLinks, ConnectTarget};
+use crate::metrics::{self, track_request, GaugeGuard};
 use matchbot_core::matches::VoteType::{Pick, Veto};
 use matchbot_core::matches::{
     Match, MatchScore, MatchSeries, NewMatch, SeriesType, Server, VoteInfo, VoteType,
 };
 use matchbot_core::team::Team;
 
-use sqlx::PgPool;
-use steamid::{SteamId, Universe};
+use serenity::http::Http;
+use sqlx::{PgExecutor, PgPool};
+use steamid::{AccountType, Instance, SteamId, Universe};
 use urlencoding::encode;
 
 #[derive(Debug, Copy, PartialEq, Eq, Serialize, Deserialize, Clone)]
 pub enum SetupState {
+    VetoStart,
     MapVeto,
     SidePick,
     ServerPick,
@@ -70,31 +89,15 @@ pub struct DathostConfig {
     pub password: String,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct CreateGsltRequest {
-    pub key: String,
-    pub appid: u32,
-    pub memo: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct SteamApiRootResponse {
-    pub response: SteamApiResponse,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct SteamApiResponse {
-    pub steamid: String,
-    pub login_token: String,
-}
-
 #[derive(Debug, Clone)]
 pub struct Setup {
+    guild: i64,
     team_one_conn_str: Option<String>,
     team_two_conn_str: Option<String>,
-    maps_remaining: Vec<String>,
+    maps_remaining: Vec<Map>,
     maps_sel: Vec<NewMatch>,
     series_type: SeriesType,
+    game: Game,
     match_series: Option<i32>,
     veto_pick_order: Vec<NewVoteInfo>,
     current_step: usize,
@@ -102,14 +105,104 @@ pub struct Setup {
     servers_remaining: Vec<ServerTemplates>,
     server_veto_team: i64,
     server_id: Option<String>,
+    server_location: Option<String>,
+    server_self_hosted: bool,
     server_hostname: Option<String>,
     server_game_port: Option<i64>,
     server_gotv_port: Option<i64>,
     team_one: Team,
     team_two: Team,
+    veto_start_rule: VetoStartRule,
+    veto_start_msg: String,
+    veto_start_chooser: Option<i64>,
+    veto_start_team: Option<i64>,
+    decider_side_team: Option<i64>,
+    side_selection: SideSelection,
+    admin_acting: Option<UserId>,
+    history: Vec<Setup>,
 }
 
 impl Setup {
+    /// Setup of a series whose veto was saved, to start its server again
+    pub(crate) async fn from_saved_veto(
+        pool: &PgPool,
+        guild: i64,
+        current_match: &MatchSeries,
+    ) -> Result<Setup> {
+        let team_one = Team::get(pool, current_match.team_one).await?;
+        let team_two = Team::get(pool, current_match.team_two).await?;
+        let maps = sqlx::query!(
+            "select map, picked_by, start_ct_team, start_t_team
+                 from match
+                 where match_series = $1
+                 order by id",
+            current_match.id
+        )
+        .fetch_all(pool)
+        .await?;
+        if maps.is_empty() {
+            bail!("the veto of match {} has not been saved", current_match.id);
+        }
+        let role = |team: i32| {
+            if team == team_one.id {
+                team_one.role
+            } else {
+                team_two.role
+            }
+        };
+        let maps_sel = maps
+            .iter()
+            .map(|m| NewMatch {
+                map_id: m.map,
+                picked_by_role: role(m.picked_by),
+                start_ct_team_role: Some(role(m.start_ct_team)),
+                start_t_team_role: Some(role(m.start_t_team)),
+            })
+            .collect();
+        let location = sqlx::query_scalar!(
+            "select server_location from match_series where id = $1",
+            current_match.id
+        )
+        .fetch_one(pool)
+        .await?
+        .ok_or_else(|| anyhow!("match {} has no server location", current_match.id))?;
+        let server = ServerTemplates::get_all(pool, guild)
+            .await?
+            .into_iter()
+            .find(|s| s.location.eq_ignore_ascii_case(&location))
+            .ok_or_else(|| anyhow!("server location `{}` no longer exists", location))?;
+        Ok(Setup {
+            guild,
+            team_one_conn_str: None,
+            team_two_conn_str: None,
+            maps_remaining: vec![],
+            maps_sel,
+            series_type: current_match.series_type,
+            game: Game::get_by_series(pool, current_match.id).await?,
+            match_series: Some(current_match.id),
+            veto_pick_order: vec![],
+            current_step: 0,
+            current_phase: SetupState::SidePick,
+            servers_remaining: vec![],
+            server_veto_team: team_two.role,
+            server_id: Some(server.server_id),
+            server_location: Some(server.location),
+            server_self_hosted: server.self_hosted,
+            server_hostname: None,
+            server_game_port: None,
+            server_gotv_port: None,
+            veto_start_rule: VetoStartRule::get_by_series(pool, current_match.id).await?,
+            veto_start_msg: String::new(),
+            veto_start_chooser: None,
+            veto_start_team: None,
+            decider_side_team: None,
+            side_selection: SideSelection::get_by_series(pool, current_match.id).await?,
+            admin_acting: None,
+            history: vec![],
+            team_one,
+            team_two,
+        })
+    }
     async fn finish(&self, executor: &PgPool) -> Result<()> {
         for vote_info in &self.veto_pick_order {
             let team = if vote_info.team_role == self.team_one.role {
@@ -126,7 +219,7 @@ impl Setup {
             )
             .await?;
         }
-        for map in &self.maps_sel {
+        for (i, map) in self.maps_sel.iter().enumerate() {
             let picked_by = if map.picked_by_role == self.team_one.role {
                 self.team_one.id
             } else {
@@ -152,154 +245,325 @@ impl Setup {
             )
             .await?;
             MatchScore::add(executor, m.id).await?;
+            if self.is_knife(i) {
+                sqlx::query!("update match set knife_round = true where id = $1", m.id)
+                    .execute(executor)
+                    .await?;
+            }
+        }
+        sqlx::query!(
+            "update match_series set server_location = $2 where id = $1",
+            self.match_series.unwrap(),
+            self.server_location,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+    /// get5/MatchZy config of the series, team one is `team1`
+    async fn match_config(&self, executor: &PgPool) -> Result<MatchConfig> {
+        let maps = Map::get_all(executor, self.guild, true).await?;
+        let num_maps = match self.series_type {
+            Bo1 => 1,
+            Bo3 => 3,
+            Bo5 => 5,
+        };
+        let mut maplist = Vec::new();
+        let mut map_sides = Vec::new();
+        for (i, new_match) in self.maps_sel.iter().take(num_maps).enumerate() {
+            let map = maps.iter().find(|m| m.id == new_match.map_id).unwrap();
+            maplist.push(map.server_map());
+            let side = if self.is_knife(i) {
+                "knife"
+            } else if new_match.start_ct_team_role.unwrap() == self.team_one.role {
+                "team1_ct"
+            } else {
+                "team2_ct"
+            };
+            map_sides.push(side.to_string());
+        }
+        let match_series = self.match_series.unwrap();
+        Ok(MatchConfig {
+            matchid: format!("matchbot-{}", match_series),
+            num_maps,
+            maplist,
+            map_sides,
+            skip_veto: true,
+            clinch_series: true,
+            team1: MatchConfigTeam {
+                name: self.team_one.name.clone(),
+                players: config_players(self.team_one_conn_str.as_ref().unwrap()),
+            },
+            team2: MatchConfigTeam {
+                name: self.team_two.name.clone(),
+                players: config_players(self.team_two_conn_str.as_ref().unwrap()),
+            },
+            cvars: BTreeMap::from([(
+                String::from("hostname"),
+                format!("match-server-{}", match_series),
+            )]),
+        })
+    }
+    fn other_team(&self, role: i64) -> i64 {
+        if role == self.team_one.role {
+            self.team_two.role
+        } else {
+            self.team_one.role
+        }
+    }
+    fn team_id(&self, role: i64) -> i32 {
+        if role == self.team_one.role {
+            self.team_one.id
+        } else {
+            self.team_two.id
         }
+    }
+    async fn start_veto(&mut self, starting_team: i64, decider_side_team: Option<i64>) {
+        let other_team = self.other_team(starting_team);
+        let match_series = self.match_series.unwrap();
+        let (veto_pick_order, veto_start_msg) = match self.series_type {
+            Bo1 => bo1_setup(match_series, starting_team, other_team).await,
+            Bo3 => bo3_setup(match_series, starting_team, other_team).await,
+            Bo5 => bo5_setup(match_series, starting_team, other_team).await,
+        };
+        self.veto_pick_order = veto_pick_order;
+        self.veto_start_msg = veto_start_msg;
+        self.veto_start_team = Some(starting_team);
+        // the team that picks the decider never also chooses its side, in a Bo1 the last pick
+        // is the decider and the side choice stays with the other team
+        let decider_picker = self.veto_pick_order.last().map(|v| v.team_role);
+        self.decider_side_team = decider_side_team.filter(|team| Some(*team) != decider_picker);
+    }
+    /// Saves the veto start of the setup. A setup without a server location gives up the
+    /// reservation of the series, i.e. when an undo went back past the server pick
+    async fn record_progress(&self, executor: &PgPool) -> Result<()> {
+        sqlx::query!(
+            "update match_series
+                set coin_toss_winner = $2, veto_start_team = $3, decider_side_team = $4,
+                    server_location = case when $5 then server_location end,
+                    server_reserved_at = case when $5 then server_reserved_at end
+                where id = $1",
+            self.match_series.unwrap(),
+            self.veto_start_chooser
+                .filter(|_| self.veto_start_rule == VetoStartRule::CoinToss)
+                .map(|r| self.team_id(r)),
+            self.veto_start_team.map(|r| self.team_id(r)),
+            self.decider_side_team.map(|r| self.team_id(r)),
+            self.server_location.is_some(),
+        )
+        .execute(executor)
+        .await?;
         Ok(())
     }
+    /// Team that chooses the starting side on the map at `index`
+    fn side_pick_team(&self, index: usize) -> i64 {
+        if index == self.maps_sel.len() - 1 {
+            if let Some(decider_side_team) = self.decider_side_team {
+                return decider_side_team;
+            }
+        }
+        self.other_team(self.maps_sel[index].picked_by_role)
+    }
+    /// Team whose turn it is in the current phase
+    fn current_team(&self) -> i64 {
+        match self.current_phase {
+            SetupState::VetoStart => self.veto_start_chooser.unwrap(),
+            SetupState::ServerPick => self.server_veto_team,
+            SetupState::MapVeto => self.veto_pick_order[self.current_step].team_role,
+            SetupState::SidePick => self.side_pick_team(self.current_step),
+        }
+    }
+    /// Saves the current state so an admin can undo the step about to be made
+    fn checkpoint(&mut self) {
+        let snapshot = Setup {
+            history: vec![],
+            ..self.clone()
+        };
+        self.history.push(snapshot);
+    }
+    fn undo(&mut self) -> bool {
+        let Some(previous) = self.history.pop() else {
+            return false;
+        };
+        let history = std::mem::take(&mut self.history);
+        *self = previous;
+        self.history = history;
+        true
+    }
+    fn is_knife(&self, index: usize) -> bool {
+        self.side_selection
+            .is_knife(index == self.maps_sel.len() - 1)
+    }
+    /// Index of the next map that needs a side pick, knife round maps are skipped
+    fn next_side_pick(&self, from: usize) -> Option<usize> {
+        (from..self.maps_sel.len()).find(|i| !self.is_knife(*i))
+    }
+    fn assign_knife_sides(&mut self) {
+        for i in 0..self.maps_sel.len() {
+            if self.is_knife(i) {
+                // placeholder, the knife round decides the actual starting sides on the server
+                self.maps_sel[i].start_ct_team_role = Some(self.team_one.role);
+                self.maps_sel[i].start_t_team_role = Some(self.team_two.role);
+            }
+        }
+    }
 }
 
-async fn bo1_setup(match_series: i32, team_one: i64, team_two: i64) -> (Vec<NewVoteInfo>, String) {
+async fn bo1_setup(
+    match_series: i32,
+    starting_team: i64,
+    other_team: i64,
+) -> (Vec<NewVoteInfo>, String) {
     (
         vec![
             NewVoteInfo {
                 match_series,
                 vote_type: Veto,
-                team_role: team_two,
+                team_role: starting_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Veto,
-                team_role: team_one,
+                team_role: other_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Veto,
-                team_role: team_two,
+                team_role: starting_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Veto,
-                team_role: team_one,
+                team_role: other_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Veto,
-                team_role: team_two,
+                team_role: starting_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Pick,
-                team_role: team_one,
+                team_role: other_team,
                 map: None,
             },
         ],
         format!(
             "Best of 1 option selected. Starting map veto. <@&{}> bans first.\n",
-            &team_two
+            &starting_team
         ),
     )
 }
 
-async fn bo3_setup(match_series: i32, team_one: i64, team_two: i64) -> (Vec<NewVoteInfo>, String) {
+async fn bo3_setup(
+    match_series: i32,
+    starting_team: i64,
+    other_team: i64,
+) -> (Vec<NewVoteInfo>, String) {
     (
         vec![
             NewVoteInfo {
                 match_series,
                 vote_type: Veto,
-                team_role: team_one,
+                team_role: starting_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Veto,
-                team_role: team_two,
+                team_role: other_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Pick,
-                team_role: team_one,
+                team_role: starting_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Pick,
-                team_role: team_two,
+                team_role: other_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Veto,
-                team_role: team_two,
+                team_role: other_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Pick,
-                team_role: team_one,
+                team_role: starting_team,
                 map: None,
             },
         ],
         format!(
             "Best of 3 option selected. Starting map veto. <@&{}> bans first.\n",
-            &team_one
+            &starting_team
         ),
     )
 }
 
-async fn bo5_setup(match_series: i32, team_one: i64, team_two: i64) -> (Vec<NewVoteInfo>, String) {
+async fn bo5_setup(
+    match_series: i32,
+    starting_team: i64,
+    other_team: i64,
+) -> (Vec<NewVoteInfo>, String) {
     (
         vec![
             NewVoteInfo {
                 match_series,
                 vote_type: Veto,
-                team_role: team_one,
+                team_role: starting_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Veto,
-                team_role: team_two,
+                team_role: other_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Pick,
-                team_role: team_one,
+                team_role: starting_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Pick,
-                team_role: team_two,
+                team_role: other_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Pick,
-                team_role: team_one,
+                team_role: starting_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Pick,
-                team_role: team_two,
+                team_role: other_team,
                 map: None,
             },
             NewVoteInfo {
                 match_series,
                 vote_type: Pick,
-                team_role: team_one,
+                team_role: starting_team,
                 map: None,
             },
         ],
         format!(
             "Best of 5 option selected. Starting map veto. <@&{}> bans first.\n",
-            &team_one
+            &starting_team
         ),
     )
 }
@@ -309,15 +573,30 @@ async fn bo5_setup(match_series: i32, team_one: i64, team_two: i64) -> (Vec<NewV
     guild_only,
     description_localized("en-US", "Setup your next scheduled match")
 )]
-pub(crate) async fn setup(context: Context<'_>) -> Result<()> {
+pub(crate) async fn setup(
+    context: Context<'_>,
+    #[description = "Only setup matches of this tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
     let pool = &context.data().pool;
-    let current_match = MatchSeries::next_user_match(pool, context.author().id.0 as i64).await;
-    if current_match.is_err() {
-        log::error!("{:#?}", current_match.err().unwrap());
+    let author = context.author().id.0 as i64;
+    let guild = guild_id(&context)?;
+    let tournament = match tournament {
+        Some(_) => match pick_tournament(&context, tournament).await? {
+            Some(tournament) => Some(tournament.id),
+            None => return Ok(()),
+        },
+        None => None,
+    };
+    let current_match = match next_user_series(pool, author, guild, tournament).await? {
+        Some(id) => MatchSeries::get(pool, id).await?,
+        None => None,
+    };
+    let Some(mut current_match) = current_match else {
         context.say("No scheduled matches found").await?;
         return Ok(());
-    }
-    let mut current_match = current_match.unwrap();
+    };
     if current_match.thread.is_some() {
         context
             .send(|m| {
@@ -336,14 +615,14 @@ pub(crate) async fn setup(context: Context<'_>) -> Result<()> {
             .await?;
         return Ok(());
     }
-    let maps = Map::get_all(pool, true).await?;
-    let maps_names: Vec<String> = maps
-        .clone()
+    let game = Game::get_by_series(pool, current_match.id).await?;
+    let maps: Vec<Map> = Map::get_all(pool, guild, true)
+        .await?
         .into_iter()
-        .filter(|m| !m.disabled)
-        .map(|m| m.name)
+        .filter(|m| m.in_pool(game))
         .collect();
-    if maps_names.len() < 7 {
+    let maps_remaining: Vec<Map> = maps.clone().into_iter().filter(|m| !m.disabled).collect();
+    if maps_remaining.len() < 7 {
         context
             .say("At least 7 maps need to be enabled before starting setup.")
             .await?;
@@ -351,27 +630,41 @@ pub(crate) async fn setup(context: Context<'_>) -> Result<()> {
     }
     let team_one = Team::get(pool, current_match.team_one).await?;
     let team_two = Team::get(pool, current_match.team_two).await?;
-    let series_setup = match current_match.series_type {
-        Bo1 => bo1_setup(current_match.id, team_one.role, team_two.role).await,
-        Bo3 => bo3_setup(current_match.id, team_one.role, team_two.role).await,
-        Bo5 => bo5_setup(current_match.id, team_one.role, team_two.role).await,
-    };
-    let servers_remaining = ServerTemplates::get_all(pool).await?;
-    if servers_remaining.len() == 0 {
+    let veto_start_rule = VetoStartRule::get_by_series(pool, current_match.id).await?;
+    let side_selection = SideSelection::get_by_series(pool, current_match.id).await?;
+    let servers_remaining =
+        ServerTemplates::get_for_game(pool, guild, game, current_match.series_type).await?;
+    if servers_remaining.is_empty() && game == Game::Cs2 && current_match.series_type != Bo1 {
+        context
+            .say("Dathost only runs single CS2 maps, add a self-hosted MatchZy server with `/admin servers add_rcon` to play CS2 series.")
+            .await?;
+        return Ok(());
+    }
+    if servers_remaining.is_empty() && !ServerTemplates::get_all(pool, guild).await?.is_empty() {
+        context
+            .say("All server locations are at capacity, try again once a running series has finished.")
+            .await?;
+        return Ok(());
+    }
+    if servers_remaining.is_empty() {
         context
             .say("No server templates have been added, use `/admin servers add` to add some.")
             .await?;
         return Ok(());
     }
     let mut setup: Setup = Setup {
-        maps_remaining: maps_names,
+        guild,
+        maps_remaining,
         maps_sel: vec![],
         series_type: current_match.series_type,
+        game,
         match_series: Some(current_match.id),
-        veto_pick_order: series_setup.0,
+        veto_pick_order: vec![],
         current_step: 0,
         current_phase: SetupState::ServerPick,
         server_id: None,
+        server_location: None,
+        server_self_hosted: false,
         server_veto_team: team_two.clone().role,
         team_one,
         team_two,
@@ -381,7 +674,56 @@ pub(crate) async fn setup(context: Context<'_>) -> Result<()> {
         team_two_conn_str: None,
         team_one_conn_str: None,
         server_gotv_port: None,
+        veto_start_rule,
+        veto_start_msg: String::new(),
+        veto_start_chooser: None,
+        veto_start_team: None,
+        decider_side_team: None,
+        side_selection,
+        admin_acting: None,
+        history: vec![],
     };
+    let mut intro = String::new();
+    match veto_start_rule {
+        VetoStartRule::Fixed => {
+            let starting_team = match setup.series_type {
+                Bo1 => setup.team_two.role,
+                _ => setup.team_one.role,
+            };
+            setup.start_veto(starting_team, None).await;
+        }
+        VetoStartRule::LowerSeedStarts => {
+            setup
+                .start_veto(setup.team_two.role, Some(setup.team_one.role))
+                .await;
+        }
+        VetoStartRule::HigherSeedChooses => {
+            setup.veto_start_chooser = Some(setup.team_one.role);
+            intro = format!(
+                "<@&{}> is the higher seed and chooses to either start the map veto or {}.",
+                setup.team_one.role,
+                decider_choice(setup.series_type)
+            );
+        }
+        VetoStartRule::CoinToss => {
+            let winner = if rand::random() {
+                setup.team_one.role
+            } else {
+                setup.team_two.role
+            };
+            setup.veto_start_chooser = Some(winner);
+            intro = format!(
+                "🪙 <@&{}> won the coin toss and chooses to either start the map veto or {}.",
+                winner,
+                decider_choice(setup.series_type)
+            );
+        }
+    }
+    if setup.veto_start_chooser.is_some() {
+        setup.current_phase = SetupState::VetoStart;
+    } else {
+        setup.record_progress(pool).await?;
+    }
     let m = context
         .say(format!(
             "Starting setup for <@&{}> vs <@&{}> ⤵️",
@@ -402,46 +744,73 @@ pub(crate) async fn setup(context: Context<'_>) -> Result<()> {
     current_match
         .update_thread(pool, thread.id.0.try_into().unwrap())
         .await?;
+    // the location is only reserved once there is a setup thread that can be restarted
+    let server_selection = ServerSelection::get_by_series(pool, current_match.id).await?;
+    let (auto_server, server_note) = auto_server(
+        pool,
+        guild,
+        current_match.id,
+        server_selection,
+        &setup.servers_remaining,
+        [&setup.team_one, &setup.team_two],
+    )
+    .await?;
+    if let Some(server) = auto_server {
+        setup.server_id = Some(server.server_id);
+        setup.server_self_hosted = server.self_hosted;
+        setup.server_location = Some(server.location);
+    }
+    if let Some(note) = server_note {
+        thread.say(context.serenity_context(), note).await?;
+    }
     let mut m = thread
         .say(context.serenity_context(), "Starting setup...")
         .await?;
-    if setup.servers_remaining.len() > 2 {
+    if setup.current_phase == SetupState::VetoStart {
         m.edit(context.serenity_context(), |d| {
-            d.content(format!(
-                "\nIt is <@&{}> turn to ban a server",
-                setup.team_two.role
-            ))
-            .components(|c| {
-                c.add_action_row(create_server_action_row(
-                    setup.servers_remaining.clone(),
-                    &Veto,
-                ))
-            })
+            d.content(intro)
+                .components(|c| c.add_action_row(create_veto_start_action_row(setup.series_type)))
         })
         .await?;
     } else {
+        let (content, row) = enter_server_phase(&mut setup);
         m.edit(context.serenity_context(), |d| {
-            d.content(format!(
-                "\nIt is <@&{}> turn to pick a server",
-                setup.team_two.role
-            ))
-            .components(|c| {
-                c.add_action_row(create_server_action_row(
-                    setup.servers_remaining.clone(),
-                    &Pick,
-                ))
-            })
+            d.content(content).components(|c| c.add_action_row(row))
         })
         .await?;
     }
+    let admin_msg = thread
+        .send_message(context.serenity_context(), |d| {
+            d.content("Admin controls")
+                .components(|c| c.add_action_row(create_admin_action_row()))
+        })
+        .await?;
     let mut cib = m
         .await_component_interactions(&context.serenity_context())
         .build();
-    while let Some(mci) = cib.next().await {
-        let completed = match setup.current_phase {
-            SetupState::ServerPick => {
-                server_pick_phase(pool, &context, &mci, &mut setup, &series_setup.1).await?
+    let mut admin_cib = admin_msg
+        .await_component_interactions(&context.serenity_context())
+        .build();
+    let _active_setup = GaugeGuard::inc(&metrics::ACTIVE_SETUPS);
+    let mut phase_started = Instant::now();
+    loop {
+        let mci = tokio::select! {
+            Some(mci) = cib.next() => mci,
+            Some(mci) = admin_cib.next() => {
+                let ended =
+                    admin_phase(pool, &context, &mci, &mut setup, &maps, &mut current_match, m.id)
+                        .await?;
+                if ended {
+                    return Ok(());
+                }
+                continue;
             }
+            else => break,
+        };
+        let phase = setup.current_phase;
+        let completed = match setup.current_phase {
+            SetupState::VetoStart => veto_start_phase(pool, &context, &mci, &mut setup).await?,
+            SetupState::ServerPick => server_pick_phase(pool, &context, &mci, &mut setup).await?,
             SetupState::MapVeto => {
                 map_veto_phase(pool, &context, &mci, &mut setup, &maps, &current_match).await?
             }
@@ -449,172 +818,548 @@ pub(crate) async fn setup(context: Context<'_>) -> Result<()> {
                 side_pick_phase(pool, &context, &mci, &mut setup, &maps).await?
             }
         };
+        if completed || setup.current_phase != phase {
+            metrics::SETUP_PHASE_SECONDS
+                .with_label_values(&[&format!("{:?}", phase)])
+                .observe(phase_started.elapsed().as_secs_f64());
+            phase_started = Instant::now();
+        }
         if completed {
-            match start_server(&context, pool, &mci, &mut setup, &mut current_match).await {
-                Ok(resp) => {
-                    setup.finish(pool).await?;
-                    send_conn_msg(&context, pool, &mci, &setup, resp).await;
-                    return Ok(());
+            admin_msg.delete(context.serenity_context()).await?;
+            // the veto result is kept when the server fails to start so admins can retry
+            setup.finish(pool).await?;
+            mci.message.delete(&context.serenity_context()).await?;
+            let mut msg = mci
+                .channel_id
+                .say(
+                    context.serenity_context(),
+                    "Match setup completed, starting server...",
+                )
+                .await?;
+            return start_series_server(&context, pool, &mut msg, &mut setup, &mut current_match)
+                .await;
+        }
+    }
+    Ok(())
+}
+
+/// Starts the server of a series with a saved veto, failed starts wait for an admin to retry
+pub(crate) async fn start_series_server(
+    context: &Context<'_>,
+    pool: &PgPool,
+    msg: &mut Message,
+    setup: &mut Setup,
+    current_match: &mut MatchSeries,
+) -> Result<()> {
+    loop {
+        match start_server(context, pool, msg, setup, current_match).await {
+            Ok(resp) => {
+                if let Err(err) = announce_setup(context, pool, setup).await {
+                    log::error!("{:#?}", err);
+                }
+                if let Err(err) = send_conn_msg(context, pool, msg.channel_id, setup, resp).await {
+                    log::error!("{:#?}", err);
                 }
-                Err(err) => {
-                    eprintln!("{:#?}", err)
+                return Ok(());
+            }
+            Err(err) => {
+                log::error!("{:#?}", err);
+                if !await_start_retry(context, pool, msg, current_match.id, &err).await? {
+                    return Ok(());
                 }
             }
-            return Ok(());
         }
     }
+}
+
+async fn acting_team(
+    pool: &PgPool,
+    mci: &Arc<MessageComponentInteraction>,
+    setup: &Setup,
+) -> Result<Option<Team>> {
+    if setup.admin_acting != Some(mci.user.id) {
+        return get_guild_team(pool, setup.guild, mci.user.id.0 as i64).await;
+    }
+    if setup.current_team() == setup.team_one.role {
+        Ok(Some(setup.team_one.clone()))
+    } else {
+        Ok(Some(setup.team_two.clone()))
+    }
+}
+
+/// Saves the undo checkpoint of a step that passed its checks. An admin acting for a team is
+/// only done acting once the step is accepted, a rejected step keeps them acting
+async fn accept_step(
+    context: &Context<'_>,
+    mci: &Arc<MessageComponentInteraction>,
+    setup: &mut Setup,
+) -> Result<()> {
+    if setup.admin_acting == Some(mci.user.id) {
+        setup.admin_acting = None;
+        mci.channel_id
+            .say(
+                context.serenity_context(),
+                format!(
+                    "🛡️ <@{}> made this step on behalf of <@&{}>",
+                    mci.user.id,
+                    setup.current_team()
+                ),
+            )
+            .await?;
+    }
+    setup.checkpoint();
     Ok(())
 }
 
-async fn server_pick_phase(
+/// Referees & organisers can use the setup admin controls
+async fn is_admin(pool: &PgPool, mci: &MessageComponentInteraction) -> Result<bool> {
+    match mci.member.as_ref() {
+        Some(member) => has_bot_role(pool, member, BotRole::Referee).await,
+        None => Ok(false),
+    }
+}
+
+/// Content and components of the setup message for the current phase
+fn phase_msg(setup: &Setup, maps: &Vec<Map>) -> (String, CreateActionRow) {
+    match setup.current_phase {
+        SetupState::VetoStart => (
+            format!(
+                "<@&{}> chooses to either start the map veto or {}.",
+                setup.veto_start_chooser.unwrap(),
+                decider_choice(setup.series_type)
+            ),
+            create_veto_start_action_row(setup.series_type),
+        ),
+        SetupState::ServerPick => server_phase_start(setup),
+        SetupState::MapVeto => {
+            let vote_type = setup.veto_pick_order[setup.current_step].vote_type;
+            (
+                format!(
+                    "It is <@&{}> turn to {}",
+                    setup.current_team(),
+                    vote_type.to_string()
+                ),
+                create_map_action_row(setup.maps_remaining.clone(), &vote_type),
+            )
+        }
+        SetupState::SidePick => {
+            let map_id = setup.maps_sel[setup.current_step].map_id;
+            (
+                format!(
+                    "It is <@&{}> turn to pick starting side on `{}`",
+                    setup.current_team(),
+                    maps.iter().find(|m| m.id == map_id).unwrap().label()
+                ),
+                create_sidepick_action_row(),
+            )
+        }
+    }
+}
+
+/// Handles the admin control buttons, returns `true` if the setup was ended
+async fn admin_phase(
     pool: &PgPool,
     context: &Context<'_>,
     mci: &Arc<MessageComponentInteraction>,
     setup: &mut Setup,
-    init_veto_msg: &String,
+    maps: &Vec<Map>,
+    current_match: &mut MatchSeries,
+    setup_msg: MessageId,
 ) -> Result<bool> {
-    let t = Team::get_by_member(pool, mci.user.id.0 as i64).await;
-    if let Err(_err) = &t {
-        no_team_resp(context, &mci).await;
+    if !is_admin(pool, mci).await? {
+        mci.create_interaction_response(&context.serenity_context(), |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.ephemeral(true)
+                        .content("Only referees & organisers can use these controls")
+                })
+        })
+        .await?;
         return Ok(false);
     }
-    match t.unwrap() {
-        Some(team) => {
-            if setup.server_veto_team != team.role {
+    match mci.data.custom_id.as_str() {
+        "admin_undo" => {
+            if !setup.undo() {
                 mci.create_interaction_response(&context.serenity_context(), |r| {
                     r.kind(InteractionResponseType::ChannelMessageWithSource)
                         .interaction_response_data(|d| {
-                            d.ephemeral(true)
-                                .content("It is not your team's turn to pick or ban a server")
+                            d.ephemeral(true).content("There is nothing to undo")
                         })
                 })
-                .await
-                .unwrap();
+                .await?;
                 return Ok(false);
             }
-            let choice_loc = mci.data.values.get(0).unwrap();
-            if setup.servers_remaining.len() > 2 {
-                let pos_remove = setup
-                    .servers_remaining
-                    .iter()
-                    .position(|s| &s.location == choice_loc)
-                    .unwrap();
-                let previous_step = if setup.servers_remaining.len() > 2 {
-                    Veto
-                } else {
-                    Pick
-                };
-                setup.servers_remaining.remove(pos_remove);
-                let current_step = if setup.servers_remaining.len() > 2 {
-                    Veto
-                } else {
-                    Pick
-                };
-                let next_team = if setup.server_veto_team == setup.team_one.role {
-                    setup.team_two.role
-                } else {
-                    setup.team_one.role
-                };
-                let content = format!(
-                    "<@&{}> {} `{}`, <@&{}> turn to {} a server",
-                    setup.server_veto_team, previous_step, choice_loc, next_team, current_step
-                );
-                setup.server_veto_team = next_team;
-                mci.create_interaction_response(&context.serenity_context(), |r| {
-                    r.kind(InteractionResponseType::UpdateMessage)
-                        .interaction_response_data(|d| {
-                            d.content(content).components(|c| {
-                                c.add_action_row(create_server_action_row(
-                                    setup.servers_remaining.clone(),
-                                    &current_step,
-                                ))
-                            })
-                        })
+            // undoing the veto start choice or the server pick clears what was saved for it
+            setup.record_progress(pool).await?;
+            let (content, row) = phase_msg(setup, maps);
+            mci.channel_id
+                .edit_message(context.serenity_context(), setup_msg, |m| {
+                    m.content(content).components(|c| c.add_action_row(row))
                 })
-                .await
-                .unwrap();
-                return Ok(false);
-            }
-
-            let server_id = &setup
-                .servers_remaining
-                .iter()
-                .find(|s| &s.location == choice_loc)
-                .unwrap()
-                .server_id;
-            setup.server_id = Some(String::from(server_id));
-            setup.current_phase = SetupState::MapVeto;
-            let content = format!(
-                "<@&{}> picked `{}`, server pick phase completed.\n{}",
-                setup.server_veto_team, choice_loc, init_veto_msg
-            );
+                .await?;
+            mci.create_interaction_response(&context.serenity_context(), |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| {
+                        d.content(format!("🛡️ <@{}> undid the last step", mci.user.id))
+                    })
+            })
+            .await?;
+            Ok(false)
+        }
+        "admin_restart" => {
+            sqlx::query!(
+                "update match_series
+                    set thread = null, coin_toss_winner = null, veto_start_team = null, decider_side_team = null,
+                        server_location = null, server_reserved_at = null
+                    where id = $1",
+                current_match.id
+            )
+            .execute(pool)
+            .await?;
+            current_match.thread = None;
+            mci.channel_id
+                .edit_message(context.serenity_context(), setup_msg, |m| {
+                    m.content("Setup was restarted by an admin.")
+                        .components(|c| c)
+                })
+                .await?;
             mci.create_interaction_response(&context.serenity_context(), |r| {
                 r.kind(InteractionResponseType::UpdateMessage)
                     .interaction_response_data(|d| {
-                        d.content(content).components(|c| {
-                            c.add_action_row(create_map_action_row(
-                                setup.maps_remaining.clone(),
-                                &setup.veto_pick_order[0].vote_type,
-                            ))
-                        })
+                        d.content(format!(
+                            "🛡️ <@{}> restarted the veto, use `/setup` to start again",
+                            mci.user.id
+                        ))
+                        .components(|c| c)
                     })
             })
-            .await
-            .unwrap();
+            .await?;
+            Ok(true)
         }
-        None => {
-            no_team_resp(context, &mci).await;
+        _ => {
+            setup.admin_acting = Some(mci.user.id);
+            let role = setup.current_team();
+            mci.create_interaction_response(&context.serenity_context(), |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| {
+                        d.content(format!(
+                            "🛡️ <@{}> will make the next step on behalf of <@&{}>",
+                            mci.user.id, role
+                        ))
+                    })
+            })
+            .await?;
+            Ok(false)
         }
     }
-    Ok(false)
 }
 
-async fn side_pick_phase(
+/// Location picked & reserved without a server veto, `None` when teams ban locations, with a
+/// note on how the pick was made when a team's home region was missing. Only `auto_or_pick`
+/// falls back to the veto silently, when a team has not set a home region
+async fn auto_server(
     pool: &PgPool,
-    context: &Context<'_>,
-    mci: &Arc<MessageComponentInteraction>,
-    setup: &mut Setup,
-    maps: &Vec<Map>,
-) -> Result<bool> {
-    let option_selected = mci.data.values.get(0).unwrap();
-    let t = Team::get_by_member(pool, mci.user.id.0 as i64).await;
-    if let Err(_err) = &t {
+    guild: i64,
+    match_series: i32,
+    server_selection: ServerSelection,
+    servers: &[ServerTemplates],
+    teams: [&Team; 2],
+) -> Result<(Option<ServerTemplates>, Option<String>)> {
+    if server_selection == ServerSelection::Pick {
+        return Ok((None, None));
+    }
+    let regions = [
+        get_team_region(pool, teams[0].id).await?,
+        get_team_region(pool, teams[1].id).await?,
+    ];
+    let missing: Vec<String> = teams
+        .iter()
+        .zip(&regions)
+        .filter(|(_, region)| region.is_none())
+        .map(|(team, _)| format!("<@&{}>", team.role))
+        .collect();
+    if server_selection == ServerSelection::AutoOrPick && !missing.is_empty() {
+        return Ok((None, None));
+    }
+    let note = match missing.len() {
+        0 => None,
+        1 => Some(format!(
+            "{} has no home region (`/team region`), the server location is picked for the other team.",
+            missing[0]
+        )),
+        _ => {
+            return Ok((
+                None,
+                Some(String::from(
+                    "Neither team has a home region (`/team region`), teams ban server locations instead.",
+                )),
+            ))
+        }
+    };
+    let pings = ServerPing::get_all(pool, guild).await?;
+    let mut candidates = servers.to_vec();
+    // a location that filled up since the setup started is passed over for the next fairest
+    while let Some(server) = pick_fairest(
+        &candidates,
+        &pings,
+        [regions[0].as_deref(), regions[1].as_deref()],
+    )
+    .cloned()
+    {
+        if ServerTemplates::reserve(pool, guild, match_series, &server.location).await? {
+            return Ok((Some(server), note));
+        }
+        candidates.retain(|s| s.location != server.location);
+    }
+    Ok((
+        None,
+        Some(String::from(
+            "Every server location filled up, teams ban server locations instead.",
+        )),
+    ))
+}
+
+/// Moves on to the server veto, or straight to the map veto when the server was picked
+/// automatically
+fn enter_server_phase(setup: &mut Setup) -> (String, CreateActionRow) {
+    let Some(location) = setup.server_location.clone() else {
+        setup.current_phase = SetupState::ServerPick;
+        return server_phase_start(setup);
+    };
+    setup.current_phase = SetupState::MapVeto;
+    (
+        format!(
+            "\nServer `{}` was picked automatically.\n{}",
+            location, setup.veto_start_msg
+        ),
+        create_map_action_row(
+            setup.maps_remaining.clone(),
+            &setup.veto_pick_order[0].vote_type,
+        ),
+    )
+}
+
+fn server_phase_start(setup: &Setup) -> (String, CreateActionRow) {
+    let vote_type = if setup.servers_remaining.len() > 2 {
+        Veto
+    } else {
+        Pick
+    };
+    (
+        format!(
+            "\nIt is <@&{}> turn to {} a server",
+            setup.server_veto_team,
+            vote_type.to_string().to_lowercase()
+        ),
+        create_server_action_row(setup.servers_remaining.clone(), &vote_type),
+    )
+}
+
+async fn veto_start_phase(
+    pool: &PgPool,
+    context: &Context<'_>,
+    mci: &Arc<MessageComponentInteraction>,
+    setup: &mut Setup,
+) -> Result<bool> {
+    let t = acting_team(pool, mci, setup).await;
+    let Ok(Some(team)) = t else {
+        no_team_resp(context, &mci).await;
+        return Ok(false);
+    };
+    let chooser = setup.veto_start_chooser.unwrap();
+    if chooser != team.role {
+        mci.create_interaction_response(&context.serenity_context(), |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.ephemeral(true)
+                        .content("It is not your team's turn to choose")
+                })
+        })
+        .await
+        .unwrap();
+        return Ok(false);
+    }
+    accept_step(context, mci, setup).await?;
+    let choice = match mci.data.custom_id.as_str() {
+        "veto_start" => {
+            setup
+                .start_veto(chooser, Some(setup.other_team(chooser)))
+                .await;
+            "start the map veto"
+        }
+        _ => {
+            setup
+                .start_veto(setup.other_team(chooser), Some(chooser))
+                .await;
+            decider_choice(setup.series_type)
+        }
+    };
+    setup.record_progress(pool).await?;
+    let (content, row) = enter_server_phase(setup);
+    mci.create_interaction_response(&context.serenity_context(), |r| {
+        r.kind(InteractionResponseType::UpdateMessage)
+            .interaction_response_data(|d| {
+                d.content(format!("<@&{}> chose to {}.\n{}", chooser, choice, content))
+                    .components(|c| c.add_action_row(row))
+            })
+    })
+    .await
+    .unwrap();
+    Ok(false)
+}
+
+async fn server_pick_phase(
+    pool: &PgPool,
+    context: &Context<'_>,
+    mci: &Arc<MessageComponentInteraction>,
+    setup: &mut Setup,
+) -> Result<bool> {
+    let t = acting_team(pool, mci, setup).await;
+    if let Err(_err) = &t {
         no_team_resp(context, &mci).await;
         return Ok(false);
     }
     match t.unwrap() {
         Some(team) => {
-            let picked_by = setup.maps_sel[setup.current_step].picked_by_role;
-            let not_picked_by = if picked_by == setup.team_one.role {
-                setup.team_two.role
-            } else {
-                setup.team_one.role
-            };
-            if picked_by == team.role {
+            if setup.server_veto_team != team.role {
                 mci.create_interaction_response(&context.serenity_context(), |r| {
                     r.kind(InteractionResponseType::ChannelMessageWithSource)
                         .interaction_response_data(|d| {
                             d.ephemeral(true)
-                                .content("It is not your team's turn to pick sides")
+                                .content("It is not your team's turn to pick or ban a server")
                         })
                 })
                 .await
                 .unwrap();
                 return Ok(false);
             }
-            if setup.maps_sel.len() != setup.current_step + 1 {
-                let next_team_picked_by = &setup
-                    .maps_sel
-                    .get(setup.current_step + 1)
-                    .unwrap()
-                    .picked_by_role;
-                let next_team = if next_team_picked_by == &(setup.team_one.role) {
+            accept_step(context, mci, setup).await?;
+            let choice_loc = mci.data.values.get(0).unwrap();
+            if setup.servers_remaining.len() > 2 {
+                let pos_remove = setup
+                    .servers_remaining
+                    .iter()
+                    .position(|s| &s.location == choice_loc)
+                    .unwrap();
+                let previous_step = if setup.servers_remaining.len() > 2 {
+                    Veto
+                } else {
+                    Pick
+                };
+                setup.servers_remaining.remove(pos_remove);
+                let current_step = if setup.servers_remaining.len() > 2 {
+                    Veto
+                } else {
+                    Pick
+                };
+                let next_team = if setup.server_veto_team == setup.team_one.role {
                     setup.team_two.role
                 } else {
                     setup.team_one.role
                 };
-                let next_map = &setup.maps_sel.get(setup.current_step + 1).unwrap().map_id;
-                let next_map_name = &maps.iter().find(|m| &m.id == next_map).unwrap().name;
+                let content = format!(
+                    "<@&{}> {} `{}`, <@&{}> turn to {} a server",
+                    setup.server_veto_team, previous_step, choice_loc, next_team, current_step
+                );
+                setup.server_veto_team = next_team;
+                mci.create_interaction_response(&context.serenity_context(), |r| {
+                    r.kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|d| {
+                            d.content(content).components(|c| {
+                                c.add_action_row(create_server_action_row(
+                                    setup.servers_remaining.clone(),
+                                    &current_step,
+                                ))
+                            })
+                        })
+                })
+                .await
+                .unwrap();
+                return Ok(false);
+            }
+
+            let server = setup
+                .servers_remaining
+                .iter()
+                .find(|s| &s.location == choice_loc)
+                .unwrap();
+            setup.server_id = Some(server.server_id.clone());
+            setup.server_self_hosted = server.self_hosted;
+            setup.server_location = Some(choice_loc.clone());
+            setup.current_phase = SetupState::MapVeto;
+            let reserved = ServerTemplates::reserve(
+                pool,
+                setup.guild,
+                setup.match_series.unwrap(),
+                choice_loc,
+            )
+            .await?;
+            let mut content = format!(
+                "<@&{}> picked `{}`, server pick phase completed.\n{}",
+                setup.server_veto_team, choice_loc, setup.veto_start_msg
+            );
+            if !reserved {
+                content.push_str(&format!(
+                    "\n⚠️ `{}` filled up during the veto, the server can only be started once a series there has finished.",
+                    choice_loc
+                ));
+            }
+            mci.create_interaction_response(&context.serenity_context(), |r| {
+                r.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|d| {
+                        d.content(content).components(|c| {
+                            c.add_action_row(create_map_action_row(
+                                setup.maps_remaining.clone(),
+                                &setup.veto_pick_order[0].vote_type,
+                            ))
+                        })
+                    })
+            })
+            .await
+            .unwrap();
+        }
+        None => {
+            no_team_resp(context, &mci).await;
+        }
+    }
+    Ok(false)
+}
+
+async fn side_pick_phase(
+    pool: &PgPool,
+    context: &Context<'_>,
+    mci: &Arc<MessageComponentInteraction>,
+    setup: &mut Setup,
+    maps: &Vec<Map>,
+) -> Result<bool> {
+    let option_selected = mci.data.values.get(0).unwrap();
+    let t = acting_team(pool, mci, setup).await;
+    if let Err(_err) = &t {
+        no_team_resp(context, &mci).await;
+        return Ok(false);
+    }
+    match t.unwrap() {
+        Some(team) => {
+            let side_pick_team = setup.side_pick_team(setup.current_step);
+            let other_team = setup.other_team(side_pick_team);
+            if side_pick_team != team.role {
+                mci.create_interaction_response(&context.serenity_context(), |r| {
+                    r.kind(InteractionResponseType::ChannelMessageWithSource)
+                        .interaction_response_data(|d| {
+                            d.ephemeral(true)
+                                .content("It is not your team's turn to pick sides")
+                        })
+                })
+                .await
+                .unwrap();
+                return Ok(false);
+            }
+            accept_step(context, mci, setup).await?;
+            let next_step = setup.next_side_pick(setup.current_step + 1);
+            if let Some(next_step) = next_step {
+                let next_team = setup.side_pick_team(next_step);
+                let next_map = &setup.maps_sel.get(next_step).unwrap().map_id;
+                let next_map_name = maps.iter().find(|m| &m.id == next_map).unwrap().label();
                 mci.create_interaction_response(&context.serenity_context(), |r| {
                     r.kind(InteractionResponseType::UpdateMessage)
                         .interaction_response_data(|d| {
@@ -629,15 +1374,15 @@ async fn side_pick_phase(
                 .unwrap();
             }
             if option_selected == &String::from("ct") {
-                setup.maps_sel[setup.current_step].start_ct_team_role = Some(not_picked_by);
-                setup.maps_sel[setup.current_step].start_t_team_role = Some(picked_by);
+                setup.maps_sel[setup.current_step].start_ct_team_role = Some(side_pick_team);
+                setup.maps_sel[setup.current_step].start_t_team_role = Some(other_team);
             } else {
-                setup.maps_sel[setup.current_step].start_t_team_role = Some(not_picked_by);
-                setup.maps_sel[setup.current_step].start_ct_team_role = Some(picked_by);
+                setup.maps_sel[setup.current_step].start_t_team_role = Some(side_pick_team);
+                setup.maps_sel[setup.current_step].start_ct_team_role = Some(other_team);
             }
-            setup.current_step += 1;
-            if setup.maps_sel.len() == setup.current_step {
-                return Ok(true);
+            match next_step {
+                Some(next_step) => setup.current_step = next_step,
+                None => return Ok(true),
             }
         }
         None => {
@@ -647,47 +1392,56 @@ async fn side_pick_phase(
     Ok(false)
 }
 
+/// Link for a connect button, a failing shortener falls back to showing the address
+async fn connect_link(
+    links: &dyn ConnectLinks,
+    series: i32,
+    target: ConnectTarget,
+    address: &str,
+) -> Option<String> {
+    links
+        .link(series, target, address)
+        .await
+        .unwrap_or_else(|err| {
+            log::error!("{:#?}", err);
+            None
+        })
+}
+
 pub async fn send_conn_msg(
     context: &Context<'_>,
     pool: &PgPool,
-    msg: &Arc<MessageComponentInteraction>,
+    channel: ChannelId,
     setup: &Setup,
     server: ServerDuplicateResponse,
-) {
-    let client = Client::new();
+) -> Result<()> {
     let game_url = format!("{}:{}", server.ip, server.ports.game);
     let gotv_url = format!("{}:{}", server.ip, server.ports.gotv);
-    let url_link = format!("steam://connect/{}", &game_url);
-    let gotv_link = format!("steam://connect/{}", &gotv_url);
-    let resp = client
-        .get(format!(
-            "https://tinyurl.com/api-create.php?url={}",
-            encode(&url_link)
-        ))
-        .send()
-        .await
-        .unwrap();
-    let t_url = resp.text_with_charset("utf-8").await.unwrap();
-    let resp = client
-        .get(format!(
-            "https://tinyurl.com/api-create.php?url={}",
-            encode(&gotv_link)
-        ))
-        .send()
-        .await
-        .unwrap();
-    let t_gotv_url = resp.text_with_charset("utf-8").await.unwrap();
+    let series = setup.match_series.unwrap();
+    let links = context.data().connect_links.as_ref();
+    let conn_link = connect_link(links, series, ConnectTarget::Game, &game_url).await;
+    let gotv_link = connect_link(links, series, ConnectTarget::Gotv, &gotv_url).await;
 
-    let eos = eos_str(pool, &setup).await.unwrap();
-    let mut m = msg
-        .channel_id
+    let eos = eos_str(pool, setup).await?;
+    let (Some(conn_link), Some(gotv_link)) = (conn_link, gotv_link) else {
+        // without web links the addresses are posted as text
+        channel
+            .send_message(&context.serenity_context(), |m| {
+                m.content(format!(
+                    "{}\nConsole: `connect {}`\nGOTV: `connect {}`",
+                    eos, &game_url, &gotv_url
+                ))
+            })
+            .await?;
+        return Ok(());
+    };
+    let mut m = channel
         .send_message(&context.serenity_context(), |m| {
             m.content(eos).components(|c| {
-                c.add_action_row(create_server_conn_button_row(&t_url, &t_gotv_url, true))
+                c.add_action_row(create_server_conn_button_row(&conn_link, &gotv_link, true))
             })
         })
-        .await
-        .unwrap();
+        .await?;
     let mut cib = m
         .await_component_interactions(&context.serenity_context())
         .timeout(Duration::from_secs(60 * 5))
@@ -705,23 +1459,24 @@ pub async fn send_conn_msg(
                             ))
                         })
                 })
-                .await
-                .unwrap();
+                .await?;
             }
             None => {
                 // remove console cmds interaction on timeout
-                let eos = eos_str(pool, &setup).await.unwrap();
+                let eos = eos_str(pool, setup).await?;
                 m.edit(&context.serenity_context(), |m| {
                     m.content(eos).components(|c| {
-                        c.add_action_row(create_server_conn_button_row(&t_url, &t_gotv_url, false))
+                        c.add_action_row(create_server_conn_button_row(
+                            &conn_link, &gotv_link, false,
+                        ))
                     })
                 })
-                .await
-                .unwrap();
+                .await?;
                 break;
             }
         }
     }
+    Ok(())
 }
 
 pub fn create_server_conn_button_row(
@@ -762,7 +1517,7 @@ async fn map_veto_phase(
     curr_series: &MatchSeries,
 ) -> Result<bool> {
     let map_selected = mci.data.values.get(0).unwrap();
-    let t = Team::get_by_member(pool, mci.user.id.0 as i64).await;
+    let t = acting_team(pool, mci, setup).await;
     if let Err(_err) = &t {
         no_team_resp(context, &mci).await;
         return Ok(false);
@@ -783,7 +1538,12 @@ async fn map_veto_phase(
                 return Ok(false);
             }
 
-            let selected_map_id = maps.iter().find(|m| &m.name == map_selected).unwrap().id;
+            accept_step(context, mci, setup).await?;
+            let selected_map_id = maps
+                .iter()
+                .find(|m| m.name.eq_ignore_ascii_case(map_selected))
+                .unwrap()
+                .id;
             if setup.veto_pick_order[setup.current_step].vote_type == Pick {
                 setup.maps_sel.push(NewMatch {
                     map_id: selected_map_id,
@@ -795,13 +1555,26 @@ async fn map_veto_phase(
             setup.veto_pick_order[setup.current_step].map = Some(selected_map_id);
 
             if setup.veto_pick_order.len() == setup.current_step + 1 {
-                let first_map = setup.maps_sel.get(0).unwrap();
-                let other_role_id = if setup.maps_sel[0].picked_by_role == setup.team_one.role {
-                    setup.team_two.role
-                } else {
-                    setup.team_one.role
+                setup.assign_knife_sides();
+                let Some(next_step) = setup.next_side_pick(0) else {
+                    mci.create_interaction_response(&context.serenity_context(), |r| {
+                        r.kind(InteractionResponseType::UpdateMessage)
+                            .interaction_response_data(|d| {
+                                d.content("Map veto completed.\nStarting sides will be decided by knife round.")
+                                    .components(|c| c)
+                            })
+                    })
+                    .await
+                    .unwrap();
+                    return Ok(true);
                 };
-                let next_map_name = &maps.iter().find(|m| m.id == first_map.map_id).unwrap().name;
+                let first_map = setup.maps_sel.get(next_step).unwrap();
+                let other_role_id = setup.side_pick_team(next_step);
+                let next_map_name = maps
+                    .iter()
+                    .find(|m| m.id == first_map.map_id)
+                    .unwrap()
+                    .label();
                 mci.create_interaction_response(&context.serenity_context(), |r| {
                     r.kind(InteractionResponseType::UpdateMessage)
                         .interaction_response_data(|d| {
@@ -814,7 +1587,7 @@ async fn map_veto_phase(
                 })
                     .await
                     .unwrap();
-                setup.current_step = 0;
+                setup.current_step = next_step;
                 setup.current_phase = SetupState::SidePick;
                 return Ok(false);
             }
@@ -828,7 +1601,7 @@ async fn map_veto_phase(
             let map_index = setup
                 .maps_remaining
                 .iter()
-                .position(|m| m == map_selected)
+                .position(|m| m.id == selected_map_id)
                 .unwrap();
             setup.maps_remaining.remove(map_index);
             let curr_vote_info: Vec<VoteInfo> = setup
@@ -879,22 +1652,45 @@ async fn map_veto_phase(
 
 pub(crate) async fn eos_str(pool: &PgPool, setup: &Setup) -> Result<String> {
     let mut resp = String::from("\n\nSetup is completed. GLHF!\n\n");
-    let maps = Map::get_all(pool, true).await?;
+    let maps = Map::get_all(pool, setup.guild, true).await?;
     for (i, el) in setup.maps_sel.iter().enumerate() {
         resp.push_str(
             format!(
-                "**{}. {}** - picked by: <@&{}>\n",
+                "**{}. {}** - picked by: <@&{}>",
                 i + 1,
-                maps.iter().find(|m| m.id == el.map_id).unwrap().name,
+                maps.iter().find(|m| m.id == el.map_id).unwrap().label(),
                 &el.picked_by_role,
             )
             .as_str(),
-        )
+        );
+        if setup.is_knife(i) {
+            resp.push_str(" - 🔪 knife round");
+        } else if let Some(ct) = el.start_ct_team_role {
+            resp.push_str(format!(" - <@&{}> starts CT", ct).as_str());
+        }
+        resp.push('\n');
+    }
+    if let Some(location) = &setup.server_location {
+        resp.push_str(format!("\nServer: `{}`\n", location).as_str());
     }
     Ok(resp)
 }
 
-pub fn create_map_action_row(map_list: Vec<String>, vote_type: &VoteType) -> CreateActionRow {
+/// Posts the outcome of the veto to the announcement channel
+async fn announce_setup(context: &Context<'_>, pool: &PgPool, setup: &Setup) -> Result<()> {
+    let mut embed = CreateEmbed::default();
+    embed
+        .title(format!(
+            "{} vs {} `#{}`",
+            setup.team_one.name,
+            setup.team_two.name,
+            setup.match_series.unwrap()
+        ))
+        .description(eos_str(pool, setup).await?.trim());
+    announce(context.serenity_context(), pool, setup.guild, embed, None).await
+}
+
+pub fn create_map_action_row(map_list: Vec<Map>, vote_type: &VoteType) -> CreateActionRow {
     let mut ar = CreateActionRow::default();
     let mut menu = CreateSelectMenu::default();
     menu.custom_id("map_select");
@@ -903,11 +1699,8 @@ pub fn create_map_action_row(map_list: Vec<String>, vote_type: &VoteType) -> Cre
         vote_type.to_string().to_lowercase()
     ));
     let mut options = Vec::new();
-    for map_name in map_list {
-        options.push(create_menu_option(
-            &map_name,
-            &map_name.to_ascii_lowercase(),
-        ))
+    for map in map_list {
+        options.push(create_menu_option(map.label(), &map.name))
     }
     menu.options(|f| f.set_options(options));
     ar.add_select_menu(menu);
@@ -943,6 +1736,63 @@ pub fn create_menu_option(label: &str, value: &str) -> CreateSelectMenuOption {
     opt
 }
 
+pub fn create_admin_action_row() -> CreateActionRow {
+    let mut ar = CreateActionRow::default();
+    let mut undo_button = CreateButton::default();
+    undo_button.custom_id("admin_undo");
+    undo_button.label("Undo last step");
+    undo_button.style(ButtonStyle::Secondary);
+    ar.add_button(undo_button);
+    let mut act_button = CreateButton::default();
+    act_button.custom_id("admin_act");
+    act_button.label("Act for team");
+    act_button.style(ButtonStyle::Primary);
+    ar.add_button(act_button);
+    let mut restart_button = CreateButton::default();
+    restart_button.custom_id("admin_restart");
+    restart_button.label("Restart veto");
+    restart_button.style(ButtonStyle::Danger);
+    ar.add_button(restart_button);
+    ar
+}
+
+pub fn create_start_retry_action_row() -> CreateActionRow {
+    let mut ar = CreateActionRow::default();
+    let mut retry_button = CreateButton::default();
+    retry_button.custom_id("start_retry");
+    retry_button.label("Retry server start");
+    retry_button.style(ButtonStyle::Primary);
+    ar.add_button(retry_button);
+    ar
+}
+
+/// Alternative to starting the map veto. The team picking a Bo1's map never also picks its side,
+/// so there the other team starts the veto and the chooser gets the map pick
+fn decider_choice(series_type: SeriesType) -> &'static str {
+    match series_type {
+        Bo1 => "pick the map",
+        Bo3 | Bo5 => "pick a side on the decider map",
+    }
+}
+
+pub fn create_veto_start_action_row(series_type: SeriesType) -> CreateActionRow {
+    let mut ar = CreateActionRow::default();
+    let mut start_button = CreateButton::default();
+    start_button.custom_id("veto_start");
+    start_button.label("Start veto");
+    start_button.style(ButtonStyle::Primary);
+    ar.add_button(start_button);
+    let mut side_button = CreateButton::default();
+    side_button.custom_id("decider_side");
+    side_button.label(match series_type {
+        Bo1 => "Pick the map",
+        Bo3 | Bo5 => "Choose side on decider",
+    });
+    side_button.style(ButtonStyle::Secondary);
+    ar.add_button(side_button);
+    ar
+}
+
 pub fn create_sidepick_action_row() -> CreateActionRow {
     let mut ar = CreateActionRow::default();
     let mut menu = CreateSelectMenu::default();
@@ -968,184 +1818,654 @@ async fn no_team_resp(context: &Context<'_>, mci: &Arc<MessageComponentInteracti
     .unwrap();
 }
 
+/// Players of a match config from a `team_conn_str`, keyed by SteamID64 which both plugins accept
+fn config_players(conn_str: &str) -> BTreeMap<String, String> {
+    conn_str
+        .split(',')
+        .filter_map(|id| {
+            SteamId::parse_steam2id(id, AccountType::Individual, Instance::Desktop).ok()
+        })
+        .map(|mut steamid| {
+            steamid.set_universe(Universe::Public);
+            (u64::from(steamid).to_string(), String::new())
+        })
+        .collect()
+}
+
+/// Attempts of a server start step before the start fails
+const START_STEP_ATTEMPTS: u32 = 3;
+/// Time a single attempt of a server start step may take
+const START_STEP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Wait before the next attempt, multiplied by the attempt number
+const START_STEP_BACKOFF: Duration = Duration::from_secs(2);
+/// How long admins can retry a failed server start
+const START_RETRY_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Progress bar message of a server start
+struct StartProgress<'a> {
+    serenity: &'a serenity::client::Context,
+    msg: &'a mut Message,
+    steps: usize,
+}
+
+impl StartProgress<'_> {
+    async fn show(&mut self, step: usize, status: &str) {
+        let content = format!(
+            "Match setup completed, starting server...\n[{}{}]⏳ _{}..._",
+            "🌕".repeat(step + 1),
+            "🌑".repeat(self.steps - step),
+            status
+        );
+        if let Err(err) = self
+            .msg
+            .edit(self.serenity, |m| m.content(content).components(|c| c))
+            .await
+        {
+            log::error!("{:#?}", err);
+        }
+    }
+    /// Runs an idempotent step of the server start, failed or timed out attempts are retried
+    async fn run<T, F, Fut>(&mut self, step: usize, status: &str, mut attempt_step: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.show(step, status).await;
+        let mut attempt = 1;
+        loop {
+            let result = match tokio::time::timeout(START_STEP_TIMEOUT, attempt_step()).await {
+                Ok(result) => result,
+                Err(_) => Err(anyhow!("timed out after {}s", START_STEP_TIMEOUT.as_secs())),
+            };
+            match result {
+                Ok(value) => return Ok(value),
+                Err(err) if attempt < START_STEP_ATTEMPTS => {
+                    log::warn!(
+                        "{} failed (attempt {}/{}): {:#}",
+                        status,
+                        attempt,
+                        START_STEP_ATTEMPTS,
+                        err
+                    );
+                    tokio::time::sleep(START_STEP_BACKOFF * attempt).await;
+                    attempt += 1;
+                    let status =
+                        format!("{} (attempt {}/{})", status, attempt, START_STEP_ATTEMPTS);
+                    self.show(step, &status).await;
+                }
+                Err(err) => return Err(err.context(format!("{} failed", status))),
+            }
+        }
+    }
+    /// Runs a step that must not happen twice in a single attempt, a timed out attempt may
+    /// still have succeeded so it is not retried either
+    async fn run_once<T, Fut>(&mut self, step: usize, status: &str, step_future: Fut) -> Result<T>
+    where
+        Fut: Future<Output = Result<T>>,
+    {
+        self.show(step, status).await;
+        match tokio::time::timeout(START_STEP_TIMEOUT, step_future).await {
+            Ok(result) => result.map_err(|err| err.context(format!("{} failed", status))),
+            Err(_) => Err(anyhow!(
+                "{} timed out after {}s",
+                status,
+                START_STEP_TIMEOUT.as_secs()
+            )),
+        }
+    }
+    async fn done(&mut self) {
+        let content = format!(
+            "Match setup completed, server started\n[{}]",
+            "🌕".repeat(self.steps + 1)
+        );
+        if let Err(err) = self.msg.edit(self.serenity, |m| m.content(content)).await {
+            log::error!("{:#?}", err);
+        }
+    }
+}
+
+/// Dathost request that fails on error statuses
+async fn dathost_request(request: RequestBuilder) -> Result<Response> {
+    Ok(track_request("dathost", request)
+        .await?
+        .error_for_status()?)
+}
+
+/// Loads the match on a free self-hosted server of the picked location, the plugin
+/// downloads the config from the API
+async fn start_self_hosted_server(
+    context: &Context<'_>,
+    pool: &PgPool,
+    msg: &mut Message,
+    setup: &mut Setup,
+    current_match: &mut MatchSeries,
+) -> Result<ServerDuplicateResponse, Error> {
+    let mut progress = StartProgress {
+        serenity: context.serenity_context(),
+        msg,
+        steps: 2,
+    };
+    let location = setup.server_location.clone().unwrap();
+    let plugin = setup.game.match_plugin();
+    let guild = setup.guild;
+    let (public_url, storage) = {
+        let config = context.data().config.read().unwrap();
+        (config.public_url.clone(), config.storage.endpoint.is_some())
+    };
+    let public_url =
+        public_url.ok_or_else(|| anyhow!("`public_url` must be set to use self-hosted servers"))?;
+    let match_series = setup.match_series.unwrap();
+    let server = progress
+        .run(0, "Finding a free server", || async {
+            RconServer::claim(pool, guild, &location, plugin, match_series)
+                .await?
+                .ok_or_else(|| anyhow!("all servers in `{}` are in use", location))
+        })
+        .await?;
+    let loaded: Result<()> = async {
+        setup.team_one_conn_str = Some(team_conn_str(setup.team_one.role, pool).await?);
+        setup.team_two_conn_str = Some(team_conn_str(setup.team_two.role, pool).await?);
+        let token = MatchConfig::new_token();
+        let mut match_config = setup.match_config(pool).await?;
+        match_config.cvars.insert(
+            plugin.remote_log_cvar().to_string(),
+            format!(
+                "{}/api/match-events/{}",
+                public_url.trim_end_matches('/'),
+                token
+            ),
+        );
+        // demos of self-hosted servers can't be downloaded like Dathost's, they are uploaded
+        if storage {
+            match_config.cvars.insert(
+                plugin.demo_upload_cvar().to_string(),
+                format!(
+                    "{}/api/demo-uploads/{}",
+                    public_url.trim_end_matches('/'),
+                    token
+                ),
+            );
+        }
+        match_config.save(pool, match_series, &token).await?;
+        let url = format!(
+            "{}/api/match-configs/{}",
+            public_url.trim_end_matches('/'),
+            token
+        );
+        let output = progress
+            .run(1, "Loading match config", || server.load_match(&url))
+            .await?;
+        log::info!(
+            "{} loaded match {}: {}",
+            server.address(),
+            match_series,
+            output
+        );
+        current_match
+            .update_dathost_match(pool, match_config.matchid)
+            .await?;
+        Ok(())
+    }
+    .await;
+    if let Err(err) = loaded {
+        if let Err(err) = server.release(pool, match_series).await {
+            log::error!("Releasing server {} failed: {:#}", server.address(), err);
+        }
+        return Err(err);
+    }
+
+    setup.server_hostname = Some(server.hostname.clone());
+    setup.server_game_port = Some(server.game_port as i64);
+    setup.server_gotv_port = Some(server.gotv_port as i64);
+    progress.done().await;
+    Ok(ServerDuplicateResponse {
+        game: None,
+        id: server.server_id(),
+        ip: server.hostname,
+        ports: Ports {
+            game: server.game_port as i64,
+            gotv: server.gotv_port as i64,
+        },
+        location: Some(location),
+    })
+}
+
+/// Starts the series on a duplicate of the picked template, or on a self-hosted server. A
+/// duplicate that fails to start is deleted again and its GSLT released
 pub async fn start_server(
     context: &Context<'_>,
     pool: &PgPool,
-    mci: &Arc<MessageComponentInteraction>,
+    msg: &mut Message,
     setup: &mut Setup,
     current_match: &mut MatchSeries,
 ) -> Result<ServerDuplicateResponse, Error> {
-    println!("{:#?}", setup);
-    mci.message.delete(&context.serenity_context()).await?;
-    let mut msg = mci.channel_id.send_message(&context.serenity_context(), |m| {
-        m.content("Match setup completed, starting server...\n[🌕🌑🌑🌑🌑]⏳ _Duplicating server template..._")
-            .components(|c| c)
-    }).await?;
+    // the undo history holds a copy of the setup per step, leave it out
+    log::debug!(
+        "{:#?}",
+        Setup {
+            history: vec![],
+            ..setup.clone()
+        }
+    );
+    if setup.server_self_hosted {
+        return start_self_hosted_server(context, pool, msg, setup, current_match).await;
+    }
+    let mut progress = StartProgress {
+        serenity: context.serenity_context(),
+        msg,
+        steps: 5,
+    };
+    let template_id = setup.server_id.clone().unwrap();
+    let location = setup.server_location.clone().unwrap();
+    // the slot reserved at the pick may have expired or been taken while the start was retried
+    if !ServerTemplates::reserve(pool, setup.guild, setup.match_series.unwrap(), &location).await? {
+        bail!(
+            "`{}` is at capacity, retry once a series there has finished",
+            location
+        );
+    }
     let sync_url = format!(
         "https://dathost.net/api/0.1/game-servers/{server_id}/sync-files",
-        server_id = encode(&setup.server_id.clone().unwrap())
+        server_id = encode(&template_id)
     );
-    let dathost_config = get_dathost_config();
-    let client = Client::new();
-    client
-        .post(sync_url)
-        .basic_auth(&dathost_config.user, Some(&dathost_config.password))
-        .send()
+    let config = context.data().config.read().unwrap().clone();
+    let guild_config = GuildConfig::get(pool, setup.guild).await?;
+    let dathost_config = guild_config.dathost_config(&config)?;
+    // rollbacks run outside of the progress steps, the request timeout bounds them as well
+    let client = Client::builder().timeout(START_STEP_TIMEOUT).build()?;
+    progress
+        .run(0, "Syncing server template files", || {
+            dathost_request(
+                client
+                    .post(&sync_url)
+                    .basic_auth(&dathost_config.user, Some(&dathost_config.password)),
+            )
+        })
         .await?;
     let dupl_url = format!(
         "https://dathost.net/api/0.1/game-servers/{}/duplicate",
-        encode(&setup.server_id.clone().unwrap())
+        encode(&template_id)
     );
-    let dupl_resp = client
-        .post(dupl_url)
-        .basic_auth(&dathost_config.user, Some(&dathost_config.password))
-        .send()
-        .await?
-        .json::<ServerDuplicateResponse>()
+    // a failed or timed out duplication may still have created a server, which is found by
+    // comparing the servers carrying the template's name before & after
+    let servers_before = progress
+        .run(1, "Looking up servers", || {
+            list_game_servers(&client, &dathost_config)
+        })
         .await?;
-
-    msg.edit(&context.serenity_context(), |m| {
-        m.content("Match setup completed, starting server...\n[🌕🌕🌑🌑]⏳ _Setting GSLT token..._")
-    })
-    .await?;
+    let template_name = servers_before
+        .iter()
+        .find(|s| s.id == template_id)
+        .map(|s| s.name.clone());
+    let duplicated = progress
+        .run_once(1, "Duplicating server template", async {
+            Ok(dathost_request(
+                client
+                    .post(&dupl_url)
+                    .basic_auth(&dathost_config.user, Some(&dathost_config.password)),
+            )
+            .await?
+            .json::<ServerDuplicateResponse>()
+            .await?)
+        })
+        .await;
+    let dupl_resp = match duplicated {
+        Ok(dupl_resp) => dupl_resp,
+        Err(err) => {
+            if let Some(name) = template_name {
+                rollback_duplicates(pool, &client, &dathost_config, &name, &servers_before).await;
+            }
+            return Err(err);
+        }
+    };
 
     let server_id = dupl_resp.id.clone();
     setup.server_hostname = dupl_resp.game.clone();
-    setup.server_game_port = Some(dupl_resp.ports.game.clone());
-    setup.server_gotv_port = Some(dupl_resp.ports.gotv.clone());
-    let gslt = create_gslt(&server_id, setup.match_series.unwrap()).await?;
-    println!("setting gslt '{}'", &gslt);
-    client
-        .put(format!(
-            "https://dathost.net/api/0.1/game-servers/{}",
-            encode(&server_id.to_string())
-        ))
-        .form(&[
+    setup.server_game_port = Some(dupl_resp.ports.game);
+    setup.server_gotv_port = Some(dupl_resp.ports.gotv);
+    let started: Result<()> = async {
+        let match_series = setup.match_series.unwrap();
+        let steam = context.data().steam.clone();
+        let app_id = setup.game.app_id();
+        let gslt = progress
+            .run(2, "Setting GSLT token", || {
+                assign_gslt(pool, steam.as_ref(), app_id, &server_id, match_series)
+            })
+            .await?;
+        let game_settings = setup.game.dathost_settings();
+        let mut settings = vec![
             (
-                "name",
-                format!("match-server-{}", setup.match_series.unwrap()),
+                String::from("name"),
+                format!("match-server-{}", match_series),
             ),
-            ("csgo_settings.steam_game_server_login_token", gslt.clone()),
-        ])
-        .basic_auth(&dathost_config.user, Some(&dathost_config.password))
-        .send()
-        .await?;
+            (
+                format!("{}.steam_game_server_login_token", game_settings),
+                gslt.clone(),
+            ),
+        ];
+        let first_map = Map::get(pool, setup.maps_sel[0].map_id).await?;
+        if let Some(workshop_id) = first_map.workshop_id {
+            // server has to boot into the workshop map so it gets downloaded before the match starts
+            settings.push((
+                format!("{}.workshop_start_map_id", game_settings),
+                workshop_id,
+            ));
+        }
+        let settings_url = format!(
+            "https://dathost.net/api/0.1/game-servers/{}",
+            encode(&server_id)
+        );
+        progress
+            .run(3, "Updating server settings", || {
+                dathost_request(
+                    client
+                        .put(&settings_url)
+                        .form(&settings)
+                        .basic_auth(&dathost_config.user, Some(&dathost_config.password)),
+                )
+            })
+            .await?;
 
-    msg.edit(&context.serenity_context(), |m| {
-        m.content(
-            "Match setup completed, starting server...\n[🌕🌕🌕🌑]⏳ _Start server from match config..._",
+        setup.team_one_conn_str = Some(team_conn_str(setup.team_one.role, pool).await?);
+        setup.team_two_conn_str = Some(team_conn_str(setup.team_two.role, pool).await?);
+        let match_end_webhook_url = guild_config.match_end_webhook_url(&config);
+        let round_end_webhook_url = guild_config.round_end_webhook_url(&config);
+        let series_end_webhook_url = guild_config.series_end_webhook_url(&config);
+        let setup: &Setup = setup;
+        let start_info = progress
+            .run_once(4, "Start server from match config", async {
+                let start_resp = match setup.series_type {
+                    Bo1 if setup.game == Game::Cs2 => {
+                        start_cs2_match(
+                            server_id.clone(),
+                            setup,
+                            client.clone(),
+                            &dathost_config,
+                            pool,
+                            match_end_webhook_url.clone(),
+                            round_end_webhook_url.clone(),
+                        )
+                        .await?
+                    }
+                    Bo1 => {
+                        start_match(
+                            server_id.clone(),
+                            setup,
+                            client.clone(),
+                            &dathost_config,
+                            pool,
+                            match_end_webhook_url.clone(),
+                            round_end_webhook_url.clone(),
+                        )
+                        .await?
+                    }
+                    Bo3 | Bo5 => {
+                        start_series_match(
+                            server_id.clone(),
+                            setup,
+                            client.clone(),
+                            &dathost_config,
+                            pool,
+                            match_end_webhook_url.clone(),
+                            round_end_webhook_url.clone(),
+                            series_end_webhook_url.clone(),
+                        )
+                        .await?
+                    }
+                };
+                Ok(start_resp.json::<DathostStartResponse>().await?)
+            })
+            .await?;
+        current_match
+            .update_dathost_match(pool, start_info.id)
+            .await?;
+        Server::add(
+            pool,
+            match_series,
+            &dupl_resp.id,
+            &dupl_resp.ip,
+            dupl_resp.ports.game as i32,
+            dupl_resp.ports.gotv as i32,
         )
-    })
+        .await?;
+        Ok(())
+    }
+    .await;
+    if let Err(err) = started {
+        rollback_dathost_server(pool, &client, &dathost_config, &server_id).await;
+        // the series may already point at the match started on the deleted server
+        if let Err(err) = clear_started_match(pool, current_match).await {
+            log::error!(
+                "Clearing the match of {} failed: {:#}",
+                current_match.id,
+                err
+            );
+        }
+        return Err(err);
+    }
+
+    progress.done().await;
+    Ok(dupl_resp)
+}
+
+/// Dathost game server as listed by the API
+#[derive(Debug, Clone, Deserialize)]
+struct DathostServer {
+    id: String,
+    name: String,
+}
+
+async fn list_game_servers(
+    client: &Client,
+    dathost_config: &DathostConfig,
+) -> Result<Vec<DathostServer>> {
+    Ok(dathost_request(
+        client
+            .get("https://dathost.net/api/0.1/game-servers")
+            .basic_auth(&dathost_config.user, Some(&dathost_config.password)),
+    )
+    .await?
+    .json::<Vec<DathostServer>>()
+    .await?)
+}
+
+/// Deletes servers named `name` that were not in `before`, created by a duplication that
+/// failed or timed out
+async fn rollback_duplicates(
+    pool: &PgPool,
+    client: &Client,
+    dathost_config: &DathostConfig,
+    name: &str,
+    before: &[DathostServer],
+) {
+    let servers = match list_game_servers(client, dathost_config).await {
+        Ok(servers) => servers,
+        Err(err) => {
+            log::error!("Looking up duplicates of `{}` failed: {:#}", name, err);
+            return;
+        }
+    };
+    for server in servers {
+        if server.name == name && !before.iter().any(|s| s.id == server.id) {
+            log::warn!("Deleting server {} left by a failed duplication", server.id);
+            rollback_dathost_server(pool, client, dathost_config, &server.id).await;
+        }
+    }
+}
+
+/// Unsets the match & location reservation of a series whose server was rolled back
+async fn clear_started_match(pool: &PgPool, current_match: &mut MatchSeries) -> Result<()> {
+    sqlx::query!(
+        "update match_series set dathost_match = null, server_reserved_at = null where id = $1",
+        current_match.id,
+    )
+    .execute(pool)
     .await?;
+    current_match.dathost_match = None;
+    Ok(())
+}
 
-    setup.team_one_conn_str = Some(team_conn_str(setup.team_one.role, pool).await?);
-    setup.team_two_conn_str = Some(team_conn_str(setup.team_two.role, pool).await?);
-    println!(
-        "starting match\nteam1 '{}'\nteam2: '{}'",
-        setup.clone().team_one_conn_str.unwrap(),
-        setup.clone().team_two_conn_str.unwrap()
+/// Deletes a duplicated server that failed to start and releases its GSLT
+async fn rollback_dathost_server(
+    pool: &PgPool,
+    client: &Client,
+    dathost_config: &DathostConfig,
+    server_id: &str,
+) {
+    let url = format!(
+        "https://dathost.net/api/0.1/game-servers/{}",
+        encode(server_id)
     );
-    let match_end_webhook_url = match env::var("MATCH_END_WEBHOOK_URL") {
-        Ok(url) => url,
-        Err(_) => String::new(),
-    };
-    let round_end_webhook_url = match env::var("ROUND_END_WEBHOOK_URL") {
-        Ok(url) => url,
-        Err(_) => String::new(),
-    };
-    let series_end_webhook_url = match env::var("SERIES_END_WEBHOOK_URL") {
-        Ok(url) => url,
-        Err(_) => String::new(),
-    };
-    let start_resp = match setup.series_type {
-        Bo1 => {
-            start_match(
-                server_id,
-                setup,
-                client,
-                &dathost_config,
-                pool,
-                match_end_webhook_url,
-                round_end_webhook_url,
-            )
+    let deleted = dathost_request(
+        client
+            .delete(url)
+            .basic_auth(&dathost_config.user, Some(&dathost_config.password)),
+    )
+    .await;
+    if let Err(err) = deleted {
+        log::error!("Deleting server {} failed: {:#}", server_id, err);
+    }
+    if let Err(err) = release_gslt(pool, server_id).await {
+        log::error!("Releasing GSLT of {} failed: {:#}", server_id, err);
+    }
+}
+
+/// Shows why the server start failed and waits for an admin to retry it, `false` when nobody
+/// did within `START_RETRY_WINDOW`. The failed start is saved so it can still be retried with
+/// `/admin matches start_server` after that or a restart
+async fn await_start_retry(
+    context: &Context<'_>,
+    pool: &PgPool,
+    msg: &mut Message,
+    match_series: i32,
+    err: &Error,
+) -> Result<bool> {
+    msg.edit(context.serenity_context(), |m| {
+        m.content(format!(
+            "⚠️ Starting the server failed: {:#}\nThe veto result has been saved, an admin can retry the server start.",
+            err
+        ))
+        .components(|c| c.add_action_row(create_start_retry_action_row()))
+    })
+    .await?;
+    record_failed_start(pool, match_series, msg).await?;
+    loop {
+        let Some(mci) = msg
+            .await_component_interaction(context.serenity_context())
+            .timeout(START_RETRY_WINDOW)
             .await
+        else {
+            msg.edit(context.serenity_context(), |m| m.components(|c| c))
+                .await?;
+            expire_failed_start(pool, match_series).await?;
+            msg.channel_id
+                .say(context.serenity_context(), retry_command_hint(match_series))
+                .await?;
+            return Ok(false);
+        };
+        if !is_admin(pool, &mci).await? {
+            mci.create_interaction_response(&context.serenity_context(), |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| {
+                        d.ephemeral(true)
+                            .content("Only admins can retry the server start")
+                    })
+            })
+            .await?;
+            continue;
         }
-        Bo3 => {
-            start_series_match(
-                server_id,
-                setup,
-                client,
-                &dathost_config,
-                pool,
-                match_end_webhook_url,
-                round_end_webhook_url,
-                series_end_webhook_url,
-            )
-            .await
+        // whoever takes the failed start retries it, the button or the command
+        if !take_failed_start(pool, match_series).await? {
+            mci.create_interaction_response(&context.serenity_context(), |r| {
+                r.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|d| d.components(|c| c))
+            })
+            .await?;
+            return Ok(false);
         }
-        Bo5 => {
-            start_series_match(
-                server_id,
-                setup,
-                client,
-                &dathost_config,
-                pool,
-                match_end_webhook_url,
-                round_end_webhook_url,
-                series_end_webhook_url,
+        mci.create_interaction_response(&context.serenity_context(), |r| {
+            r.kind(InteractionResponseType::DeferredUpdateMessage)
+        })
+        .await?;
+        mci.channel_id
+            .say(
+                context.serenity_context(),
+                format!("🛡️ <@{}> retried the server start", mci.user.id),
             )
-            .await
-        }
-    };
-    if let Err(err) = start_resp {
-        eprintln!("{:#?}", err);
-        return Err(Error::from(err));
+            .await?;
+        return Ok(true);
     }
-    let start_info = start_resp?.json::<DathostStartResponse>().await?;
-    current_match
-        .update_dathost_match(pool, start_info.id)
-        .await?;
-    Server::add(
-        pool,
-        setup.match_series.unwrap(),
-        &dupl_resp.id,
-        &dupl_resp.ip,
-        dupl_resp.ports.game as i32,
-        dupl_resp.ports.gotv as i32,
+}
+
+fn retry_command_hint(match_series: i32) -> String {
+    format!(
+        "An admin can retry the server start with `/admin matches start_server match_id:{}`",
+        match_series
     )
+}
+
+async fn record_failed_start(pool: &PgPool, match_series: i32, msg: &Message) -> Result<()> {
+    sqlx::query!(
+        "insert into failed_starts (match_series, channel, message) values ($1, $2, $3)
+             on conflict (match_series) do update
+             set channel = excluded.channel, message = excluded.message, failed_at = now(), expired = false",
+        match_series,
+        msg.channel_id.0 as i64,
+        msg.id.0 as i64,
+    )
+    .execute(pool)
     .await?;
+    Ok(())
+}
 
-    msg.edit(&context.serenity_context(), |m| {
-        m.content("Match setup completed, server started\n[🌕🌕🌕🌕]")
-    })
+async fn expire_failed_start(pool: &PgPool, match_series: i32) -> Result<()> {
+    sqlx::query!(
+        "update failed_starts set expired = true where match_series = $1",
+        match_series
+    )
+    .execute(pool)
     .await?;
-    Ok(dupl_resp)
+    Ok(())
 }
 
-pub fn get_dathost_config() -> DathostConfig {
-    DathostConfig {
-        user: env::var("DATHOST_USER").unwrap(),
-        password: env::var("DATHOST_PASSWORD").unwrap(),
-    }
+/// Removes the failed start of the series, `false` when it was already retried
+pub(crate) async fn take_failed_start(
+    executor: impl PgExecutor<'_>,
+    match_series: i32,
+) -> Result<bool> {
+    let result = sqlx::query!(
+        "delete from failed_starts where match_series = $1",
+        match_series
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() == 1)
 }
 
-pub async fn create_gslt(server_id: &String, match_id: i32) -> Result<String> {
-    let client = Client::new();
-    let api_key = env::var("STEAM_API_KEY")?;
-    let json = serde_json::to_string(&CreateGsltRequest {
-        appid: 730,
-        key: String::from(server_id),
-        memo: match_id.to_string(),
-    })?;
-    let resp = client
-        .post("https://api.steampowered.com/IGameServersService/CreateAccount/v1/")
-        .query(&[("key", &&api_key), ("input_json", &&json)])
-        .header("Content-Length", 0)
-        .send()
-        .await?
-        .json::<SteamApiRootResponse>()
-        .await?;
-    Ok(resp.response.login_token)
+/// Retry buttons of a previous run no longer work, points admins to the command instead
+pub async fn expire_retry_buttons(http: &Http, pool: &PgPool) -> Result<()> {
+    let failed =
+        sqlx::query!("select match_series, channel, message from failed_starts where not expired")
+            .fetch_all(pool)
+            .await?;
+    for start in failed {
+        let channel = ChannelId(start.channel as u64);
+        let edited = channel
+            .edit_message(http, start.message as u64, |m| m.components(|c| c))
+            .await;
+        if let Err(err) = edited {
+            log::warn!(
+                "Removing the retry button of {} failed: {:#}",
+                start.match_series,
+                err
+            );
+        }
+        expire_failed_start(pool, start.match_series).await?;
+        channel
+            .say(http, retry_command_hint(start.match_series))
+            .await?;
+    }
+    Ok(())
 }
 
 pub async fn start_match(
@@ -1156,7 +2476,7 @@ pub async fn start_match(
     pool: &PgPool,
     match_end_webhook_url: String,
     round_end_webhook_url: String,
-) -> std::result::Result<Response, reqwest::Error> {
+) -> Result<Response> {
     let start_match_url = String::from("https://dathost.net/api/0.1/matches");
     let team_ct: String;
     let team_t: String;
@@ -1174,126 +2494,138 @@ pub async fn start_match(
         team_t = setup.team_one_conn_str.clone().unwrap();
         team_t_name = setup.team_one.name.clone();
     }
-    let map = Map::get(pool, new_match.map_id).await.unwrap();
-
-    println!("starting match request...");
-    client
-        .post(&start_match_url)
-        .form(&[
-            ("game_server_id", &&server_id),
-            ("map", &&map.name),
-            ("team1_name", &&team_t_name),
-            ("team2_name", &&team_ct_name),
-            ("team1_steam_ids", &&team_t),
-            ("team2_steam_ids", &&team_ct),
-            ("enable_pause", &&String::from("true")),
-            ("enable_tech_pause", &&String::from("true")),
-            ("match_end_webhook_url", &&match_end_webhook_url),
-            ("round_end_webhook_url", &&round_end_webhook_url),
-        ])
-        .basic_auth(&dathost_config.user, Some(&dathost_config.password))
-        .send()
-        .await
+    let map = Map::get(pool, new_match.map_id).await?;
+
+    let mut params = vec![
+        ("game_server_id", server_id),
+        ("map", map.server_map()),
+        ("team1_name", team_t_name),
+        ("team2_name", team_ct_name),
+        ("team1_steam_ids", team_t),
+        ("team2_steam_ids", team_ct),
+        ("enable_pause", String::from("true")),
+        ("enable_tech_pause", String::from("true")),
+        ("match_end_webhook_url", match_end_webhook_url),
+        ("round_end_webhook_url", round_end_webhook_url),
+    ];
+    if setup.is_knife(0) {
+        params.push(("enable_knife_round", String::from("true")));
+    }
+
+    log::debug!("starting match request...");
+    dathost_request(
+        client
+            .post(&start_match_url)
+            .form(&params)
+            .basic_auth(&dathost_config.user, Some(&dathost_config.password)),
+    )
+    .await
+}
+
+/// Starts a single map through Dathost's CS2 match API, which takes JSON instead of a form
+pub async fn start_cs2_match(
+    server_id: String,
+    setup: &Setup,
+    client: Client,
+    dathost_config: &DathostConfig,
+    pool: &PgPool,
+    match_end_webhook_url: String,
+    round_end_webhook_url: String,
+) -> Result<Response> {
+    let new_match = setup.maps_sel[0].clone();
+    let map = Map::get(pool, new_match.map_id).await?;
+    let mut players = Vec::new();
+    for (team, conn_str) in [
+        ("team1", setup.team_one_conn_str.as_ref().unwrap()),
+        ("team2", setup.team_two_conn_str.as_ref().unwrap()),
+    ] {
+        for steam_id in config_players(conn_str).into_keys() {
+            players.push(json!({ "steam_id_64": steam_id, "team": team }));
+        }
+    }
+    let body = json!({
+        "game_server_id": server_id,
+        "team1": { "name": setup.team_one.name },
+        "team2": { "name": setup.team_two.name },
+        "players": players,
+        "settings": {
+            "map": map.server_map(),
+            "team1_start_ct": new_match.start_ct_team_role == Some(setup.team_one.role),
+            "enable_knife_round": setup.is_knife(0),
+            "enable_pause": true,
+            "enable_tech_pause": true,
+        },
+        "webhooks": {
+            "match_end_url": match_end_webhook_url,
+            "round_end_url": round_end_webhook_url,
+        },
+    });
+    log::debug!("starting cs2 match request...");
+    dathost_request(
+        client
+            .post("https://dathost.net/api/0.1/cs2-matches")
+            .json(&body)
+            .basic_auth(&dathost_config.user, Some(&dathost_config.password)),
+    )
+    .await
 }
 
 pub async fn start_series_match(
     server_id: String,
-    setup: &mut Setup,
+    setup: &Setup,
     client: Client,
     dathost_config: &DathostConfig,
     pool: &PgPool,
     match_end_webhook_url: String,
     round_end_webhook_url: String,
     series_end_webhook_url: String,
-) -> std::result::Result<Response, reqwest::Error> {
+) -> Result<Response> {
     let start_match_url = String::from("https://dathost.net/api/0.1/match-series");
     let team_one = setup.team_one_conn_str.clone().unwrap();
     let team_one_name = setup.team_one.name.clone();
     let team_two = setup.team_two_conn_str.clone().unwrap();
     let team_two_name = setup.team_two.name.clone();
-    let mut params: HashMap<&str, &str> = HashMap::new();
+    let mut params: HashMap<String, String> = HashMap::new();
     let team_map = HashMap::from([
         (setup.team_one.role, "team1"),
         (setup.team_two.role, "team2"),
     ]);
-    let maps = Map::get_all(pool, true).await.unwrap();
-    let mut num_maps = "3";
-    params.insert("game_server_id", server_id.as_str());
-    params.insert("enable_pause", "true");
-    params.insert("enable_tech_pause", "true");
-    params.insert("match_end_webhook_url", &&match_end_webhook_url);
-    params.insert("round_end_webhook_url", &&round_end_webhook_url);
-    params.insert("match_series_end_webhook_url", &&series_end_webhook_url);
-    params.insert("team1_name", team_one_name.as_str());
-    params.insert("team2_name", team_two_name.as_str());
-    params.insert("team1_steam_ids", team_one.as_str());
-    params.insert("team2_steam_ids", team_two.as_str());
-    let map1 = maps
-        .iter()
-        .find(|m| m.id == setup.maps_sel[0].map_id)
-        .unwrap();
-    params.insert("map1", &map1.name);
-    params.insert(
-        "map1_start_ct",
-        team_map
-            .get(&setup.maps_sel[0].start_ct_team_role.unwrap())
-            .unwrap(),
-    );
-    let map2 = maps
-        .iter()
-        .find(|m| m.id == setup.maps_sel[1].map_id)
-        .unwrap();
-    params.insert("map2", &map2.name);
+    let maps = Map::get_all(pool, setup.guild, true).await?;
+    let num_maps = if setup.series_type == Bo5 { 5 } else { 3 };
+    params.insert("game_server_id".into(), server_id);
+    params.insert("enable_pause".into(), "true".into());
+    params.insert("enable_tech_pause".into(), "true".into());
+    params.insert("match_end_webhook_url".into(), match_end_webhook_url);
+    params.insert("round_end_webhook_url".into(), round_end_webhook_url);
     params.insert(
-        "map2_start_ct",
-        team_map
-            .get(&setup.maps_sel[1].start_ct_team_role.unwrap())
-            .unwrap(),
+        "match_series_end_webhook_url".into(),
+        series_end_webhook_url,
     );
-    let map3 = maps
-        .iter()
-        .find(|m| m.id == setup.maps_sel[2].map_id)
-        .unwrap();
-    params.insert("map3", &map3.name);
-    params.insert(
-        "map3_start_ct",
-        team_map
-            .get(&setup.maps_sel[2].start_ct_team_role.unwrap())
-            .unwrap(),
-    );
-    if setup.series_type == Bo5 {
-        num_maps = "5";
-        let map4 = maps
-            .iter()
-            .find(|m| m.id == setup.maps_sel[3].map_id)
-            .unwrap();
-        params.insert("map4", &map4.name);
-        params.insert(
-            "map4_start_ct",
-            team_map
-                .get(&setup.maps_sel[3].start_ct_team_role.unwrap())
-                .unwrap(),
-        );
-        let map5 = maps
-            .iter()
-            .find(|m| m.id == setup.maps_sel[4].map_id)
-            .unwrap();
-        params.insert("map5", &map5.name);
-        params.insert(
-            "map5_start_ct",
-            team_map
-                .get(&setup.maps_sel[4].start_ct_team_role.unwrap())
-                .unwrap(),
-        );
+    params.insert("team1_name".into(), team_one_name);
+    params.insert("team2_name".into(), team_two_name);
+    params.insert("team1_steam_ids".into(), team_one);
+    params.insert("team2_steam_ids".into(), team_two);
+    for (i, new_match) in setup.maps_sel.iter().take(num_maps).enumerate() {
+        let map = maps.iter().find(|m| m.id == new_match.map_id).unwrap();
+        params.insert(format!("map{}", i + 1), map.server_map());
+        let start_ct = if setup.is_knife(i) {
+            "knife"
+        } else {
+            *team_map
+                .get(&new_match.start_ct_team_role.unwrap())
+                .unwrap()
+        };
+        params.insert(format!("map{}_start_ct", i + 1), start_ct.to_string());
     }
-    params.insert("number_of_maps", num_maps);
+    params.insert("number_of_maps".into(), num_maps.to_string());
     println!("{:#?}", params);
-    client
-        .post(&start_match_url)
-        .form(&params)
-        .basic_auth(&dathost_config.user, Some(&dathost_config.password))
-        .send()
-        .await
+    dathost_request(
+        client
+            .post(&start_match_url)
+            .form(&params)
+            .basic_auth(&dathost_config.user, Some(&dathost_config.password)),
+    )
+    .await
 }
 
 pub async fn team_conn_str(team: i64, pool: &PgPool) -> Result<String> {
@@ -1310,3 +2642,30 @@ pub async fn team_conn_str(team: i64, pool: &PgPool) -> Result<String> {
     str.remove(str.len() - 1);
     Ok(str)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connect::tests::FakeLinks;
+
+    #[tokio::test]
+    async fn connect_links_come_from_the_links() {
+        let links = FakeLinks::default();
+        let link = connect_link(&links, 7, ConnectTarget::Gotv, "1.2.3.4:27020").await;
+        assert_eq!(link.as_deref(), Some("https://fake.link/1.2.3.4:27020"));
+        assert_eq!(
+            *links.calls.lock().unwrap(),
+            vec![(7, ConnectTarget::Gotv, String::from("1.2.3.4:27020"))]
+        );
+    }
+
+    #[tokio::test]
+    async fn failing_links_fall_back_to_the_address() {
+        let links = FakeLinks {
+            fail: true,
+            ..FakeLinks::default()
+        };
+        let link = connect_link(&links, 7, ConnectTarget::Game, "1.2.3.4:27015").await;
+        assert_eq!(link, None);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::commands::config::guild_id;
+use crate::commands::pagination::paginate;
+use crate::commands::team::get_guild_team;
+use crate::commands::tournament::{active_tournaments, get_active, get_active_by_name};
+use crate::Context;
+use anyhow::Result;
+use matchbot_core::team::Team;
+use poise::command;
+use serenity::model::id::RoleId;
+use sqlx::{FromRow, PgExecutor};
+
+/// Maps per table of the output, a table has to stay within one page
+const TABLE_MAPS: usize = 15;
+
+/// Stats of a map, maps with the same name are counted apart
+#[derive(Debug, FromRow)]
+pub struct MapVoteStats {
+    pub map: String,
+    pub picks: i64,
+    pub bans: i64,
+    pub first_bans: i64,
+}
+
+#[derive(Debug, FromRow)]
+pub struct PickWinRate {
+    pub own_pick: bool,
+    pub played: i64,
+    pub won: i64,
+}
+
+impl MapVoteStats {
+    pub async fn get_by_team(
+        executor: impl PgExecutor<'_>,
+        team: i32,
+    ) -> Result<Vec<MapVoteStats>> {
+        Ok(sqlx::query_as!(
+            MapVoteStats,
+            r#"select coalesce(m.display_name, m.name) as "map!",
+                   count(*) filter (where vi.type = 'pick') as "picks!",
+                   count(*) filter (where vi.type = 'veto') as "bans!",
+                   count(*) filter (where vi.type = 'veto' and vi.id = (select min(f.id)
+                                                                      from vote_info f
+                                                                      where f.match_series = vi.match_series
+                                                                        and f.team = vi.team
+                                                                        and f.type = 'veto')) as "first_bans!"
+               from vote_info vi
+                  join maps m on m.id = vi.map
+               where vi.team = $1
+               group by m.id
+               order by 1, m.id"#,
+            team
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+    pub async fn get_by_tournament(
+        executor: impl PgExecutor<'_>,
+        tournament: i32,
+    ) -> Result<Vec<MapVoteStats>> {
+        Ok(sqlx::query_as!(
+            MapVoteStats,
+            r#"select coalesce(m.display_name, m.name) as "map!",
+                   count(*) filter (where vi.type = 'pick') as "picks!",
+                   count(*) filter (where vi.type = 'veto') as "bans!",
+                   count(*) filter (where vi.type = 'veto' and vi.id = (select min(f.id)
+                                                                      from vote_info f
+                                                                      where f.match_series = vi.match_series
+                                                                        and f.type = 'veto')) as "first_bans!"
+               from vote_info vi
+                  join maps m on m.id = vi.map
+                  join match_series ms on ms.id = vi.match_series
+               where ms.tournament = $1
+               group by m.id
+               order by 1, m.id"#,
+            tournament
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+}
+
+impl PickWinRate {
+    pub async fn get_by_team(executor: impl PgExecutor<'_>, team: i32) -> Result<Vec<PickWinRate>> {
+        Ok(sqlx::query_as!(
+            PickWinRate,
+            r#"select mt.picked_by = $1 as "own_pick!",
+                   count(*) as "played!",
+                   count(*) filter (where (ms.team_one = $1 and sc.team_one_score > sc.team_two_score)
+                                       or (ms.team_two = $1 and sc.team_two_score > sc.team_one_score)) as "won!"
+               from match mt
+                  join match_series ms on ms.id = mt.match_series
+                  join match_scores sc on sc.match_id = mt.id
+               where mt.completed_at is not null
+                 and (ms.team_one = $1 or ms.team_two = $1)
+               group by 1"#,
+            team
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+}
+
+#[command(slash_command, guild_only, subcommands("vetoes"))]
+pub(crate) async fn stats(_context: Context<'_>) -> Result<()> {
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized(
+        "en-US",
+        "Show map pick & ban stats for a team and the active tournaments"
+    )
+)]
+pub(crate) async fn vetoes(
+    context: Context<'_>,
+    #[description = "Team role"] team: Option<RoleId>,
+    #[description = "Tournament, defaults to all active tournaments"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let guild = guild_id(&context)?;
+    let team = match team {
+        Some(role) => match Team::get_by_role(pool, role.0 as i64).await? {
+            Some(team) => Some(team),
+            None => {
+                context
+                    .say(format!("Role <@&{role}> is not associated with a team!"))
+                    .await?;
+                return Ok(());
+            }
+        },
+        None => get_guild_team(pool, guild, context.author().id.0 as i64).await?,
+    };
+    // sections & tables are separate items, so pages never split a table
+    let mut items = Vec::new();
+    if let Some(team) = team {
+        let vote_stats = MapVoteStats::get_by_team(pool, team.id).await?;
+        let mut s = format!("**{}** veto stats\n", team.name);
+        if vote_stats.is_empty() {
+            s.push_str("No vetoes recorded yet.\n");
+        } else {
+            s.push_str(&top_map_line("First ban", &vote_stats, |v| v.first_bans));
+            s.push_str(&top_map_line("Most picked", &vote_stats, |v| v.picks));
+            s.push_str(&top_map_line("Most banned", &vote_stats, |v| v.bans));
+        }
+        let win_rates = PickWinRate::get_by_team(pool, team.id).await?;
+        s.push_str(&win_rate_line("Win rate on own picks", &win_rates, true));
+        s.push_str(&win_rate_line(
+            "Win rate on opponent picks",
+            &win_rates,
+            false,
+        ));
+        items.push(s);
+        items.extend(vote_tables(&vote_stats));
+    }
+    let tournaments = match tournament {
+        Some(name) => match get_active_by_name(pool, guild, &name).await? {
+            Some(tournament) => vec![tournament],
+            None => {
+                context
+                    .say(format!(
+                        "Could not find an active tournament named `{}`",
+                        name
+                    ))
+                    .await?;
+                return Ok(());
+            }
+        },
+        None => get_active(pool, guild).await?,
+    };
+    if tournaments.is_empty() {
+        items.push(String::from("There is no active tournament."));
+    }
+    for tournament in tournaments {
+        let vote_stats = MapVoteStats::get_by_tournament(pool, tournament.id).await?;
+        let mut s = format!("**{}** pick/ban heatmap", tournament.name);
+        if vote_stats.is_empty() {
+            s.push_str("\nNo vetoes recorded yet.");
+        }
+        items.push(s);
+        items.extend(vote_tables(&vote_stats));
+    }
+    paginate(&context, "Veto stats", &items).await?;
+    Ok(())
+}
+
+fn top_map_line(label: &str, stats: &[MapVoteStats], count: fn(&MapVoteStats) -> i64) -> String {
+    let max = stats.iter().map(count).max().unwrap_or(0);
+    if max == 0 {
+        return format!("{}: -\n", label);
+    }
+    let maps: Vec<&str> = stats
+        .iter()
+        .filter(|v| count(v) == max)
+        .map(|v| v.map.as_str())
+        .collect();
+    format!("{}: `{}` ({}x)\n", label, maps.join("`, `"), max)
+}
+
+fn win_rate_line(label: &str, win_rates: &[PickWinRate], own_pick: bool) -> String {
+    match win_rates.iter().find(|w| w.own_pick == own_pick) {
+        Some(w) if w.played > 0 => format!(
+            "{}: **{}%** ({}/{})\n",
+            label,
+            w.won * 100 / w.played,
+            w.won,
+            w.played
+        ),
+        _ => format!("{}: -\n", label),
+    }
+}
+
+fn heat(count: i64, max: i64) -> &'static str {
+    if count == 0 || max == 0 {
+        return "     ";
+    }
+    match count * 4 / max {
+        0 => "░    ",
+        1 => "▒▒   ",
+        2 => "▓▓▓  ",
+        3 => "████ ",
+        _ => "█████",
+    }
+}
+
+/// Tables of at most `TABLE_MAPS` maps, scaled to the maximum of all of them
+fn vote_tables(stats: &[MapVoteStats]) -> Vec<String> {
+    let width = stats
+        .iter()
+        .map(|v| v.map.chars().count())
+        .max()
+        .unwrap_or(3)
+        .max(3);
+    let max_picks = stats.iter().map(|v| v.picks).max().unwrap_or(0);
+    let max_bans = stats.iter().map(|v| v.bans).max().unwrap_or(0);
+    let mut tables = Vec::new();
+    for chunk in stats.chunks(TABLE_MAPS) {
+        let mut s = format!(
+            "```\n{:<width$} {:>5} {:<5} {:>5} {:<5} {:>6}\n",
+            "Map",
+            "Picks",
+            "",
+            "Bans",
+            "",
+            "First",
+            width = width
+        );
+        for v in chunk {
+            s.push_str(
+                format!(
+                    "{:<width$} {:>5} {} {:>5} {} {:>6}\n",
+                    v.map,
+                    v.picks,
+                    heat(v.picks, max_picks),
+                    v.bans,
+                    heat(v.bans, max_bans),
+                    v.first_bans,
+                    width = width
+                )
+                .as_str(),
+            );
+        }
+        s.push_str("```");
+        tables.push(s);
+    }
+    tables
+}
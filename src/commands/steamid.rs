@@ -6,6 +6,7 @@ use regex::Regex;
 use sqlx::{FromRow, PgExecutor};
 use steamid::{AccountType, Instance, SteamId, Universe};
 
+use crate::commands::audit::{audit_log, AuditAction};
 use crate::Context;
 
 trait ParseWithDefaults: Sized {
@@ -97,16 +98,32 @@ pub(crate) async fn steamid(
     let steamid64 = SteamId::parse(&steamid);
     let Ok(steamid64) = steamid64 else {
         error!("Error parsing '{}'", &steamid);
-        context.say(format!("Error parsing steamid '{}', contact an admin", steamid)).await?;
+        context
+            .say(format!(
+                "Error parsing steamid '{}', contact an admin",
+                steamid
+            ))
+            .await?;
         return Ok(());
     };
     let pool = &context.data().pool;
+    let previous = SteamUser::get_by_discord_id(pool, context.author().id.0 as i64).await?;
+    let mut transaction = pool.begin().await?;
     SteamUser::add(
-        pool,
+        &mut transaction,
         context.author().id.0 as i64,
         u64::from(steamid64) as i64,
     )
     .await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::SteamIdChanged,
+        format!("<@{}>", context.author().id),
+        previous.map(|p| p.steam.to_string()),
+        Some(u64::from(steamid64).to_string()),
+    )
+    .await?;
     context.say(format!("Your SteamID has been set to the following Steam account: {} \
                                 \nPlease verify this is the account you will be playing on, otherwise you will not be able to join a match server!",
                                      steamid64.community_link()))
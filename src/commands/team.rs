@@ -1,14 +1,59 @@
 use super::super::Context;
+use crate::commands::audit::{audit_log, AuditAction};
+use crate::commands::config::guild_id;
+use crate::commands::locations::{get_team_region, regions, set_team_region};
+use crate::commands::pagination::paginate;
 use crate::commands::steamid::SteamUser;
+use crate::commands::tournament::{active_tournaments, pick_tournament, TeamArchive};
 use anyhow::{Error, Result};
 use matchbot_core::team::*;
 use poise::command;
 use serenity::model::{application::component::ButtonStyle, id::RoleId, user::User};
+use sqlx::{PgExecutor, PgPool};
+
+/// Active team of `member` in `guild`, a user can be on one team per guild
+pub(crate) async fn get_guild_team(pool: &PgPool, guild: i64, member: i64) -> Result<Option<Team>> {
+    let team = sqlx::query_scalar!(
+        "select t.id
+             from teams t
+                join team_members tm on tm.team = t.id
+             where tm.member = $1 and t.guild = $2 and t.is_active is true
+             limit 1",
+        member,
+        guild
+    )
+    .fetch_optional(pool)
+    .await?;
+    match team {
+        Some(team) => Ok(Some(Team::get(pool, team).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Ids of the active teams of `guild`
+pub(crate) async fn get_guild_team_ids(
+    executor: impl PgExecutor<'_>,
+    guild: i64,
+) -> Result<Vec<i32>> {
+    Ok(sqlx::query_scalar!(
+        "select id from teams where guild = $1 and is_active is true",
+        guild
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+async fn set_team_guild(executor: impl PgExecutor<'_>, role: i64, guild: i64) -> Result<()> {
+    sqlx::query!("update teams set guild = $2 where role = $1", role, guild)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
 
 #[command(
     slash_command,
     guild_only,
-    subcommands("create", "show", "leave", "invite", "kick")
+    subcommands("create", "show", "leave", "invite", "kick", "carryover", "region")
 )]
 pub(crate) async fn team(_context: Context<'_>) -> Result<()> {
     Ok(())
@@ -27,19 +72,23 @@ pub(crate) async fn teams(_context: Context<'_>) -> Result<()> {
 )]
 pub(crate) async fn all(context: Context<'_>) -> Result<()> {
     let pool = &context.data().pool;
-    let teams = Team::get_all(pool).await?;
+    let team_ids = get_guild_team_ids(pool, guild_id(&context)?).await?;
+    let mut teams = Team::get_all(pool).await?;
+    teams.retain(|t| team_ids.contains(&t.id));
     if teams.is_empty() {
         context.say("No teams found.").await?;
         return Ok(());
     }
-    let mut all_teams = String::new();
+    let mut all_teams = Vec::new();
     for (i, team) in teams.iter().enumerate() {
         let members = team.members(pool).await?;
-        all_teams.push_str(format!("{}. ", i + 1).as_str());
-        all_teams.push_str(team.format_team_str(members).await.as_str());
-        all_teams.push_str("\n");
+        all_teams.push(format!(
+            "{}. {}",
+            i + 1,
+            team.format_team_str(members).await.trim_end()
+        ));
     }
-    context.say(all_teams).await?;
+    paginate(&context, "Teams", &all_teams).await?;
     Ok(())
 }
 
@@ -73,7 +122,7 @@ pub(crate) async fn create(
     }
 
     // User does not have a team
-    if let Some(team) = Team::get_by_member(pool, author.0 as i64).await? {
+    if let Some(team) = get_guild_team(pool, guild_id(&context)?, author.0 as i64).await? {
         if team.captain == author.0 as i64 {
             context
                 .say(format!(
@@ -99,10 +148,21 @@ pub(crate) async fn create(
         .await?
         .id;
 
-    if let Err(err) = create_team(pool, role.0, &name, author.0).await {
+    let mut transaction = pool.begin().await?;
+    if let Err(err) = create_team(&mut transaction, role.0, &name, author.0).await {
         guild.delete_role(context.serenity_context(), role).await?;
         return Err(err);
     }
+    set_team_guild(&mut transaction, role.0 as i64, guild.0 as i64).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::TeamCreated,
+        &name,
+        None,
+        None,
+    )
+    .await?;
 
     let mut member = guild.member(context.serenity_context(), author).await?;
     member.add_role(context.serenity_context(), role).await?;
@@ -133,13 +193,15 @@ pub(crate) async fn show(
                 return Ok(());
             }
         },
-        None => match Team::get_by_member(pool, context.author().id.0 as i64).await? {
-            Some(team) => team,
-            None => {
-                context.say("You are not on a team!").await?;
-                return Ok(());
+        None => {
+            match get_guild_team(pool, guild_id(&context)?, context.author().id.0 as i64).await? {
+                Some(team) => team,
+                None => {
+                    context.say("You are not on a team!").await?;
+                    return Ok(());
+                }
             }
-        },
+        }
     };
 
     let members = team.members(pool).await?;
@@ -164,7 +226,7 @@ pub(crate) async fn leave(context: Context<'_>) -> Result<()> {
         .ok_or_else::<Error, _>(|| unreachable!())?;
 
     // User has team
-    let team = match Team::get_by_member(pool, author.0 as i64).await? {
+    let team = match get_guild_team(pool, guild_id(&context)?, author.0 as i64).await? {
         None => {
             context.say("You are not on a team!").await?;
             return Ok(());
@@ -182,14 +244,24 @@ pub(crate) async fn leave(context: Context<'_>) -> Result<()> {
         return Ok(());
     }
 
-    Team::remove_member(pool, team.id, author.0 as i64).await?;
+    let mut transaction = pool.begin().await?;
+    Team::remove_member(&mut transaction, team.id, author.0 as i64).await?;
+    let member_vec: Vec<u64> = members.into_iter().map(|n| n as u64).collect();
+    let disbanded = [author.0] == member_vec.as_slice();
+    if disbanded {
+        Team::delete(&mut transaction, team.id).await?;
+    }
+    let action = if disbanded {
+        AuditAction::TeamDisbanded
+    } else {
+        AuditAction::TeamLeft
+    };
+    audit_log(&context, transaction, action, &team.name, None, None).await?;
     let mut member = guild.member(context.serenity_context(), author.0).await?;
     member
         .remove_role(context.serenity_context(), team.role as u64)
         .await?;
-    let member_vec: Vec<u64> = members.into_iter().map(|n| n as u64).collect();
-    if [author.0] == member_vec.as_slice() {
-        Team::delete(pool, team.id).await?;
+    if disbanded {
         guild
             .delete_role(context.serenity_context(), team.role as u64)
             .await?;
@@ -224,7 +296,7 @@ pub(crate) async fn invite(context: Context<'_>, user: User) -> Result<()> {
     let author = context.author();
 
     // Author has team
-    let team = match Team::get_by_member(pool, author.id.0 as i64).await? {
+    let team = match get_guild_team(pool, guild_id(&context)?, author.id.0 as i64).await? {
         None => {
             context.say("You are not on a team!").await?;
             return Ok(());
@@ -239,7 +311,7 @@ pub(crate) async fn invite(context: Context<'_>, user: User) -> Result<()> {
     }
 
     // User does not have team
-    if let Some(user_team) = Team::get_by_member(pool, user.id.0 as i64).await? {
+    if let Some(user_team) = get_guild_team(pool, guild_id(&context)?, user.id.0 as i64).await? {
         if team.id == user_team.id {
             context.say("This user is already on your team!").await?;
         } else {
@@ -307,7 +379,17 @@ pub(crate) async fn invite(context: Context<'_>, user: User) -> Result<()> {
 
     match response {
         "accepted" => {
-            Team::add_member(pool, team.id, user.id.0 as i64).await?;
+            let mut transaction = pool.begin().await?;
+            Team::add_member(&mut transaction, team.id, user.id.0 as i64).await?;
+            audit_log(
+                &context,
+                transaction,
+                AuditAction::TeamJoined,
+                &team.name,
+                None,
+                Some(format!("<@{}>", user.id)),
+            )
+            .await?;
             let mut member = guild.member(context.serenity_context(), user.id).await?;
             member
                 .add_role(context.serenity_context(), team.role as u64)
@@ -343,7 +425,7 @@ pub(crate) async fn kick(context: Context<'_>, user: User) -> Result<()> {
     }
 
     // Author has team
-    let team = match Team::get_by_member(pool, author.id.0 as i64).await? {
+    let team = match get_guild_team(pool, guild_id(&context)?, author.id.0 as i64).await? {
         None => {
             context.say("You are not on a team!").await?;
             return Ok(());
@@ -358,7 +440,7 @@ pub(crate) async fn kick(context: Context<'_>, user: User) -> Result<()> {
     }
 
     // User is on team, and it is author's team
-    if let Some(user_team) = Team::get_by_member(pool, user.id.0 as i64).await? {
+    if let Some(user_team) = get_guild_team(pool, guild_id(&context)?, user.id.0 as i64).await? {
         if user_team.id != team.id {
             context
                 .say(format!("<@{}> is not on your team!", user.id))
@@ -372,7 +454,17 @@ pub(crate) async fn kick(context: Context<'_>, user: User) -> Result<()> {
         return Ok(());
     }
 
-    Team::remove_member(pool, team.id, user.id.0 as i64).await?;
+    let mut transaction = pool.begin().await?;
+    Team::remove_member(&mut transaction, team.id, user.id.0 as i64).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::TeamKicked,
+        &team.name,
+        Some(format!("<@{}>", user.id)),
+        None,
+    )
+    .await?;
 
     let mut member = guild.member(context.serenity_context(), user.id).await?;
     member
@@ -411,7 +503,7 @@ pub(crate) async fn transfer(
     }
 
     // Author has team
-    let team = match Team::get_by_member(pool, author.id.0 as i64).await? {
+    let team = match get_guild_team(pool, guild_id(&context)?, author.id.0 as i64).await? {
         None => {
             context.say("You are not on a team!").await?;
             return Ok(());
@@ -426,7 +518,7 @@ pub(crate) async fn transfer(
     }
 
     // User is on team, and it is author's team
-    if let Some(user_team) = Team::get_by_member(pool, user.id.0 as i64).await? {
+    if let Some(user_team) = get_guild_team(pool, guild_id(&context)?, user.id.0 as i64).await? {
         if user_team.id != team.id {
             context
                 .say(format!("<@{}> is not on your team!", user.id))
@@ -440,7 +532,17 @@ pub(crate) async fn transfer(
         return Ok(());
     }
 
-    Team::update_captain(pool, team.id, user.id.0 as i64).await?;
+    let mut transaction = pool.begin().await?;
+    Team::update_captain(&mut transaction, team.id, user.id.0 as i64).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::TeamCaptainTransferred,
+        &team.name,
+        Some(format!("<@{}>", team.captain)),
+        Some(format!("<@{}>", user.id)),
+    )
+    .await?;
 
     let mut member = guild.member(context.serenity_context(), user.id).await?;
     member
@@ -455,3 +557,174 @@ pub(crate) async fn transfer(
         .await?;
     Ok(())
 }
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized(
+        "en-US",
+        "Bring your team and roster from the last tournament into a new one"
+    )
+)]
+pub(crate) async fn carryover(
+    context: Context<'_>,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+
+    let author = context.author().id;
+    let guild = context
+        .guild_id()
+        .ok_or_else::<Error, _>(|| unreachable!())?;
+
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    if let Some(team) = get_guild_team(pool, guild_id(&context)?, author.0 as i64).await? {
+        context
+            .say(format!(
+                "You are already a member of the <@&{role}> team!",
+                role = team.role
+            ))
+            .await?;
+        return Ok(());
+    }
+    let Some(archive) =
+        TeamArchive::get_latest_by_captain(pool, guild.0 as i64, author.0 as i64).await?
+    else {
+        context
+            .say("You were not the captain of a team in a previous tournament.")
+            .await?;
+        return Ok(());
+    };
+
+    // Role may have been deleted when the previous tournament ended
+    let roles = guild.roles(context.serenity_context()).await?;
+    let kept = roles.contains_key(&RoleId(archive.role as u64));
+    let role = if kept {
+        RoleId(archive.role as u64)
+    } else {
+        guild
+            .create_role(context.serenity_context(), |role| {
+                role.name(archive.name.clone()).mentionable(true)
+            })
+            .await?
+            .id
+    };
+
+    let carried: Result<Vec<i64>> = async {
+        let mut transaction = pool.begin().await?;
+        let members = archive
+            .carry_over(&mut transaction, current.id, role.0 as i64)
+            .await?;
+        audit_log(
+            &context,
+            transaction,
+            AuditAction::TeamCarriedOver,
+            &archive.name,
+            None,
+            Some(current.name.clone()),
+        )
+        .await?;
+        Ok(members)
+    }
+    .await;
+    let members = match carried {
+        Ok(members) => members,
+        Err(err) => {
+            if !kept {
+                if let Err(err) = guild.delete_role(context.serenity_context(), role).await {
+                    log::error!("Deleting role {} failed: {}", role, err);
+                }
+            }
+            return Err(err);
+        }
+    };
+    // the kept role gets its name back once the team is active again
+    if kept {
+        guild
+            .edit_role(context.serenity_context(), role, |r| {
+                r.name(archive.name.clone())
+            })
+            .await?;
+    }
+    for member in &members {
+        if let Ok(mut member) = guild
+            .member(context.serenity_context(), *member as u64)
+            .await
+        {
+            member.add_role(context.serenity_context(), role).await?;
+        }
+    }
+    let dropped = archive.members.len() - members.len();
+    let mut content = format!(
+        "Team <@&{role}> carried over into {} with {} players.",
+        current.name,
+        members.len()
+    );
+    if dropped > 0 {
+        content.push_str(
+            format!(
+                " {} players joined another team in the meantime and were not carried over.",
+                dropped
+            )
+            .as_str(),
+        );
+    }
+    context.say(content).await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized(
+        "en-US",
+        "Set your team's home region, used to pick fair server locations"
+    )
+)]
+pub(crate) async fn region(
+    context: Context<'_>,
+    #[description = "Region most of your players are in, i.e. eu"]
+    #[autocomplete = "regions"]
+    region: String,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let author = context.author();
+
+    // Author has team
+    let team = match get_guild_team(pool, guild_id(&context)?, author.id.0 as i64).await? {
+        None => {
+            context.say("You are not on a team!").await?;
+            return Ok(());
+        }
+        Some(team) => team,
+    };
+
+    // Author is team captain
+    if author.id.0 != team.captain as u64 {
+        context.say("You are not the captain of this team!").await?;
+        return Ok(());
+    }
+
+    let before = get_team_region(pool, team.id).await?;
+    let mut transaction = pool.begin().await?;
+    set_team_region(&mut transaction, team.id, &region).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::TeamRegionChanged,
+        &team.name,
+        before,
+        Some(region.clone()),
+    )
+    .await?;
+    context
+        .say(format!("Your team's home region is now `{}`.", region))
+        .await?;
+    Ok(())
+}
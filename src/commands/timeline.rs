@@ -0,0 +1,522 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::{FromRow, PgExecutor, PgPool};
+
+/// Sparkline levels of the momentum row, lowest first
+const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// Rounds of an overtime half
+const OVERTIME_HALF_ROUNDS: i32 = 3;
+
+/// Round-end event of a map, the scores are the ones after the round
+#[derive(Debug, FromRow, Clone, PartialEq, Eq)]
+pub struct MatchRound {
+    pub round: i32,
+    /// `ct` or `t`
+    pub winner_side: String,
+    /// i.e. `t_eliminated`, `bomb_defused` or `target_saved`
+    pub reason: String,
+    pub team_one_won: bool,
+    pub team_one_score: i32,
+    pub team_two_score: i32,
+    /// Not known for rounds read from the demo
+    pub ended_at: Option<OffsetDateTime>,
+}
+
+/// Round of a parsed demo, see `demo_rounds`
+#[derive(Debug, FromRow, Clone)]
+pub struct DemoRound {
+    pub round: i32,
+    pub winner_side: String,
+    pub reason: String,
+}
+
+impl MatchRound {
+    pub async fn get_by_match(
+        executor: impl PgExecutor<'_>,
+        match_id: i32,
+    ) -> Result<Vec<MatchRound>> {
+        Ok(sqlx::query_as!(
+            MatchRound,
+            r#"select round, winner_side, reason, team_one_won, team_one_score, team_two_score,
+                      ended_at as "ended_at?"
+                 from match_rounds
+                 where match_id = $1
+                 order by round"#,
+            match_id
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+    /// Rounds of the parsed demo of a map, for maps without recorded round ends like the ones
+    /// played on Dathost, whose round ends go to the `round_end` webhook
+    pub async fn get_from_demo(
+        pool: &PgPool,
+        match_id: i32,
+        half_rounds: i32,
+    ) -> Result<Vec<MatchRound>> {
+        let rounds = sqlx::query_as!(
+            DemoRound,
+            "select round, winner_side, reason
+                 from demo_rounds
+                 where match_id = $1
+                 order by round",
+            match_id
+        )
+        .fetch_all(pool)
+        .await?;
+        if rounds.is_empty() {
+            return Ok(vec![]);
+        }
+        let map = sqlx::query!(
+            r#"select m.start_ct_team = ms.team_one as "team_one_starts_ct!",
+                      sc.team_one_score as "team_one_score?", sc.team_two_score as "team_two_score?"
+                 from match m
+                    join match_series ms on ms.id = m.match_series
+                    left join match_scores sc on sc.match_id = m.id
+                 where m.id = $1
+                 limit 1"#,
+            match_id
+        )
+        .fetch_one(pool)
+        .await?;
+        let score = map.team_one_score.zip(map.team_two_score);
+        Ok(rounds_from_demo(
+            &rounds,
+            map.team_one_starts_ct,
+            score,
+            half_rounds,
+        ))
+    }
+    /// Whether any map of the series has recorded rounds or a parsed demo
+    pub async fn exists_for_series(
+        executor: impl PgExecutor<'_>,
+        match_series: i32,
+    ) -> Result<bool> {
+        Ok(sqlx::query_scalar!(
+            r#"select exists(select 1
+                               from match m
+                               where m.match_series = $1
+                                 and (exists(select 1 from match_rounds r where r.match_id = m.id)
+                                      or exists(select 1 from demo_rounds r where r.match_id = m.id))) as "exists!""#,
+            match_series
+        )
+        .fetch_one(executor)
+        .await?)
+    }
+    /// Records the end of a round. Later rounds are dropped, they were replayed after the
+    /// server restored a backup
+    pub async fn add(pool: &PgPool, match_id: i32, round: &MatchRound) -> Result<()> {
+        let mut transaction = pool.begin().await?;
+        sqlx::query!(
+            "delete from match_rounds where match_id = $1 and round > $2",
+            match_id,
+            round.round
+        )
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query!(
+            "insert into match_rounds (match_id, round, winner_side, reason, team_one_won, team_one_score, team_two_score, ended_at)
+                 values ($1, $2, $3, $4, $5, $6, $7, coalesce($8, now()))
+                 on conflict (match_id, round) do update set winner_side = excluded.winner_side,
+                                                             reason = excluded.reason,
+                                                             team_one_won = excluded.team_one_won,
+                                                             team_one_score = excluded.team_one_score,
+                                                             team_two_score = excluded.team_two_score,
+                                                             ended_at = excluded.ended_at",
+            match_id,
+            round.round,
+            round.winner_side,
+            round.reason,
+            round.team_one_won,
+            round.team_one_score,
+            round.team_two_score,
+            round.ended_at,
+        )
+        .execute(&mut transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+}
+
+/// Round of a map timeline, also the format of the JSON attachment
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineRound {
+    pub round: i32,
+    /// Halves count on through overtime, the 3rd half is the 1st one of overtime
+    pub half: i32,
+    /// `team_one` or `team_two`
+    pub winner: &'static str,
+    pub winner_side: String,
+    pub reason: String,
+    pub team_one_score: i32,
+    pub team_two_score: i32,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub ended_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MapTimeline {
+    pub match_id: i32,
+    pub map: String,
+    pub team_one: String,
+    pub team_two: String,
+    pub rounds: Vec<TimelineRound>,
+}
+
+/// Half `round` is played in, regulation halves have `half_rounds` rounds
+fn half_of(round: i32, half_rounds: i32) -> i32 {
+    if round <= 2 * half_rounds {
+        (round - 1) / half_rounds + 1
+    } else {
+        3 + (round - 2 * half_rounds - 1) / OVERTIME_HALF_ROUNDS
+    }
+}
+
+/// Whether team one plays the other side than it started on in `half`. Teams switch at
+/// halftime, keep their side into overtime and switch at every overtime halftime
+fn sides_switched(half: i32) -> bool {
+    half == 2 || (half > 2 && half % 2 == 1)
+}
+
+/// Winners & scores of the rounds of a demo, which only knows the winning side. The starting
+/// sides of the match are wrong after a knife round, so the ones the final score agrees with
+/// are used
+fn rounds_from_demo(
+    rounds: &[DemoRound],
+    team_one_starts_ct: bool,
+    score: Option<(i32, i32)>,
+    half_rounds: i32,
+) -> Vec<MatchRound> {
+    let replay = |starts_ct: bool| {
+        let (mut team_one_score, mut team_two_score) = (0, 0);
+        rounds
+            .iter()
+            .map(|r| {
+                let ct = starts_ct != sides_switched(half_of(r.round, half_rounds));
+                let team_one_won = r.winner_side.eq_ignore_ascii_case("ct") == ct;
+                if team_one_won {
+                    team_one_score += 1;
+                } else {
+                    team_two_score += 1;
+                }
+                MatchRound {
+                    round: r.round,
+                    winner_side: r.winner_side.to_lowercase(),
+                    reason: r.reason.clone(),
+                    team_one_won,
+                    team_one_score,
+                    team_two_score,
+                    ended_at: None,
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    let as_started = replay(team_one_starts_ct);
+    let final_score =
+        |rounds: &[MatchRound]| rounds.last().map(|r| (r.team_one_score, r.team_two_score));
+    if score.is_some() && final_score(&as_started) != score {
+        let swapped = replay(!team_one_starts_ct);
+        if final_score(&swapped) == score {
+            return swapped;
+        }
+    }
+    as_started
+}
+
+fn half_label(half: i32) -> String {
+    match half {
+        1 | 2 => format!("H{}", half),
+        _ => format!("OT{}", (half - 1) / 2),
+    }
+}
+
+fn other_side(side: &str) -> &'static str {
+    if side.eq_ignore_ascii_case("ct") {
+        "T"
+    } else {
+        "CT"
+    }
+}
+
+impl MapTimeline {
+    pub fn new(
+        match_id: i32,
+        map: String,
+        teams: (String, String),
+        rounds: Vec<MatchRound>,
+        half_rounds: i32,
+    ) -> MapTimeline {
+        let rounds = rounds
+            .into_iter()
+            .map(|r| TimelineRound {
+                round: r.round,
+                half: half_of(r.round, half_rounds),
+                winner: if r.team_one_won {
+                    "team_one"
+                } else {
+                    "team_two"
+                },
+                winner_side: r.winner_side,
+                reason: r.reason,
+                team_one_score: r.team_one_score,
+                team_two_score: r.team_two_score,
+                ended_at: r.ended_at,
+            })
+            .collect();
+        MapTimeline {
+            match_id,
+            map,
+            team_one: teams.0,
+            team_two: teams.1,
+            rounds,
+        }
+    }
+
+    /// Rounds won per half (■ team one, □ team two) with team one's side and a sparkline of
+    /// team one's lead, followed by the longest run of rounds
+    pub fn chart(&self) -> String {
+        let Some(last) = self.rounds.last() else {
+            return String::new();
+        };
+        let leads: Vec<i32> = self
+            .rounds
+            .iter()
+            .map(|r| r.team_one_score - r.team_two_score)
+            .collect();
+        let lowest = leads.iter().copied().min().unwrap_or_default().min(0);
+        let highest = leads.iter().copied().max().unwrap_or_default().max(0);
+        let level = |lead: i32| {
+            if highest == lowest {
+                LEVELS[LEVELS.len() / 2]
+            } else {
+                LEVELS[((lead - lowest) * (LEVELS.len() as i32 - 1) / (highest - lowest)) as usize]
+            }
+        };
+        let width = self
+            .rounds
+            .iter()
+            .fold(Vec::<(i32, usize)>::new(), |mut halves, r| {
+                match halves.last_mut() {
+                    Some((half, count)) if *half == r.half => *count += 1,
+                    _ => halves.push((r.half, 1)),
+                }
+                halves
+            })
+            .into_iter()
+            .map(|(_, count)| count)
+            .max()
+            .unwrap_or_default();
+
+        let mut s = format!(
+            "**{}** - {} `{}` - `{}` {}\n```\n",
+            self.map, self.team_one, last.team_one_score, last.team_two_score, self.team_two
+        );
+        let (mut before_one, mut before_two) = (0, 0);
+        let mut start = 0;
+        while start < self.rounds.len() {
+            let half = self.rounds[start].half;
+            let end = self.rounds[start..]
+                .iter()
+                .position(|r| r.half != half)
+                .map_or(self.rounds.len(), |p| start + p);
+            let rounds = &self.rounds[start..end];
+            let first = &rounds[0];
+            let side = if first.winner == "team_one" {
+                first.winner_side.to_uppercase()
+            } else {
+                other_side(&first.winner_side).to_string()
+            };
+            let wins: String = rounds
+                .iter()
+                .map(|r| if r.winner == "team_one" { '■' } else { '□' })
+                .collect();
+            let momentum: String = leads[start..end].iter().map(|l| level(*l)).collect();
+            let end_round = &rounds[rounds.len() - 1];
+            let mut score = format!(
+                "{}-{}",
+                end_round.team_one_score - before_one,
+                end_round.team_two_score - before_two
+            );
+            if start > 0 {
+                score.push_str(
+                    format!(
+                        " ({}-{})",
+                        end_round.team_one_score, end_round.team_two_score
+                    )
+                    .as_str(),
+                );
+            }
+            s.push_str(
+                format!(
+                    "{:<4}{:<3}{:<width$}  {}\n",
+                    half_label(half),
+                    side,
+                    wins,
+                    score,
+                    width = width
+                )
+                .as_str(),
+            );
+            s.push_str(format!("{:7}{}\n", "", momentum).as_str());
+            before_one = end_round.team_one_score;
+            before_two = end_round.team_two_score;
+            start = end;
+        }
+        s.push_str("```\n");
+        if let Some((winner, length, first)) = self.longest_run() {
+            let team = if winner == "team_one" {
+                &self.team_one
+            } else {
+                &self.team_two
+            };
+            s.push_str(
+                format!(
+                    "Longest run: **{}** {} rounds ({}-{})\n",
+                    team,
+                    length,
+                    first,
+                    first + length - 1
+                )
+                .as_str(),
+            );
+        }
+        s
+    }
+
+    /// Winner, length & first round of the longest run of won rounds, the first one on ties
+    fn longest_run(&self) -> Option<(&'static str, i32, i32)> {
+        let mut longest: Option<(&'static str, i32, i32)> = None;
+        let mut current: Option<(&'static str, i32, i32)> = None;
+        for round in &self.rounds {
+            current = match current {
+                Some((winner, length, first)) if winner == round.winner => {
+                    Some((winner, length + 1, first))
+                }
+                _ => Some((round.winner, 1, round.round)),
+            };
+            if current.map(|c| c.1) > longest.map(|l| l.1) {
+                longest = current;
+            }
+        }
+        longest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rounds of `winners` (`1` team one, `2` team two) with team one on CT in the first half
+    fn rounds(winners: &str, half_rounds: i32) -> Vec<MatchRound> {
+        let (mut team_one_score, mut team_two_score) = (0, 0);
+        winners
+            .chars()
+            .zip(1..)
+            .map(|(winner, round)| {
+                let team_one_won = winner == '1';
+                if team_one_won {
+                    team_one_score += 1;
+                } else {
+                    team_two_score += 1;
+                }
+                let team_one_ct = !sides_switched(half_of(round, half_rounds));
+                MatchRound {
+                    round,
+                    winner_side: String::from(if team_one_won == team_one_ct {
+                        "ct"
+                    } else {
+                        "t"
+                    }),
+                    reason: String::from("t_eliminated"),
+                    team_one_won,
+                    team_one_score,
+                    team_two_score,
+                    ended_at: None,
+                }
+            })
+            .collect()
+    }
+
+    fn timeline(winners: &str, half_rounds: i32) -> MapTimeline {
+        MapTimeline::new(
+            1,
+            String::from("de_dust2"),
+            (String::from("One"), String::from("Two")),
+            rounds(winners, half_rounds),
+            half_rounds,
+        )
+    }
+
+    #[test]
+    fn halves() {
+        assert_eq!(half_of(1, 15), 1);
+        assert_eq!(half_of(15, 15), 1);
+        assert_eq!(half_of(16, 15), 2);
+        assert_eq!(half_of(30, 15), 2);
+        assert_eq!(half_of(31, 15), 3);
+        assert_eq!(half_of(33, 15), 3);
+        assert_eq!(half_of(34, 15), 4);
+        assert_eq!(half_of(37, 15), 5);
+        assert_eq!(half_of(24, 12), 2);
+        assert_eq!(half_of(25, 12), 3);
+        assert_eq!(half_label(2), "H2");
+        assert_eq!(half_label(3), "OT1");
+        assert_eq!(half_label(4), "OT1");
+        assert_eq!(half_label(5), "OT2");
+    }
+
+    #[test]
+    fn sides_switch_at_halftime_but_not_into_overtime() {
+        let switched: Vec<bool> = (1..=6).map(sides_switched).collect();
+        assert_eq!(switched, vec![false, true, true, false, true, false]);
+    }
+
+    #[test]
+    fn chart() {
+        let chart = timeline("112221", 3).chart();
+        assert_eq!(
+            chart,
+            "**de_dust2** - One `3` - `3` Two\n\
+             ```\n\
+             H1  CT ■■□  2-1\n       ▅█▅\n\
+             H2  T  □□■  1-2 (3-3)\n       ▃▁▃\n\
+             ```\n\
+             Longest run: **Two** 3 rounds (3-5)\n"
+        );
+        assert_eq!(timeline("", 3).chart(), "");
+    }
+
+    #[test]
+    fn longest_run() {
+        assert_eq!(
+            timeline("112221", 3).longest_run(),
+            Some(("team_two", 3, 3))
+        );
+        // ties go to the first run
+        assert_eq!(timeline("1122", 3).longest_run(), Some(("team_one", 2, 1)));
+        assert_eq!(timeline("", 3).longest_run(), None);
+    }
+
+    #[test]
+    fn demo_rounds_follow_the_final_score() {
+        let demo: Vec<DemoRound> = ["ct", "ct", "t", "ct"]
+            .iter()
+            .zip(1..)
+            .map(|(side, round)| DemoRound {
+                round,
+                winner_side: side.to_string(),
+                reason: String::from("t_eliminated"),
+            })
+            .collect();
+        let as_started = rounds_from_demo(&demo, true, None, 2);
+        assert_eq!(as_started, rounds("1112", 2));
+        assert_eq!(rounds_from_demo(&demo, true, Some((3, 1)), 2), as_started);
+        // the knife round put team one on T first
+        let knifed = rounds_from_demo(&demo, true, Some((1, 3)), 2);
+        let winners: Vec<bool> = knifed.iter().map(|r| r.team_one_won).collect();
+        assert_eq!(winners, vec![false, false, false, true]);
+        assert_eq!((knifed[3].team_one_score, knifed[3].team_two_score), (1, 3));
+    }
+}
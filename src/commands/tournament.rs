@@ -0,0 +1,1465 @@
+use crate::commands::audit::{audit_log, AuditAction};
+use crate::commands::config::guild_id;
+use crate::commands::pagination::paginate;
+use crate::commands::self_hosted::MatchPlugin;
+use crate::commands::steamid::SteamUser;
+use crate::commands::team::get_guild_team;
+use crate::Context;
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use matchbot_core::team::Team;
+use matchbot_core::tournament::Tournament;
+use poise::command;
+use reqwest::StatusCode;
+use serenity::http::{Http, HttpError};
+use serenity::model::id::{GuildId, RoleId};
+use sqlx::types::time::OffsetDateTime;
+use sqlx::{FromRow, PgExecutor, PgPool, Postgres, Transaction};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use strum_macros::{Display, EnumIter, EnumString};
+
+pub async fn create_tournament(
+    executor: impl PgExecutor<'_>,
+    guild: i64,
+    name: &str,
+    started_at: OffsetDateTime,
+) -> Result<i32> {
+    Ok(sqlx::query_scalar!(
+        "insert into tournament (guild, name, started_at) values ($1, $2, $3) returning id",
+        guild,
+        name,
+        started_at
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+/// All tournaments of `guild` that have not ended yet, oldest first
+pub async fn get_active(executor: impl PgExecutor<'_>, guild: i64) -> Result<Vec<Tournament>> {
+    Ok(sqlx::query_as!(
+        Tournament,
+        "select id, name, started_at, completed_at
+             from tournament
+             where guild = $1 and completed_at is null
+             order by started_at, id",
+        guild
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+pub async fn get_active_by_name(
+    executor: impl PgExecutor<'_>,
+    guild: i64,
+    name: &str,
+) -> Result<Option<Tournament>> {
+    Ok(sqlx::query_as!(
+        Tournament,
+        "select id, name, started_at, completed_at
+             from tournament
+             where guild = $1 and completed_at is null and lower(name) = lower($2)
+             order by started_at, id
+             limit 1",
+        guild,
+        name
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+/// Whether match series `series` belongs to `guild`, series belong to the guild of their teams
+pub async fn is_guild_series(
+    executor: impl PgExecutor<'_>,
+    guild: i64,
+    series: i32,
+) -> Result<bool> {
+    Ok(sqlx::query_scalar!(
+        r#"select exists(select 1
+                           from match_series ms
+                              join teams t on t.id = ms.team_one
+                           where ms.id = $2 and t.guild = $1) as "exists!""#,
+        guild,
+        series
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+/// Ids out of `series` that belong to `guild`
+pub async fn get_guild_series_ids(
+    executor: impl PgExecutor<'_>,
+    guild: i64,
+    series: &[i32],
+) -> Result<Vec<i32>> {
+    Ok(sqlx::query_scalar!(
+        "select ms.id
+             from match_series ms
+                join teams t on t.id = ms.team_one
+             where t.guild = $1 and ms.id = any($2)",
+        guild,
+        series
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Ids of the unfinished match series of `user`'s teams in `guild`, optionally limited to
+/// `tournament`
+pub async fn get_user_series_ids(
+    executor: impl PgExecutor<'_>,
+    guild: i64,
+    user: i64,
+    tournament: Option<i32>,
+    limit: i64,
+) -> Result<Vec<i32>> {
+    Ok(sqlx::query_scalar!(
+        "select ms.id
+             from match_series ms
+                join teams t on t.id = ms.team_one
+             where t.guild = $1
+               and ms.completed_at is null
+               and ($3::integer is null or ms.tournament = $3)
+               and exists(select 1
+                            from team_members tm
+                            where tm.member = $2 and tm.team in (ms.team_one, ms.team_two))
+             order by ms.scheduled_at nulls last, ms.id
+             limit $4",
+        guild,
+        user,
+        tournament,
+        limit
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Number of completed match series of `guild` with a score
+pub async fn count_completed_series(executor: impl PgExecutor<'_>, guild: i64) -> Result<i64> {
+    Ok(sqlx::query_scalar!(
+        r#"select count(*) as "count!"
+               from match_series ms
+                  join teams t on t.id = ms.team_one
+               where t.guild = $1
+                 and ms.completed_at is not null
+                 and exists(select 1
+                              from match m
+                                 join match_scores sc on sc.match_id = m.id
+                              where m.match_series = ms.id
+                                and (sc.team_one_score > 0 or sc.team_two_score > 0))"#,
+        guild
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+/// Ids of the completed match series of `guild` with a score, most recently completed first
+pub async fn get_completed_series_ids(
+    executor: impl PgExecutor<'_>,
+    guild: i64,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<i32>> {
+    Ok(sqlx::query_scalar!(
+        "select ms.id
+             from match_series ms
+                join teams t on t.id = ms.team_one
+             where t.guild = $1
+               and ms.completed_at is not null
+               and exists(select 1
+                            from match m
+                               join match_scores sc on sc.match_id = m.id
+                            where m.match_series = ms.id
+                              and (sc.team_one_score > 0 or sc.team_two_score > 0))
+             order by ms.completed_at desc, ms.id desc
+             limit $2 offset $3",
+        guild,
+        limit,
+        offset
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Id of the next unfinished match series of `user`'s team in `guild`,
+/// optionally limited to `tournament`
+pub async fn next_user_series(
+    executor: impl PgExecutor<'_>,
+    user: i64,
+    guild: i64,
+    tournament: Option<i32>,
+) -> Result<Option<i32>> {
+    Ok(sqlx::query_scalar!(
+        "select ms.id
+             from match_series ms
+                join team_members tm on tm.team = ms.team_one or tm.team = ms.team_two
+                join teams t on t.id = tm.team
+             where tm.member = $1
+               and t.guild = $2
+               and ($3::integer is null or ms.tournament = $3)
+               and ms.completed_at is null
+             order by ms.id
+             limit 1",
+        user,
+        guild,
+        tournament
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+pub(crate) async fn active_tournaments<'a>(
+    context: Context<'_>,
+    partial: &'a str,
+) -> impl Stream<Item = String> + 'a {
+    let guild = context.guild_id().map_or(0, |g| g.0 as i64);
+    let names: Vec<String> = match get_active(&context.data().pool, guild).await {
+        Ok(tournaments) => tournaments.into_iter().map(|t| t.name).collect(),
+        Err(err) => {
+            log::error!("{:#?}", err);
+            vec![]
+        }
+    };
+    futures::stream::iter(names).filter(move |name| {
+        futures::future::ready(name.to_lowercase().starts_with(&partial.to_lowercase()))
+    })
+}
+
+/// Active tournament named `name`, or the only active tournament when no name is given.
+/// Responds to the author when the tournament cannot be determined
+pub(crate) async fn pick_tournament(
+    context: &Context<'_>,
+    name: Option<String>,
+) -> Result<Option<Tournament>> {
+    let pool = &context.data().pool;
+    let guild = guild_id(context)?;
+    if let Some(name) = name {
+        let tournament = get_active_by_name(pool, guild, &name).await?;
+        if tournament.is_none() {
+            context
+                .say(format!(
+                    "Could not find an active tournament named `{}`",
+                    name
+                ))
+                .await?;
+        }
+        return Ok(tournament);
+    }
+    let mut active = get_active(pool, guild).await?;
+    match active.len() {
+        0 => {
+            context
+                .say("There is no active tournament, use `/admin tournament new` to create one.")
+                .await?;
+            Ok(None)
+        }
+        1 => Ok(active.pop()),
+        _ => {
+            context
+                .say("There are multiple active tournaments, please choose one with the `tournament` option.")
+                .await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Membership of a team in a tournament, a team can play in several active tournaments
+#[derive(Debug, FromRow, Clone)]
+pub struct TournamentTeam {
+    pub tournament: i32,
+    pub team: i32,
+    pub joined_at: OffsetDateTime,
+}
+
+impl TournamentTeam {
+    pub async fn add(executor: impl PgExecutor<'_>, tournament: i32, team: i32) -> Result<()> {
+        sqlx::query!(
+            "insert into tournament_teams (tournament, team) values ($1, $2)
+                 on conflict (tournament, team) do nothing",
+            tournament,
+            team
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+    pub async fn remove(executor: impl PgExecutor<'_>, tournament: i32, team: i32) -> Result<()> {
+        sqlx::query!(
+            "delete from tournament_teams where tournament = $1 and team = $2",
+            tournament,
+            team
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Decides which team starts the map veto of a series
+#[derive(Debug, Copy, Clone, PartialEq, Eq, sqlx::Type, Display, EnumString, EnumIter)]
+#[sqlx(type_name = "veto_start_rule", rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum VetoStartRule {
+    /// Bo1: team two starts, Bo3/Bo5: team one starts
+    Fixed,
+    /// Team one picks between starting the veto and choosing side on the decider
+    HigherSeedChooses,
+    /// Team two starts the veto, team one chooses side on the decider. In a Bo1 team one picks
+    /// the map, so team two chooses the side
+    LowerSeedStarts,
+    /// Coin toss winner picks between starting the veto and choosing side on the decider
+    CoinToss,
+}
+
+impl VetoStartRule {
+    pub async fn get_by_series(
+        executor: impl PgExecutor<'_>,
+        match_series: i32,
+    ) -> Result<VetoStartRule> {
+        Ok(sqlx::query_scalar!(
+            r#"select t.veto_start_rule as "veto_start_rule: VetoStartRule"
+                 from tournament t
+                    join match_series ms on ms.tournament = t.id
+                 where ms.id = $1"#,
+            match_series
+        )
+        .fetch_one(executor)
+        .await?)
+    }
+    pub async fn set(
+        executor: impl PgExecutor<'_>,
+        tournament: i32,
+        rule: VetoStartRule,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            "update tournament set veto_start_rule = $2 where id = $1",
+            tournament,
+            rule as VetoStartRule,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+/// Decides how starting sides are chosen for the maps of a series
+#[derive(Debug, Copy, Clone, PartialEq, Eq, sqlx::Type, Display, EnumString, EnumIter)]
+#[sqlx(type_name = "side_selection", rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SideSelection {
+    /// The team that did not pick the map chooses the starting side
+    Pick,
+    /// Sides on the decider map are decided by a knife round
+    KnifeDecider,
+    /// Sides on every map are decided by a knife round
+    KnifeAll,
+}
+
+impl SideSelection {
+    /// Series override if set, otherwise the tournament setting
+    pub async fn get_by_series(
+        executor: impl PgExecutor<'_>,
+        match_series: i32,
+    ) -> Result<SideSelection> {
+        Ok(sqlx::query_scalar!(
+            r#"select coalesce(ms.side_selection, t.side_selection) as "side_selection!: SideSelection"
+                 from match_series ms
+                    join tournament t on t.id = ms.tournament
+                 where ms.id = $1"#,
+            match_series
+        )
+        .fetch_one(executor)
+        .await?)
+    }
+    pub async fn set(
+        executor: impl PgExecutor<'_>,
+        tournament: i32,
+        side_selection: SideSelection,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            "update tournament set side_selection = $2 where id = $1",
+            tournament,
+            side_selection as SideSelection,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    pub async fn set_for_series(
+        executor: impl PgExecutor<'_>,
+        match_series: i32,
+        side_selection: Option<SideSelection>,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            "update match_series set side_selection = $2 where id = $1",
+            match_series,
+            side_selection as Option<SideSelection>,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    pub fn is_knife(&self, is_decider: bool) -> bool {
+        match self {
+            SideSelection::Pick => false,
+            SideSelection::KnifeDecider => is_decider,
+            SideSelection::KnifeAll => true,
+        }
+    }
+}
+
+/// Game the matches of a tournament are played in
+#[derive(Debug, Copy, Clone, PartialEq, Eq, sqlx::Type, Display, EnumString, EnumIter)]
+#[sqlx(type_name = "game", rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Game {
+    Csgo,
+    Cs2,
+}
+
+impl Game {
+    pub async fn get_by_series(executor: impl PgExecutor<'_>, match_series: i32) -> Result<Game> {
+        Ok(sqlx::query_scalar!(
+            r#"select t.game as "game: Game"
+                 from tournament t
+                    join match_series ms on ms.tournament = t.id
+                 where ms.id = $1"#,
+            match_series
+        )
+        .fetch_one(executor)
+        .await?)
+    }
+    /// Only changes the game of tournaments without series, whose maps, servers & demos were
+    /// set up for the old one. `false` when the tournament already has series
+    pub async fn set(executor: impl PgExecutor<'_>, tournament: i32, game: Game) -> Result<bool> {
+        let result = sqlx::query!(
+            "update tournament set game = $2
+                 where id = $1
+                   and not exists (select 1 from match_series where tournament = $1)",
+            tournament,
+            game as Game,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    pub fn label(&self) -> &'static str {
+        match self {
+            Game::Csgo => "CS:GO",
+            Game::Cs2 => "CS2",
+        }
+    }
+    /// Prefix of the game specific Dathost server settings
+    pub fn dathost_settings(&self) -> &'static str {
+        match self {
+            Game::Csgo => "csgo_settings",
+            Game::Cs2 => "cs2_settings",
+        }
+    }
+    /// Steam app id game server login tokens are created for, CS2 kept the CS:GO app id
+    pub fn app_id(&self) -> u32 {
+        730
+    }
+    /// Rounds of a regulation half, overtime halves are 3 rounds in both games
+    pub fn half_rounds(&self) -> i32 {
+        match self {
+            Game::Csgo => 15,
+            Game::Cs2 => 12,
+        }
+    }
+    /// Plugin self-hosted servers of this game run
+    pub fn match_plugin(&self) -> MatchPlugin {
+        match self {
+            Game::Csgo => MatchPlugin::Get5,
+            Game::Cs2 => MatchPlugin::Matchzy,
+        }
+    }
+    /// File name of the demo of map `map_number` in the bucket, `None` for Bo1s
+    pub fn demo_name(&self, match_id: &str, map_number: Option<usize>) -> String {
+        match (self, map_number) {
+            (Game::Csgo, None) => format!("{}.dem", match_id),
+            (Game::Csgo, Some(map_number)) => format!("{}_{}.dem", match_id, map_number),
+            (Game::Cs2, map_number) => {
+                format!("{}_map{}.dem", match_id, map_number.unwrap_or(1))
+            }
+        }
+    }
+}
+
+/// How the server location of a series is chosen
+#[derive(Debug, Copy, Clone, PartialEq, Eq, sqlx::Type, Display, EnumString, EnumIter)]
+#[sqlx(type_name = "server_selection", rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ServerSelection {
+    /// Teams ban locations until one is left
+    Pick,
+    /// The location fairest to the home regions of both teams is picked
+    Auto,
+    /// Picked automatically when both teams have a home region, otherwise teams ban locations
+    AutoOrPick,
+}
+
+impl ServerSelection {
+    pub async fn get_by_series(
+        executor: impl PgExecutor<'_>,
+        match_series: i32,
+    ) -> Result<ServerSelection> {
+        Ok(sqlx::query_scalar!(
+            r#"select t.server_selection as "server_selection: ServerSelection"
+                 from tournament t
+                    join match_series ms on ms.tournament = t.id
+                 where ms.id = $1"#,
+            match_series
+        )
+        .fetch_one(executor)
+        .await?)
+    }
+    pub async fn set(
+        executor: impl PgExecutor<'_>,
+        tournament: i32,
+        server_selection: ServerSelection,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            "update tournament set server_selection = $2 where id = $1",
+            tournament,
+            server_selection as ServerSelection,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, sqlx::Type, Display)]
+#[sqlx(type_name = "registration_status", rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum RegistrationStatus {
+    Registered,
+    Waitlisted,
+    Dropped,
+}
+
+#[derive(Debug, FromRow, Clone)]
+pub struct RegistrationSettings {
+    pub id: i32,
+    pub started_at: OffsetDateTime,
+    pub registration_opens_at: Option<OffsetDateTime>,
+    pub registration_closes_at: Option<OffsetDateTime>,
+    pub check_in_opens_at: Option<OffsetDateTime>,
+    pub check_in_finalized: bool,
+    pub max_teams: Option<i32>,
+    pub min_roster: i32,
+}
+
+#[derive(Debug, FromRow, Clone)]
+pub struct Registration {
+    pub tournament: i32,
+    pub team: i32,
+    pub name: String,
+    pub role: i64,
+    pub status: RegistrationStatus,
+    pub registered_at: OffsetDateTime,
+    pub checked_in_at: Option<OffsetDateTime>,
+}
+
+impl RegistrationSettings {
+    pub async fn get(
+        executor: impl PgExecutor<'_>,
+        tournament: i32,
+    ) -> Result<RegistrationSettings> {
+        Ok(sqlx::query_as!(
+            RegistrationSettings,
+            "select id, started_at, registration_opens_at, registration_closes_at, check_in_opens_at,
+                    check_in_finalized, max_teams, min_roster
+                 from tournament
+                 where id = $1",
+            tournament
+        )
+        .fetch_one(executor)
+        .await?)
+    }
+    pub async fn set(
+        executor: impl PgExecutor<'_>,
+        tournament: i32,
+        registration_opens_at: OffsetDateTime,
+        registration_closes_at: OffsetDateTime,
+        check_in_opens_at: Option<OffsetDateTime>,
+        max_teams: Option<i32>,
+        min_roster: i32,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            "update tournament
+                set registration_opens_at = $2, registration_closes_at = $3, check_in_opens_at = $4,
+                    max_teams = $5, min_roster = $6, check_in_finalized = false
+                where id = $1",
+            tournament,
+            registration_opens_at,
+            registration_closes_at,
+            check_in_opens_at,
+            max_teams,
+            min_roster,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    /// Tournaments that started with an open check-in that has not been processed yet
+    pub async fn get_check_in_due(executor: impl PgExecutor<'_>) -> Result<Vec<i32>> {
+        Ok(sqlx::query_scalar!(
+            "select id
+                 from tournament
+                 where check_in_opens_at is not null
+                   and check_in_finalized is false
+                   and completed_at is null
+                   and started_at <= now()"
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+    pub fn uses_registration(&self) -> bool {
+        self.registration_opens_at.is_some()
+    }
+    pub fn registration_open(&self, now: OffsetDateTime) -> bool {
+        match (self.registration_opens_at, self.registration_closes_at) {
+            (Some(opens), Some(closes)) => opens <= now && now < closes,
+            _ => false,
+        }
+    }
+    pub fn check_in_open(&self, now: OffsetDateTime) -> bool {
+        match self.check_in_opens_at {
+            Some(opens) => !self.check_in_finalized && opens <= now && now < self.started_at,
+            None => false,
+        }
+    }
+}
+
+impl Registration {
+    pub async fn get_all(
+        executor: impl PgExecutor<'_>,
+        tournament: i32,
+    ) -> Result<Vec<Registration>> {
+        Ok(sqlx::query_as!(
+            Registration,
+            r#"select tr.tournament, tr.team, t.name, t.role, tr.status as "status: RegistrationStatus",
+                      tr.registered_at, tr.checked_in_at
+                 from tournament_registrations tr
+                    join teams t on t.id = tr.team
+                 where tr.tournament = $1
+                 order by tr.status, tr.registered_at"#,
+            tournament
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+    pub async fn get(
+        executor: impl PgExecutor<'_>,
+        tournament: i32,
+        team: i32,
+    ) -> Result<Option<Registration>> {
+        Ok(sqlx::query_as!(
+            Registration,
+            r#"select tr.tournament, tr.team, t.name, t.role, tr.status as "status: RegistrationStatus",
+                      tr.registered_at, tr.checked_in_at
+                 from tournament_registrations tr
+                    join teams t on t.id = tr.team
+                 where tr.tournament = $1 and tr.team = $2"#,
+            tournament,
+            team
+        )
+        .fetch_optional(executor)
+        .await?)
+    }
+    pub async fn add(
+        executor: impl PgExecutor<'_>,
+        tournament: i32,
+        team: i32,
+        status: RegistrationStatus,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            "insert into tournament_registrations (tournament, team, status) values ($1, $2, $3)
+                on conflict (tournament, team) do update
+                set status = $3, registered_at = now(), checked_in_at = null",
+            tournament,
+            team,
+            status as RegistrationStatus,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    pub async fn set_status(
+        executor: impl PgExecutor<'_>,
+        tournament: i32,
+        team: i32,
+        status: RegistrationStatus,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            "update tournament_registrations set status = $3 where tournament = $1 and team = $2",
+            tournament,
+            team,
+            status as RegistrationStatus,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    pub async fn check_in(
+        executor: impl PgExecutor<'_>,
+        tournament: i32,
+        team: i32,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            "update tournament_registrations set checked_in_at = now()
+                where tournament = $1 and team = $2 and status != 'dropped'",
+            tournament,
+            team,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+    pub async fn count_registered(executor: impl PgExecutor<'_>, tournament: i32) -> Result<i64> {
+        Ok(sqlx::query_scalar!(
+            r#"select count(*) as "count!"
+                 from tournament_registrations
+                 where tournament = $1 and status = 'registered'"#,
+            tournament
+        )
+        .fetch_one(executor)
+        .await?)
+    }
+    /// Registers the team, or waitlists it once `max_teams` teams are registered. The tournament
+    /// row stays locked until the team is added, so concurrent registrations cannot go over
+    /// the limit
+    pub async fn register(
+        transaction: &mut Transaction<'_, Postgres>,
+        tournament: i32,
+        team: i32,
+    ) -> Result<RegistrationStatus> {
+        let max_teams = sqlx::query_scalar!(
+            "select max_teams from tournament where id = $1 for update",
+            tournament
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        let registered = Registration::count_registered(&mut *transaction, tournament).await?;
+        let status = match max_teams {
+            Some(max_teams) if registered >= max_teams as i64 => RegistrationStatus::Waitlisted,
+            _ => RegistrationStatus::Registered,
+        };
+        Registration::add(&mut *transaction, tournament, team, status).await?;
+        TournamentTeam::add(&mut *transaction, tournament, team).await?;
+        Ok(status)
+    }
+    /// Moves the longest waiting team off the waitlist, returns its team id
+    pub async fn promote_waitlisted(
+        executor: impl PgExecutor<'_>,
+        tournament: i32,
+    ) -> Result<Option<i32>> {
+        Ok(sqlx::query_scalar!(
+            "update tournament_registrations set status = 'registered'
+                where tournament = $1 and team = (select team
+                                                    from tournament_registrations
+                                                    where tournament = $1 and status = 'waitlisted'
+                                                    order by registered_at
+                                                    limit 1)
+                returning team",
+            tournament
+        )
+        .fetch_optional(executor)
+        .await?)
+    }
+    /// Drops registered teams that did not check in and fills the free slots with checked in
+    /// teams from the waitlist
+    pub async fn finalize_check_in(pool: &PgPool, tournament: i32) -> Result<()> {
+        let settings = RegistrationSettings::get(pool, tournament).await?;
+        let mut transaction = pool.begin().await?;
+        sqlx::query!(
+            "update tournament_registrations set status = 'dropped'
+                where tournament = $1 and status = 'registered' and checked_in_at is null",
+            tournament
+        )
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query!(
+            "update tournament_registrations set status = 'registered'
+                where tournament = $1 and team in (select team
+                                                   from tournament_registrations
+                                                   where tournament = $1
+                                                     and status = 'waitlisted'
+                                                     and checked_in_at is not null
+                                                   order by registered_at
+                                                   limit greatest(coalesce($2::integer, 2147483647) - (select count(*)
+                                                                                           from tournament_registrations
+                                                                                           where tournament = $1
+                                                                                             and status = 'registered'), 0))",
+            tournament,
+            settings.max_teams,
+        )
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query!(
+            "update tournament set check_in_finalized = true where id = $1",
+            tournament
+        )
+        .execute(&mut transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+}
+
+/// Periodically drops teams that missed check-in once a tournament starts
+pub async fn check_in_task(pool: PgPool) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let due = match RegistrationSettings::get_check_in_due(&pool).await {
+            Ok(due) => due,
+            Err(err) => {
+                log::error!("{:#?}", err);
+                continue;
+            }
+        };
+        for tournament in due {
+            if let Err(err) = Registration::finalize_check_in(&pool, tournament).await {
+                log::error!("{:#?}", err);
+            } else {
+                log::info!("Finalized check-in for tournament {}", tournament);
+            }
+        }
+    }
+}
+
+/// One line per team, grouped by registration status
+pub fn format_registrations(registrations: &[Registration]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for status in [
+        RegistrationStatus::Registered,
+        RegistrationStatus::Waitlisted,
+        RegistrationStatus::Dropped,
+    ] {
+        let teams: Vec<&Registration> = registrations
+            .iter()
+            .filter(|r| r.status == status)
+            .collect();
+        if teams.is_empty() {
+            continue;
+        }
+        lines.push(format!("**{}** ({})", status, teams.len()));
+        for (i, r) in teams.iter().enumerate() {
+            let mut line = format!("{}. <@&{}>", i + 1, r.role);
+            if r.checked_in_at.is_some() {
+                line.push_str(" ✅");
+            }
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+/// Team of the author if they are its captain, responds to the author otherwise
+async fn captain_team(context: &Context<'_>) -> Result<Option<Team>> {
+    let pool = &context.data().pool;
+    let author = context.author().id.0 as i64;
+    let Some(team) = get_guild_team(pool, guild_id(context)?, author).await? else {
+        context.say("You are not on a team!").await?;
+        return Ok(None);
+    };
+    if team.captain != author {
+        context.say("You are not the captain of this team!").await?;
+        return Ok(None);
+    }
+    Ok(Some(team))
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    subcommands("register", "checkin", "withdraw", "participants", "standings")
+)]
+pub(crate) async fn tournament(_context: Context<'_>) -> Result<()> {
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("en-US", "Register your team for a tournament")
+)]
+pub(crate) async fn register(
+    context: Context<'_>,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let settings = RegistrationSettings::get(pool, current.id).await?;
+    if !settings.registration_open(OffsetDateTime::now_utc()) {
+        context
+            .say(format!("Registration for {} is not open.", current.name))
+            .await?;
+        return Ok(());
+    }
+    let Some(team) = captain_team(&context).await? else {
+        return Ok(());
+    };
+    if let Some(registration) = Registration::get(pool, current.id, team.id).await? {
+        if registration.status != RegistrationStatus::Dropped {
+            context
+                .say(format!(
+                    "<@&{}> is already {} for {}.",
+                    team.role, registration.status, current.name
+                ))
+                .await?;
+            return Ok(());
+        }
+    }
+    let members = team.members(pool).await?;
+    if (members.len() as i32) < settings.min_roster {
+        context
+            .say(format!(
+                "Your team needs at least {} players to register, it currently has {}.",
+                settings.min_roster,
+                members.len()
+            ))
+            .await?;
+        return Ok(());
+    }
+    let steam_users = SteamUser::get_by_team(pool, team.role).await?;
+    let missing: Vec<String> = members
+        .iter()
+        .filter(|m| !steam_users.iter().any(|u| &u.discord == *m))
+        .map(|m| format!("<@{}>", m))
+        .collect();
+    if !missing.is_empty() {
+        context
+            .say(format!(
+                "The following players need to set their SteamID using `/steamid` first: {}",
+                missing.join(", ")
+            ))
+            .await?;
+        return Ok(());
+    }
+    let mut transaction = pool.begin().await?;
+    let status = Registration::register(&mut transaction, current.id, team.id).await?;
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::TeamRegistered,
+        &team.name,
+        None,
+        Some(format!("{} ({})", current.name, status)),
+    )
+    .await?;
+    match status {
+        RegistrationStatus::Waitlisted => {
+            context
+                .say(format!(
+                    "{} is full, <@&{}> has been added to the waitlist.",
+                    current.name, team.role
+                ))
+                .await?
+        }
+        _ => {
+            context
+                .say(format!(
+                    "<@&{}> is registered for {}!",
+                    team.role, current.name
+                ))
+                .await?
+        }
+    };
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("en-US", "Check in your team for a tournament")
+)]
+pub(crate) async fn checkin(
+    context: Context<'_>,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let settings = RegistrationSettings::get(pool, current.id).await?;
+    if !settings.check_in_open(OffsetDateTime::now_utc()) {
+        context
+            .say(format!("Check-in for {} is not open.", current.name))
+            .await?;
+        return Ok(());
+    }
+    let Some(team) = captain_team(&context).await? else {
+        return Ok(());
+    };
+    if !Registration::check_in(pool, current.id, team.id).await? {
+        context
+            .say(format!(
+                "<@&{}> is not registered for {}.",
+                team.role, current.name
+            ))
+            .await?;
+        return Ok(());
+    }
+    context
+        .say(format!("<@&{}> is checked in!", team.role))
+        .await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("en-US", "Withdraw your team from a tournament")
+)]
+pub(crate) async fn withdraw(
+    context: Context<'_>,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let Some(team) = captain_team(&context).await? else {
+        return Ok(());
+    };
+    let registration = Registration::get(pool, current.id, team.id).await?;
+    let Some(registration) = registration.filter(|r| r.status != RegistrationStatus::Dropped)
+    else {
+        context
+            .say(format!(
+                "<@&{}> is not registered for {}.",
+                team.role, current.name
+            ))
+            .await?;
+        return Ok(());
+    };
+    let mut transaction = pool.begin().await?;
+    Registration::set_status(
+        &mut transaction,
+        current.id,
+        team.id,
+        RegistrationStatus::Dropped,
+    )
+    .await?;
+    TournamentTeam::remove(&mut transaction, current.id, team.id).await?;
+    if registration.status == RegistrationStatus::Registered {
+        Registration::promote_waitlisted(&mut transaction, current.id).await?;
+    }
+    audit_log(
+        &context,
+        transaction,
+        AuditAction::TeamWithdrawn,
+        &team.name,
+        Some(format!("{} ({})", current.name, registration.status)),
+        None,
+    )
+    .await?;
+    context
+        .say(format!("<@&{}> withdrew from {}.", team.role, current.name))
+        .await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("en-US", "Show teams registered for a tournament")
+)]
+pub(crate) async fn participants(
+    context: Context<'_>,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let registrations = Registration::get_all(pool, current.id).await?;
+    if registrations.is_empty() {
+        context.say("No teams have registered yet.").await?;
+        return Ok(());
+    }
+    paginate(
+        &context,
+        &format!("{} participants", current.name),
+        &format_registrations(&registrations),
+    )
+    .await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("en-US", "Show series & map results of a tournament")
+)]
+pub(crate) async fn standings(
+    context: Context<'_>,
+    #[description = "Tournament"]
+    #[autocomplete = "active_tournaments"]
+    tournament: Option<String>,
+) -> Result<()> {
+    let pool = &context.data().pool;
+    let Some(current) = pick_tournament(&context, tournament).await? else {
+        return Ok(());
+    };
+    let standings = Standing::get_by_tournament(pool, current.id).await?;
+    if standings.is_empty() {
+        context
+            .say(format!("No teams are taking part in {} yet.", current.name))
+            .await?;
+        return Ok(());
+    }
+    let lines: Vec<String> = standings
+        .iter()
+        .enumerate()
+        .map(|(i, standing)| {
+            format!(
+                "{}. <@&{}> {}-{} (maps {}-{})",
+                i + 1,
+                standing.role,
+                standing.series_won,
+                standing.series_lost,
+                standing.maps_won,
+                standing.maps_lost
+            )
+        })
+        .collect();
+    paginate(&context, &format!("{} standings", current.name), &lines).await?;
+    Ok(())
+}
+
+/// Series & map results of a team in a tournament
+#[derive(Debug, FromRow, Clone)]
+pub struct Standing {
+    pub team: i32,
+    pub role: i64,
+    pub series_won: i64,
+    pub series_lost: i64,
+    pub maps_won: i64,
+    pub maps_lost: i64,
+}
+
+impl Standing {
+    pub async fn get_by_tournament(
+        executor: impl PgExecutor<'_>,
+        tournament: i32,
+    ) -> Result<Vec<Standing>> {
+        Ok(sqlx::query_as!(
+            Standing,
+            r#"with series_results as (select ms.team_one,
+                                              ms.team_two,
+                                              count(*) filter (where sc.team_one_score > sc.team_two_score) as one_wins,
+                                              count(*) filter (where sc.team_two_score > sc.team_one_score) as two_wins
+                                       from match_series ms
+                                                join match m on m.match_series = ms.id
+                                                join match_scores sc on sc.match_id = m.id
+                                       where ms.tournament = $1
+                                         and ms.completed_at is not null
+                                       group by ms.id)
+               select t.id as team,
+                      t.role,
+                      (select count(*) from series_results sr
+                       where (sr.team_one = t.id and sr.one_wins > sr.two_wins)
+                          or (sr.team_two = t.id and sr.two_wins > sr.one_wins)) as "series_won!",
+                      (select count(*) from series_results sr
+                       where (sr.team_one = t.id and sr.one_wins < sr.two_wins)
+                          or (sr.team_two = t.id and sr.two_wins < sr.one_wins)) as "series_lost!",
+                      coalesce((select sum(case when sr.team_one = t.id then sr.one_wins else sr.two_wins end)
+                                from series_results sr
+                                where sr.team_one = t.id or sr.team_two = t.id), 0)::int8 as "maps_won!",
+                      coalesce((select sum(case when sr.team_one = t.id then sr.two_wins else sr.one_wins end)
+                                from series_results sr
+                                where sr.team_one = t.id or sr.team_two = t.id), 0)::int8 as "maps_lost!"
+               from tournament_teams tt
+                  join teams t on t.id = tt.team
+               where tt.tournament = $1
+               order by 3 desc, 4, 5 desc, 6, t.name"#,
+            tournament
+        )
+        .fetch_all(executor)
+        .await?)
+    }
+}
+
+/// Snapshot of a team, its roster and results taken when a tournament ends
+#[derive(Debug, FromRow, Clone)]
+pub struct TeamArchive {
+    pub tournament: i32,
+    pub team: i32,
+    pub name: String,
+    pub role: i64,
+    pub captain: i64,
+    pub members: Vec<i64>,
+    pub series_won: i32,
+    pub series_lost: i32,
+    pub maps_won: i32,
+    pub maps_lost: i32,
+    pub archived_at: OffsetDateTime,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum ArchivedRoleAction {
+    Delete,
+    Keep,
+    Rename,
+    Uncolour,
+}
+
+impl TeamArchive {
+    /// Archives every team of `tournament` with its roster and series results
+    pub async fn archive_active(executor: impl PgExecutor<'_>, tournament: i32) -> Result<u64> {
+        let result = sqlx::query!(
+            "with series_results as (select ms.team_one,
+                                            ms.team_two,
+                                            count(*) filter (where sc.team_one_score > sc.team_two_score) as one_wins,
+                                            count(*) filter (where sc.team_two_score > sc.team_one_score) as two_wins
+                                     from match_series ms
+                                              join match m on m.match_series = ms.id
+                                              join match_scores sc on sc.match_id = m.id
+                                     where ms.tournament = $1
+                                       and ms.completed_at is not null
+                                     group by ms.id)
+             insert into team_archive (tournament, team, name, role, captain, members,
+                                       series_won, series_lost, maps_won, maps_lost)
+             select $1, t.id, t.name, t.role, t.captain,
+                    coalesce((select array_agg(tm.member) from team_members tm where tm.team = t.id), '{}'),
+                    (select count(*) from series_results sr
+                     where (sr.team_one = t.id and sr.one_wins > sr.two_wins)
+                        or (sr.team_two = t.id and sr.two_wins > sr.one_wins)),
+                    (select count(*) from series_results sr
+                     where (sr.team_one = t.id and sr.one_wins < sr.two_wins)
+                        or (sr.team_two = t.id and sr.two_wins < sr.one_wins)),
+                    coalesce((select sum(case when sr.team_one = t.id then sr.one_wins else sr.two_wins end)
+                              from series_results sr
+                              where sr.team_one = t.id or sr.team_two = t.id), 0),
+                    coalesce((select sum(case when sr.team_one = t.id then sr.two_wins else sr.one_wins end)
+                              from series_results sr
+                              where sr.team_one = t.id or sr.team_two = t.id), 0)
+             from tournament_teams tt
+                join teams t on t.id = tt.team
+             where tt.tournament = $1
+             on conflict (tournament, team) do nothing",
+            tournament
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected())
+    }
+    /// Most recent archive of an inactive team of `guild` captained by `captain`
+    pub async fn get_latest_by_captain(
+        executor: impl PgExecutor<'_>,
+        guild: i64,
+        captain: i64,
+    ) -> Result<Option<TeamArchive>> {
+        Ok(sqlx::query_as!(
+            TeamArchive,
+            "select ta.*
+                 from team_archive ta
+                    join teams t on t.id = ta.team
+                 where t.guild = $1 and ta.captain = $2 and t.is_active is not true
+                 order by ta.archived_at desc
+                 limit 1",
+            guild,
+            captain
+        )
+        .fetch_optional(executor)
+        .await?)
+    }
+    /// Reactivates the archived team for `tournament` and restores the archived roster,
+    /// skipping players that joined another active team in the meantime.
+    /// Returns the restored members.
+    pub async fn carry_over(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+        tournament: i32,
+        role: i64,
+    ) -> Result<Vec<i64>> {
+        sqlx::query!(
+            "update teams set is_active = true, tournament = $2, role = $3 where id = $1",
+            self.team,
+            tournament,
+            role,
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            "insert into tournament_teams (tournament, team) values ($1, $2)
+                 on conflict (tournament, team) do nothing",
+            tournament,
+            self.team,
+        )
+        .execute(&mut *transaction)
+        .await?;
+        // the queued cleanup of the role would otherwise delete or rename it under the team
+        sqlx::query!(
+            "delete from role_cleanups
+                 where guild = (select guild from teams where id = $1) and role = $2",
+            self.team,
+            role,
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!("delete from team_members where team = $1", self.team)
+            .execute(&mut *transaction)
+            .await?;
+        let members = sqlx::query_scalar!(
+            "insert into team_members (team, member)
+                 select $1, m.member
+                 from unnest($2::int8[]) as m(member)
+                 where not exists (select 1
+                                   from team_members tm
+                                      join teams t on t.id = tm.team
+                                   where tm.member = m.member
+                                     and t.is_active is true
+                                     and t.guild = (select guild from teams where id = $1))
+                 returning member",
+            self.team,
+            &self.members,
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        Ok(members)
+    }
+}
+
+/// Ends the tournament: archives its teams, marks it completed and deactivates teams that do not
+/// play in another tournament. Role changes of the deactivated teams are queued, returns the
+/// number of archived teams
+pub async fn close_tournament(
+    transaction: &mut Transaction<'_, Postgres>,
+    tournament: &Tournament,
+    guild: i64,
+    role_action: ArchivedRoleAction,
+) -> Result<u64> {
+    let archived = TeamArchive::archive_active(&mut *transaction, tournament.id).await?;
+    sqlx::query!(
+        "update tournament set completed_at = now() where id = $1",
+        tournament.id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    // Teams still playing in another tournament keep their role & roster
+    let teams = sqlx::query!(
+        "update teams t set is_active = false
+             from tournament_teams tt
+             where tt.team = t.id
+               and tt.tournament = $1
+               and not exists (select 1
+                                 from tournament_teams other
+                                    join tournament ot on ot.id = other.tournament
+                                 where other.team = t.id and ot.completed_at is null)
+             returning t.role, t.name",
+        tournament.id
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+    if role_action != ArchivedRoleAction::Keep {
+        for team in teams {
+            sqlx::query!(
+                "insert into role_cleanups (guild, role, action, name) values ($1, $2, $3, $4)",
+                guild,
+                team.role,
+                role_action.to_string(),
+                format!("{} ({})", team.name, tournament.name),
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+    }
+    Ok(archived)
+}
+
+/// Queued role change of an ended tournament
+struct RoleCleanup {
+    id: i32,
+    guild: i64,
+    role: i64,
+    action: String,
+    name: String,
+}
+
+/// Applies the queued role changes of `guild`, or of every guild, and returns how many failed.
+/// Failed changes stay queued, a role that is already gone counts as deleted
+pub async fn apply_role_cleanups(http: &Http, pool: &PgPool, guild: Option<i64>) -> Result<usize> {
+    let cleanups = sqlx::query_as!(
+        RoleCleanup,
+        "select id, guild, role, action, name
+             from role_cleanups
+             where guild = $1 or $1 is null
+             order by id",
+        guild
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut failed = 0;
+    for cleanup in cleanups {
+        let guild = GuildId(cleanup.guild as u64);
+        let role = RoleId(cleanup.role as u64);
+        let result = match ArchivedRoleAction::from_str(&cleanup.action) {
+            Ok(ArchivedRoleAction::Delete) => guild.delete_role(http, role).await,
+            Ok(ArchivedRoleAction::Rename) => guild
+                .edit_role(http, role, |r| r.name(&cleanup.name))
+                .await
+                .map(|_| ()),
+            Ok(ArchivedRoleAction::Uncolour) => guild
+                .edit_role(http, role, |r| r.colour(0))
+                .await
+                .map(|_| ()),
+            Ok(ArchivedRoleAction::Keep) | Err(_) => Ok(()),
+        };
+        match result {
+            Err(err) if !is_unknown_role(&err) => {
+                log::warn!("Role change of {} failed: {}", role, err);
+                failed += 1;
+            }
+            _ => {
+                sqlx::query!("delete from role_cleanups where id = $1", cleanup.id)
+                    .execute(pool)
+                    .await?;
+            }
+        }
+    }
+    Ok(failed)
+}
+
+fn is_unknown_role(err: &serenity::Error) -> bool {
+    match err {
+        serenity::Error::Http(err) => matches!(
+            err.as_ref(),
+            HttpError::UnsuccessfulRequest(response) if response.status_code == StatusCode::NOT_FOUND
+        ),
+        _ => false,
+    }
+}
+
+/// Retries role changes of ended tournaments that Discord did not accept
+pub async fn role_cleanup_task(http: Arc<Http>, pool: PgPool) {
+    let mut interval = tokio::time::interval(Duration::from_secs(15 * 60));
+    loop {
+        interval.tick().await;
+        if let Err(err) = apply_role_cleanups(&http, &pool, None).await {
+            log::error!("{:#?}", err);
+        }
+    }
+}
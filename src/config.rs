@@ -0,0 +1,250 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// Read when `CONFIG_FILE` is not set, the file is optional
+const DEFAULT_CONFIG_FILE: &str = "matchbot.toml";
+/// Prefix of the environment variables `/config dathost` may read a password from
+pub const DATHOST_PASSWORD_PREFIX: &str = "DATHOST_PASSWORD_";
+/// Prefix of the environment variables self-hosted servers may read their RCON password from
+pub const RCON_PASSWORD_PREFIX: &str = "RCON_PASSWORD_";
+
+/// The only environment variable the Dathost account of a guild may read its password from
+pub fn dathost_password_env(guild: i64) -> String {
+    format!("{}{}", DATHOST_PASSWORD_PREFIX, guild)
+}
+
+pub type SharedConfig = Arc<RwLock<Config>>;
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DathostSettings {
+    pub user: Option<String>,
+    pub password: Option<String>,
+    /// Discord servers allowed to start servers on this account, others need their own
+    pub guilds: Vec<u64>,
+}
+
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookSettings {
+    pub match_end: Option<String>,
+    pub round_end: Option<String>,
+    pub series_end: Option<String>,
+}
+
+/// S3-compatible storage finished demos are uploaded to
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageSettings {
+    /// i.e. `https://s3.eu-central-1.amazonaws.com` or the url of a MinIO server
+    pub endpoint: Option<String>,
+    pub bucket: Option<String>,
+    /// Defaults to `us-east-1`
+    pub region: Option<String>,
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+}
+
+/// Settings from the config file, environment variables take precedence.
+/// Only `bucket_url`, `public_url` & `webhooks` can be reloaded while running, and only from the
+/// file: the environment is the one the bot started with, so values it sets can't be reloaded.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database_url: String,
+    pub discord_token: String,
+    /// Needed to create game server login tokens for Dathost servers
+    pub steam_api_key: Option<String>,
+    /// Public base url of the bucket match demos are uploaded to
+    pub bucket_url: Option<String>,
+    /// Base url the API is reachable at from game servers, needed for self-hosted servers
+    pub public_url: Option<String>,
+    pub api_address: Option<SocketAddr>,
+    pub metrics_address: Option<SocketAddr>,
+    /// Default Dathost account, servers can use their own with `/config dathost`
+    pub dathost: DathostSettings,
+    /// Default webhooks, servers can use their own with `/config webhooks`
+    pub webhooks: WebhookSettings,
+    /// Demo uploads are off when no storage is set
+    pub storage: StorageSettings,
+    /// Command printing the rounds & kills of the demo passed as last argument as JSON,
+    /// stored demos are only parsed when set
+    pub demo_parser: Option<String>,
+}
+
+impl Config {
+    /// Reads the config file, applies environment overrides and validates the result
+    pub fn load() -> Result<Config> {
+        let mut config = match env::var("CONFIG_FILE") {
+            Ok(path) => Config::from_file(&path)?,
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Config::from_file(DEFAULT_CONFIG_FILE)?
+            }
+            Err(_) => Config::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &str) -> Result<Config> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("could not read config file `{}`", path))?;
+        toml::from_str(&content).with_context(|| format!("invalid config file `{}`", path))
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        if let Some(value) = env_value("DATABASE_URL") {
+            self.database_url = value;
+        }
+        if let Some(value) = env_value("DISCORD_TOKEN") {
+            self.discord_token = value;
+        }
+        override_option(&mut self.steam_api_key, "STEAM_API_KEY");
+        override_option(&mut self.bucket_url, "BUCKET_URL");
+        override_option(&mut self.public_url, "PUBLIC_URL");
+        override_option(&mut self.dathost.user, "DATHOST_USER");
+        override_option(&mut self.dathost.password, "DATHOST_PASSWORD");
+        if let Some(env) = env_value("DATHOST_GUILDS") {
+            self.dathost.guilds = env
+                .split(',')
+                .map(|guild| guild.trim().parse())
+                .collect::<Result<_, _>>()
+                .with_context(|| {
+                    format!(
+                        "DATHOST_GUILDS must be a comma separated list of server ids, got `{}`",
+                        env
+                    )
+                })?;
+        }
+        override_option(&mut self.webhooks.match_end, "MATCH_END_WEBHOOK_URL");
+        override_option(&mut self.webhooks.round_end, "ROUND_END_WEBHOOK_URL");
+        override_option(&mut self.webhooks.series_end, "SERIES_END_WEBHOOK_URL");
+        override_option(&mut self.storage.endpoint, "S3_ENDPOINT");
+        override_option(&mut self.storage.bucket, "S3_BUCKET");
+        override_option(&mut self.storage.region, "S3_REGION");
+        override_option(&mut self.storage.access_key, "S3_ACCESS_KEY");
+        override_option(&mut self.storage.secret_key, "S3_SECRET_KEY");
+        override_option(&mut self.demo_parser, "DEMO_PARSER");
+        override_address(&mut self.api_address, "API_ADDRESS")?;
+        override_address(&mut self.metrics_address, "METRICS_ADDRESS")?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+        for (value, key, var) in [
+            (&self.database_url, "database_url", "DATABASE_URL"),
+            (&self.discord_token, "discord_token", "DISCORD_TOKEN"),
+        ] {
+            if value.is_empty() {
+                errors.push(format!("`{}` ({}) is required", key, var));
+            }
+        }
+        for (value, key) in [
+            (&self.bucket_url, "bucket_url"),
+            (&self.public_url, "public_url"),
+            (&self.storage.endpoint, "storage.endpoint"),
+            (&self.webhooks.match_end, "webhooks.match_end"),
+            (&self.webhooks.round_end, "webhooks.round_end"),
+            (&self.webhooks.series_end, "webhooks.series_end"),
+        ] {
+            if let Some(url) = value {
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    errors.push(format!("`{}` must be an http(s) url, got `{}`", key, url));
+                }
+            }
+        }
+        if self.dathost.user.is_some() != self.dathost.password.is_some() {
+            errors.push(String::from(
+                "`dathost.user` (DATHOST_USER) and `dathost.password` (DATHOST_PASSWORD) must be set together",
+            ));
+        }
+        let storage = [
+            &self.storage.endpoint,
+            &self.storage.bucket,
+            &self.storage.access_key,
+            &self.storage.secret_key,
+        ];
+        if storage.iter().any(|v| v.is_some()) && !storage.iter().all(|v| v.is_some()) {
+            errors.push(String::from(
+                "`storage.endpoint` (S3_ENDPOINT), `storage.bucket` (S3_BUCKET), `storage.access_key` (S3_ACCESS_KEY) and `storage.secret_key` (S3_SECRET_KEY) must be set together",
+            ));
+        }
+        if !errors.is_empty() {
+            bail!("invalid configuration:\n{}", errors.join("\n"));
+        }
+        Ok(())
+    }
+
+    /// Base url demo buttons link to, the bucket's path-style url when `bucket_url` is not set
+    pub fn demo_url(&self) -> Option<String> {
+        self.bucket_url.clone().or_else(|| {
+            let endpoint = self.storage.endpoint.as_ref()?;
+            let bucket = self.storage.bucket.as_ref()?;
+            Some(format!("{}/{}", endpoint.trim_end_matches('/'), bucket))
+        })
+    }
+
+    /// Reloadable keys set by an environment variable, a reload keeps their value
+    pub fn env_pinned() -> Vec<&'static str> {
+        [
+            ("bucket_url", "BUCKET_URL"),
+            ("public_url", "PUBLIC_URL"),
+            ("webhooks.match_end", "MATCH_END_WEBHOOK_URL"),
+            ("webhooks.round_end", "ROUND_END_WEBHOOK_URL"),
+            ("webhooks.series_end", "SERIES_END_WEBHOOK_URL"),
+        ]
+        .into_iter()
+        .filter(|(_, var)| env_value(var).is_some())
+        .map(|(key, _)| key)
+        .collect()
+    }
+
+    /// Takes over the reloadable values of `new`, returns the keys that changed
+    pub fn reload(&mut self, new: Config) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.bucket_url != new.bucket_url {
+            changed.push("bucket_url");
+        }
+        if self.public_url != new.public_url {
+            changed.push("public_url");
+        }
+        if self.webhooks != new.webhooks {
+            changed.push("webhooks");
+        }
+        self.bucket_url = new.bucket_url;
+        self.public_url = new.public_url;
+        self.webhooks = new.webhooks;
+        changed
+    }
+}
+
+/// Value of `var`, empty values count as unset
+fn env_value(var: &str) -> Option<String> {
+    env::var(var).ok().filter(|v| !v.is_empty())
+}
+
+fn override_option(value: &mut Option<String>, var: &str) {
+    if let Some(env) = env_value(var) {
+        *value = Some(env);
+    }
+}
+
+fn override_address(value: &mut Option<SocketAddr>, var: &str) -> Result<()> {
+    if let Some(env) = env_value(var) {
+        let address = env.parse().with_context(|| {
+            format!(
+                "{} must be an address like `0.0.0.0:8080`, got `{}`",
+                var, env
+            )
+        })?;
+        *value = Some(address);
+    }
+    Ok(())
+}
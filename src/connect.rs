@@ -0,0 +1,205 @@
+use crate::config::SharedConfig;
+use anyhow::Result;
+use async_trait::async_trait;
+use rand::distributions::{Alphanumeric, DistString};
+use sqlx::{PgExecutor, PgPool};
+
+/// Port of the series server a connect link points to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConnectTarget {
+    Game,
+    Gotv,
+}
+
+impl ConnectTarget {
+    /// Path of the bot's redirect to the server of the series with the connect `token`
+    pub fn path(&self, token: &str) -> String {
+        match self {
+            ConnectTarget::Game => format!("/connect/{}", token),
+            ConnectTarget::Gotv => format!("/connect/{}/gotv", token),
+        }
+    }
+}
+
+/// Web links for `steam://connect` addresses, Discord link buttons only accept http(s) urls.
+/// A trait so the connect message can run against another shortener or none at all
+#[async_trait]
+pub trait ConnectLinks: Send + Sync {
+    /// Link opening `steam://connect/<address>`, `None` when the address is shown as text instead
+    async fn link(
+        &self,
+        series: i32,
+        target: ConnectTarget,
+        address: &str,
+    ) -> Result<Option<String>>;
+}
+
+/// Random token the connect links of `match_series` are served under, kept once created so
+/// links posted before a server restart keep working
+pub async fn connect_token(executor: impl PgExecutor<'_>, match_series: i32) -> Result<String> {
+    let token = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+    Ok(sqlx::query_scalar!(
+        "insert into connect_tokens (match_series, token) values ($1, $2)
+             on conflict (match_series) do update set match_series = excluded.match_series
+             returning token",
+        match_series,
+        token,
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+/// Links to the `/connect` redirect of the bot's API, available when both `api_address`
+/// and `public_url` are set
+pub struct RedirectLinks {
+    config: SharedConfig,
+    pool: PgPool,
+}
+
+impl RedirectLinks {
+    pub fn new(config: SharedConfig, pool: PgPool) -> Self {
+        RedirectLinks { config, pool }
+    }
+}
+
+#[async_trait]
+impl ConnectLinks for RedirectLinks {
+    async fn link(
+        &self,
+        series: i32,
+        target: ConnectTarget,
+        _address: &str,
+    ) -> Result<Option<String>> {
+        let public_url = {
+            let config = self.config.read().unwrap();
+            if config.api_address.is_none() {
+                return Ok(None);
+            }
+            config.public_url.clone()
+        };
+        let Some(public_url) = public_url else {
+            return Ok(None);
+        };
+        let token = connect_token(&self.pool, series).await?;
+        Ok(Some(format!(
+            "{}{}",
+            public_url.trim_end_matches('/'),
+            target.path(&token)
+        )))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::Config;
+    use anyhow::anyhow;
+    use std::sync::{Arc, Mutex, RwLock};
+
+    /// Links answered from a fixed result, recording what was asked for
+    #[derive(Default)]
+    pub(crate) struct FakeLinks {
+        pub(crate) fail: bool,
+        pub(crate) calls: Mutex<Vec<(i32, ConnectTarget, String)>>,
+    }
+
+    #[async_trait]
+    impl ConnectLinks for FakeLinks {
+        async fn link(
+            &self,
+            series: i32,
+            target: ConnectTarget,
+            address: &str,
+        ) -> Result<Option<String>> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push((series, target, address.to_string()));
+            if self.fail {
+                return Err(anyhow!("shortener is down"));
+            }
+            Ok(Some(format!("https://fake.link/{}", address)))
+        }
+    }
+
+    fn shared_config(api_address: bool, public_url: Option<&str>) -> SharedConfig {
+        let config = Config {
+            api_address: api_address.then(|| "127.0.0.1:8080".parse().unwrap()),
+            public_url: public_url.map(String::from),
+            ..Config::default()
+        };
+        Arc::new(RwLock::new(config))
+    }
+
+    pub(crate) async fn create_series(pool: &PgPool) -> i32 {
+        let team_one: i32 = sqlx::query_scalar(
+            "insert into teams (role, name, captain) values (1, 'one', 1) returning id",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let team_two: i32 = sqlx::query_scalar(
+            "insert into teams (role, name, captain) values (2, 'two', 2) returning id",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        sqlx::query_scalar(
+            "insert into match_series (team_one, team_two, series_type, created_at)
+                 values ($1, $2, 'bo1', now()) returning id",
+        )
+        .bind(team_one)
+        .bind(team_two)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(ConnectTarget::Game.path("abc"), "/connect/abc");
+        assert_eq!(ConnectTarget::Gotv.path("abc"), "/connect/abc/gotv");
+    }
+
+    #[sqlx::test]
+    async fn links_use_a_random_token_per_series(pool: PgPool) {
+        let series = create_series(&pool).await;
+        let other = create_series(&pool).await;
+        let links = RedirectLinks::new(shared_config(true, Some("https://bot.gg/")), pool);
+        let game = links
+            .link(series, ConnectTarget::Game, "1.2.3.4:27015")
+            .await
+            .unwrap()
+            .unwrap();
+        let gotv = links
+            .link(series, ConnectTarget::Gotv, "1.2.3.4:27020")
+            .await
+            .unwrap()
+            .unwrap();
+        let other = links
+            .link(other, ConnectTarget::Game, "1.2.3.4:27015")
+            .await
+            .unwrap()
+            .unwrap();
+        let token = game.strip_prefix("https://bot.gg/connect/").unwrap();
+        assert_eq!(token.len(), 32);
+        assert_eq!(gotv, format!("https://bot.gg/connect/{}/gotv", token));
+        assert_ne!(other, game);
+    }
+
+    #[sqlx::test]
+    async fn no_links_without_api_or_public_url(pool: PgPool) {
+        let series = create_series(&pool).await;
+        for config in [
+            shared_config(false, Some("https://bot.gg")),
+            shared_config(true, None),
+        ] {
+            let links = RedirectLinks::new(config, pool.clone());
+            let link = links
+                .link(series, ConnectTarget::Game, "1.2.3.4:27015")
+                .await
+                .unwrap();
+            assert_eq!(link, None);
+        }
+    }
+}
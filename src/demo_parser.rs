@@ -0,0 +1,440 @@
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Time a parser command gets per demo before it is killed
+const PARSE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Side of a player in a round
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Ct,
+    T,
+}
+
+impl Side {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Side::Ct => "ct",
+            Side::T => "t",
+        }
+    }
+}
+
+/// Round of a demo with the SteamID64s playing on either side
+#[derive(Debug, Clone, Deserialize)]
+pub struct ParsedRound {
+    pub number: i32,
+    pub winner: Side,
+    /// i.e. `t_eliminated`, `bomb_defused` or `target_saved`
+    pub reason: String,
+    pub ct_players: Vec<u64>,
+    pub t_players: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ParsedKill {
+    pub round: i32,
+    pub tick: i32,
+    /// Not set for deaths without a killer
+    pub attacker: Option<u64>,
+    pub victim: u64,
+    pub weapon: String,
+    #[serde(default)]
+    pub headshot: bool,
+}
+
+/// Rounds & kills of a demo, the JSON a parser command prints
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ParsedDemo {
+    pub rounds: Vec<ParsedRound>,
+    pub kills: Vec<ParsedKill>,
+}
+
+impl ParsedDemo {
+    /// Rounds by number. A round that was played again after a backup was restored is in the
+    /// demo twice, the last one counts
+    pub fn unique_rounds(&self) -> Vec<&ParsedRound> {
+        let rounds: BTreeMap<i32, &ParsedRound> =
+            self.rounds.iter().map(|r| (r.number, r)).collect();
+        rounds.into_values().collect()
+    }
+}
+
+/// Reads `.dem` files, a trait so the demo job can run against canned results
+#[async_trait]
+pub trait DemoParser: Send + Sync {
+    async fn parse(&self, demo: &Path) -> Result<ParsedDemo>;
+}
+
+/// Runs an external parser, i.e. `scripts/parse_demo.py`, with the demo path as last argument
+/// and reads the `ParsedDemo` JSON it prints
+pub struct CommandParser {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl CommandParser {
+    /// `None` for an empty command
+    pub fn new(command: &str) -> Option<CommandParser> {
+        let mut parts = command.split_whitespace().map(String::from);
+        Some(CommandParser {
+            program: parts.next()?,
+            args: parts.collect(),
+            timeout: PARSE_TIMEOUT,
+        })
+    }
+}
+
+#[async_trait]
+impl DemoParser for CommandParser {
+    async fn parse(&self, demo: &Path) -> Result<ParsedDemo> {
+        // the child is killed when the timed out future is dropped
+        let output = Command::new(&self.program)
+            .args(&self.args)
+            .arg(demo)
+            .kill_on_drop(true)
+            .output();
+        let output = tokio::time::timeout(self.timeout, output)
+            .await
+            .map_err(|_| {
+                anyhow!(
+                    "`{}` did not finish within {}s",
+                    self.program,
+                    self.timeout.as_secs()
+                )
+            })??;
+        if !output.status.success() {
+            bail!(
+                "`{}` exited with {}: {}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(serde_json::from_slice(&output.stdout)?)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlayerStats {
+    pub kills: i32,
+    pub deaths: i32,
+    pub headshots: i32,
+    pub triples: i32,
+    pub quads: i32,
+    pub aces: i32,
+    pub clutches: i32,
+}
+
+/// Round won by the last player alive of the winning side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clutch {
+    pub round: i32,
+    pub steamid: u64,
+    /// Opponents alive when the player became the last one of their side
+    pub opponents: i32,
+}
+
+/// Round in which a player killed the whole enemy side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ace {
+    pub round: i32,
+    pub steamid: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DemoAnalysis {
+    pub players: BTreeMap<u64, PlayerStats>,
+    pub clutches: Vec<Clutch>,
+    pub aces: Vec<Ace>,
+}
+
+/// Per player stats, clutches & aces of a parsed demo. Team kills do not count as kills
+pub fn analyse(demo: &ParsedDemo) -> DemoAnalysis {
+    let mut analysis = DemoAnalysis::default();
+    for round in demo.unique_rounds() {
+        let sides: HashMap<u64, Side> = round
+            .ct_players
+            .iter()
+            .map(|p| (*p, Side::Ct))
+            .chain(round.t_players.iter().map(|p| (*p, Side::T)))
+            .collect();
+        let mut alive: HashMap<Side, HashSet<u64>> = HashMap::from([
+            (Side::Ct, round.ct_players.iter().copied().collect()),
+            (Side::T, round.t_players.iter().copied().collect()),
+        ]);
+        for player in sides.keys() {
+            analysis.players.entry(*player).or_default();
+        }
+        let mut kills = demo
+            .kills
+            .iter()
+            .filter(|k| k.round == round.number)
+            .collect::<Vec<_>>();
+        kills.sort_by_key(|k| k.tick);
+
+        let mut round_kills: HashMap<u64, i32> = HashMap::new();
+        let mut last_alive: Option<(u64, Side, i32)> = None;
+        for kill in kills {
+            analysis.players.entry(kill.victim).or_default().deaths += 1;
+            let victim_side = sides.get(&kill.victim).copied();
+            if let Some(attacker) = kill.attacker {
+                let attacker_side = sides.get(&attacker).copied();
+                if attacker != kill.victim
+                    && (attacker_side != victim_side || victim_side.is_none())
+                {
+                    let stats = analysis.players.entry(attacker).or_default();
+                    stats.kills += 1;
+                    if kill.headshot {
+                        stats.headshots += 1;
+                    }
+                    *round_kills.entry(attacker).or_default() += 1;
+                }
+            }
+            let Some(victim_side) = victim_side else {
+                continue;
+            };
+            if let Some(players) = alive.get_mut(&victim_side) {
+                players.remove(&kill.victim);
+            }
+            if last_alive.is_some() {
+                continue;
+            }
+            for side in [Side::Ct, Side::T] {
+                let other = if side == Side::Ct { Side::T } else { Side::Ct };
+                let (players, opponents) = (&alive[&side], alive[&other].len());
+                if players.len() == 1 && opponents > 0 {
+                    let player = *players.iter().next().unwrap();
+                    last_alive = Some((player, side, opponents as i32));
+                }
+            }
+        }
+
+        // a player that died after the bomb was planted did not win the round alone
+        if let Some((steamid, side, opponents)) = last_alive {
+            if side == round.winner && alive[&side].contains(&steamid) {
+                analysis.players.entry(steamid).or_default().clutches += 1;
+                analysis.clutches.push(Clutch {
+                    round: round.number,
+                    steamid,
+                    opponents,
+                });
+            }
+        }
+        for (steamid, count) in round_kills {
+            let stats = analysis.players.entry(steamid).or_default();
+            match count {
+                3 => stats.triples += 1,
+                4 => stats.quads += 1,
+                n if n >= 5 => {
+                    stats.aces += 1;
+                    analysis.aces.push(Ace {
+                        round: round.number,
+                        steamid,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+    analysis.aces.sort_by_key(|a| a.round);
+    analysis
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    const CT: [u64; 5] = [1, 2, 3, 4, 5];
+    const T: [u64; 5] = [6, 7, 8, 9, 10];
+
+    /// Parser answering every demo with the same result
+    pub(crate) struct CannedParser(pub(crate) ParsedDemo);
+
+    #[async_trait]
+    impl DemoParser for CannedParser {
+        async fn parse(&self, _demo: &Path) -> Result<ParsedDemo> {
+            Ok(self.0.clone())
+        }
+    }
+
+    pub(crate) fn round(number: i32, winner: Side, reason: &str) -> ParsedRound {
+        ParsedRound {
+            number,
+            winner,
+            reason: reason.to_string(),
+            ct_players: CT.to_vec(),
+            t_players: T.to_vec(),
+        }
+    }
+
+    pub(crate) fn kill(round: i32, tick: i32, attacker: u64, victim: u64) -> ParsedKill {
+        ParsedKill {
+            round,
+            tick,
+            attacker: Some(attacker),
+            victim,
+            weapon: String::from("ak47"),
+            headshot: false,
+        }
+    }
+
+    #[test]
+    fn counts_kills_deaths_and_multi_kills() {
+        let mut headshot = kill(1, 10, 1, 6);
+        headshot.headshot = true;
+        let demo = ParsedDemo {
+            rounds: vec![round(1, Side::Ct, "t_eliminated")],
+            kills: vec![
+                headshot,
+                kill(1, 20, 1, 7),
+                kill(1, 30, 1, 8),
+                kill(1, 40, 2, 9),
+                kill(1, 50, 10, 3),
+                // team kill
+                kill(1, 60, 2, 4),
+            ],
+        };
+        let analysis = analyse(&demo);
+        let first = analysis.players[&1];
+        assert_eq!((first.kills, first.headshots, first.triples), (3, 1, 1));
+        assert_eq!(analysis.players[&2].kills, 1);
+        assert_eq!(analysis.players[&4].deaths, 1);
+        assert_eq!(analysis.players[&10].kills, 1);
+        assert_eq!(analysis.players[&5], PlayerStats::default());
+        assert!(analysis.aces.is_empty());
+    }
+
+    #[test]
+    fn finds_aces() {
+        let demo = ParsedDemo {
+            rounds: vec![
+                round(1, Side::T, "ct_eliminated"),
+                round(2, Side::Ct, "t_eliminated"),
+            ],
+            kills: (0..5)
+                .map(|i| kill(2, i * 10, 5, T[i as usize]))
+                .chain([kill(1, 10, 6, 1)])
+                .collect(),
+        };
+        let analysis = analyse(&demo);
+        assert_eq!(
+            analysis.aces,
+            vec![Ace {
+                round: 2,
+                steamid: 5
+            }]
+        );
+        assert_eq!(analysis.players[&5].aces, 1);
+    }
+
+    #[test]
+    fn finds_clutches_won_by_the_last_player_alive() {
+        let demo = ParsedDemo {
+            rounds: vec![round(1, Side::Ct, "t_eliminated")],
+            kills: vec![
+                kill(1, 10, 6, 1),
+                kill(1, 20, 6, 2),
+                kill(1, 30, 6, 3),
+                kill(1, 40, 7, 4),
+                kill(1, 50, 5, 6),
+                kill(1, 60, 5, 7),
+                kill(1, 70, 5, 8),
+                kill(1, 80, 5, 9),
+                kill(1, 90, 5, 10),
+            ],
+        };
+        let analysis = analyse(&demo);
+        assert_eq!(
+            analysis.clutches,
+            vec![Clutch {
+                round: 1,
+                steamid: 5,
+                opponents: 5
+            }]
+        );
+        assert_eq!(analysis.players[&5].clutches, 1);
+    }
+
+    #[test]
+    fn no_clutch_for_a_player_that_died() {
+        // the last T plants and dies, the bomb wins the round
+        let demo = ParsedDemo {
+            rounds: vec![round(1, Side::T, "target_bombed")],
+            kills: vec![
+                kill(1, 10, 1, 6),
+                kill(1, 20, 1, 7),
+                kill(1, 30, 1, 8),
+                kill(1, 40, 1, 9),
+                kill(1, 50, 1, 10),
+            ],
+        };
+        let analysis = analyse(&demo);
+        assert!(analysis.clutches.is_empty());
+        assert_eq!(analysis.players[&10].clutches, 0);
+    }
+
+    #[test]
+    fn no_clutch_for_the_losing_side() {
+        let demo = ParsedDemo {
+            rounds: vec![round(1, Side::T, "ct_eliminated")],
+            kills: (0..5).map(|i| kill(1, i * 10, 6, CT[i as usize])).collect(),
+        };
+        assert!(analyse(&demo).clutches.is_empty());
+    }
+
+    #[test]
+    fn replayed_rounds_count_once() {
+        let demo = ParsedDemo {
+            rounds: vec![
+                round(1, Side::T, "ct_eliminated"),
+                round(1, Side::Ct, "t_eliminated"),
+            ],
+            kills: vec![kill(1, 10, 1, 6)],
+        };
+        let rounds = demo.unique_rounds();
+        assert_eq!(rounds.len(), 1);
+        assert_eq!(rounds[0].winner, Side::Ct);
+        let analysis = analyse(&demo);
+        assert_eq!(analysis.players[&1].kills, 1);
+        assert_eq!(analysis.players[&6].deaths, 1);
+    }
+
+    #[tokio::test]
+    async fn reads_the_json_of_the_command() {
+        let demo = std::env::temp_dir().join("matchbot-parser-test.json");
+        tokio::fs::write(
+            &demo,
+            r#"{"rounds": [{"number": 1, "winner": "ct", "reason": "t_eliminated",
+                            "ct_players": [1], "t_players": [6]}],
+                "kills": [{"round": 1, "tick": 10, "attacker": 1, "victim": 6, "weapon": "ak47"}]}"#,
+        )
+        .await
+        .unwrap();
+        // `cat <demo>` prints the file as the parser output
+        let parsed = CommandParser::new("cat").unwrap().parse(&demo).await;
+        tokio::fs::remove_file(&demo).await.unwrap();
+        let parsed = parsed.unwrap();
+        assert_eq!(parsed.rounds[0].winner, Side::Ct);
+        assert_eq!(parsed.kills[0].attacker, Some(1));
+        assert!(!parsed.kills[0].headshot);
+    }
+
+    #[tokio::test]
+    async fn kills_commands_that_take_too_long() {
+        let parser = CommandParser {
+            program: String::from("sleep"),
+            args: vec![],
+            timeout: Duration::from_millis(100),
+        };
+        let err = parser.parse(Path::new("10")).await.unwrap_err();
+        assert!(err.to_string().contains("did not finish"));
+    }
+}
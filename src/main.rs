@@ -1,21 +1,47 @@
+mod api;
 mod commands;
+mod config;
+mod connect;
+mod demo_parser;
+mod metrics;
+mod rcon;
+mod steam;
+mod storage;
 
 use crate::commands::admin::admin;
+use crate::commands::announcements::announcement_task;
+use crate::commands::config::{claim_unowned, config};
+use crate::commands::demos::demo_task;
+use crate::commands::gslt::gslt_task;
+use crate::commands::highlights::highlights_task;
+use crate::commands::maps::Map;
 use crate::commands::matches::matches;
-use crate::commands::setup::setup;
+use crate::commands::setup::{expire_retry_buttons, setup};
+use crate::commands::stats::stats;
 use crate::commands::steamid::steamid;
 use crate::commands::team::team;
 use crate::commands::team::teams;
+use crate::commands::tournament::{check_in_task, role_cleanup_task, tournament};
+use crate::config::{Config, SharedConfig};
+use crate::connect::{ConnectLinks, RedirectLinks};
+use crate::demo_parser::{CommandParser, DemoParser};
+use crate::steam::{GameServerAccounts, MissingApiKey, SteamClient};
+use crate::storage::{DemoStorage, S3Storage};
 use anyhow::Error;
-use dotenvy::{dotenv, var};
+use dotenvy::dotenv;
 use poise::{builtins::create_application_commands, Event, Framework, FrameworkOptions};
+use serenity::gateway::ConnectionStage;
 use serenity::model::gateway::GatewayIntents;
 use sqlx::{migrate::Migrator, PgPool};
+use std::sync::{Arc, RwLock};
 
 static MIGRATOR: Migrator = sqlx::migrate!();
 
 pub struct Data {
     pub pool: PgPool,
+    pub config: SharedConfig,
+    pub steam: Arc<dyn GameServerAccounts>,
+    pub connect_links: Arc<dyn ConnectLinks>,
 }
 
 type Context<'a> = poise::Context<'a, Data, Error>;
@@ -29,24 +55,123 @@ async fn main() {
         .parse_default_env()
         .init();
 
-    let pool = PgPool::connect(&var("DATABASE_URL").expect("missing DATABASE_URL"))
-        .await
-        .unwrap();
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(error) => {
+            log::error!("{:#}", error);
+            std::process::exit(1);
+        }
+    };
+    let pool = PgPool::connect(&config.database_url).await.unwrap();
     if let Err(error) = MIGRATOR.run(&pool).await {
         log::error!("Migration error: {}", error);
         std::process::exit(1);
     }
 
+    tokio::spawn(check_in_task(pool.clone()));
+    if let Some(address) = config.metrics_address {
+        tokio::spawn(metrics::serve(pool.clone(), address));
+    }
+    let token = config.discord_token.clone();
+    let steam: Arc<dyn GameServerAccounts> = match &config.steam_api_key {
+        Some(api_key) => {
+            let steam: Arc<dyn GameServerAccounts> = Arc::new(SteamClient::new(api_key.clone()));
+            tokio::spawn(gslt_task(pool.clone(), steam.clone()));
+            steam
+        }
+        None => {
+            log::warn!(
+                "`steam_api_key` (STEAM_API_KEY) is not set, Dathost servers can't be started"
+            );
+            Arc::new(MissingApiKey)
+        }
+    };
+    let storage = match S3Storage::new(&config.storage) {
+        Ok(storage) => storage,
+        Err(error) => {
+            log::error!("Invalid storage endpoint: {:#}", error);
+            std::process::exit(1);
+        }
+    };
+    let parser: Option<Arc<dyn DemoParser>> = config
+        .demo_parser
+        .as_deref()
+        .and_then(CommandParser::new)
+        .map(|parser| Arc::new(parser) as Arc<dyn DemoParser>);
+    let api_address = config.api_address;
+    let config: SharedConfig = Arc::new(RwLock::new(config));
+    let storage: Option<Arc<dyn DemoStorage>> =
+        storage.map(|storage| Arc::new(storage) as Arc<dyn DemoStorage>);
+    if let Some(storage) = storage.clone() {
+        tokio::spawn(demo_task(pool.clone(), config.clone(), storage));
+    }
+    if let Some(address) = api_address {
+        tokio::spawn(api::serve(pool.clone(), storage.clone(), address));
+    }
+    let connect_links: Arc<dyn ConnectLinks> =
+        Arc::new(RedirectLinks::new(config.clone(), pool.clone()));
+
     let framework = Framework::<_, Error>::builder()
         .options(FrameworkOptions {
-            commands: vec![admin(), team(), teams(), steamid(), matches(), setup()],
-            event_handler: move |context, event, framework, _data| {
+            commands: vec![
+                admin(),
+                team(),
+                teams(),
+                steamid(),
+                matches(),
+                setup(),
+                stats(),
+                tournament(),
+                config(),
+            ],
+            pre_command: |context| {
+                Box::pin(async move {
+                    metrics::command_started(context.id());
+                })
+            },
+            post_command: |context| {
+                Box::pin(async move {
+                    metrics::command_finished(
+                        context.id(),
+                        &context.command().qualified_name,
+                        "ok",
+                    );
+                })
+            },
+            on_error: |error| {
                 Box::pin(async move {
+                    // Every error kind with an invocation finishes it, so no start is left behind
+                    if let Some(ctx) = error.ctx() {
+                        metrics::command_finished(ctx.id(), &ctx.command().qualified_name, "error");
+                    }
+                    if let Err(err) = poise::builtins::on_error(error).await {
+                        log::error!("Error while handling error: {}", err);
+                    }
+                })
+            },
+            event_handler: move |context, event, framework, data| {
+                Box::pin(async move {
+                    if let Event::ShardStageUpdate { update } = event {
+                        metrics::GATEWAY_CONNECTED
+                            .set(i64::from(update.new == ConnectionStage::Connected));
+                    }
                     if let Event::Ready { data_about_bot } = event {
+                        metrics::GATEWAY_CONNECTED.set(1);
                         let commands_builder =
                             create_application_commands(&framework.options().commands);
                         let commands_count = commands_builder.0.len();
+                        // Data from before multi-guild support is only assigned when unambiguous
+                        if let [guild] = data_about_bot.guilds.as_slice() {
+                            claim_unowned(&data.pool, guild.id.0 as i64).await?;
+                        } else {
+                            log::warn!(
+                                "Bot is in several guilds, data without a guild is not claimed"
+                            );
+                        }
                         for guild in &data_about_bot.guilds {
+                            if Map::add_defaults(&data.pool, guild.id.0 as i64).await? > 0 {
+                                log::info!("Added default map pool for guild {}", guild.id);
+                            }
                             let guild = guild.id.to_partial_guild(&context).await?;
 
                             let commands_builder = commands_builder.clone();
@@ -70,9 +195,35 @@ async fn main() {
             },
             ..Default::default()
         })
-        .token(var("DISCORD_TOKEN").expect("missing DISCORD_TOKEN"))
+        .token(token)
         .intents(GatewayIntents::empty())
-        .setup(move |_context, _ready, _framework| Box::pin(async move { Ok(Data { pool }) }));
+        .setup(move |context, _ready, _framework| {
+            Box::pin(async move {
+                tokio::spawn(announcement_task(
+                    context.http.clone(),
+                    pool.clone(),
+                    config.clone(),
+                ));
+                tokio::spawn(role_cleanup_task(context.http.clone(), pool.clone()));
+                if let Err(err) = expire_retry_buttons(&context.http, &pool).await {
+                    log::error!("{:#?}", err);
+                }
+                if let (Some(storage), Some(parser)) = (storage, parser) {
+                    tokio::spawn(highlights_task(
+                        context.http.clone(),
+                        pool.clone(),
+                        storage,
+                        parser,
+                    ));
+                }
+                Ok(Data {
+                    pool,
+                    config,
+                    steam,
+                    connect_links,
+                })
+            })
+        });
 
     if let Err(error) = framework.run().await {
         log::error!("Error: {}", error);
@@ -0,0 +1,186 @@
+use crate::api::ApiResult;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge, HistogramVec,
+    IntCounterVec, IntGauge, TextEncoder,
+};
+use reqwest::RequestBuilder;
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub static COMMANDS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "matchbot_commands_total",
+        "Command invocations by command & outcome",
+        &["command", "outcome"]
+    )
+    .unwrap()
+});
+pub static COMMAND_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "matchbot_command_duration_seconds",
+        "Time to run a command",
+        &["command"]
+    )
+    .unwrap()
+});
+pub static SETUP_PHASE_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "matchbot_setup_phase_duration_seconds",
+        "Time teams spend in each `/setup` phase",
+        &["phase"],
+        vec![5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 3600.0]
+    )
+    .unwrap()
+});
+pub static EXTERNAL_REQUEST_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "matchbot_external_request_duration_seconds",
+        "Latency of requests to Dathost & Steam",
+        &["service"]
+    )
+    .unwrap()
+});
+pub static EXTERNAL_REQUEST_FAILURES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "matchbot_external_request_failures_total",
+        "Requests to Dathost & Steam that failed or returned an error status",
+        &["service"]
+    )
+    .unwrap()
+});
+pub static ACTIVE_SETUPS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!("matchbot_active_setups", "`/setup` threads in progress").unwrap()
+});
+pub static LIVE_SERVERS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "matchbot_live_servers",
+        "Game servers of series in progress"
+    )
+    .unwrap()
+});
+pub static GATEWAY_CONNECTED: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "matchbot_gateway_connected",
+        "1 while the Discord gateway is connected"
+    )
+    .unwrap()
+});
+
+/// Start of commands in progress by invocation id
+static COMMAND_STARTS: Lazy<Mutex<HashMap<u64, Instant>>> = Lazy::new(Default::default);
+/// Starts older than this belong to invocations that never finished, e.g. a panic
+const COMMAND_START_TTL: Duration = Duration::from_secs(3600);
+
+pub fn command_started(id: u64) {
+    let mut starts = COMMAND_STARTS.lock().unwrap();
+    starts.retain(|_, started| started.elapsed() < COMMAND_START_TTL);
+    starts.insert(id, Instant::now());
+}
+
+pub fn command_finished(id: u64, command: &str, outcome: &str) {
+    COMMANDS.with_label_values(&[command, outcome]).inc();
+    if let Some(started) = COMMAND_STARTS.lock().unwrap().remove(&id) {
+        COMMAND_SECONDS
+            .with_label_values(&[command])
+            .observe(started.elapsed().as_secs_f64());
+    }
+}
+
+/// Increments `gauge` until dropped
+pub struct GaugeGuard(&'static IntGauge);
+
+impl GaugeGuard {
+    pub fn inc(gauge: &'static IntGauge) -> GaugeGuard {
+        gauge.inc();
+        GaugeGuard(gauge)
+    }
+}
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// Sends `request`, recording its latency & whether it failed for `service`
+pub async fn track_request(
+    service: &str,
+    request: RequestBuilder,
+) -> reqwest::Result<reqwest::Response> {
+    let started = Instant::now();
+    let response = request.send().await;
+    EXTERNAL_REQUEST_SECONDS
+        .with_label_values(&[service])
+        .observe(started.elapsed().as_secs_f64());
+    if !matches!(&response, Ok(response) if response.status().is_success()) {
+        EXTERNAL_REQUEST_FAILURES
+            .with_label_values(&[service])
+            .inc();
+    }
+    response
+}
+
+/// How long `/healthz` waits for the database before reporting it unreachable
+const HEALTHZ_DATABASE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// `200 OK` when the database is reachable and the gateway connected, `503` otherwise
+async fn healthz(State(pool): State<PgPool>) -> Response {
+    let database = matches!(
+        tokio::time::timeout(
+            HEALTHZ_DATABASE_TIMEOUT,
+            sqlx::query("select 1").execute(&pool)
+        )
+        .await,
+        Ok(Ok(_))
+    );
+    let gateway = GATEWAY_CONNECTED.get() == 1;
+    let status = if database && gateway {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(json!({ "database": database, "gateway": gateway })),
+    )
+        .into_response()
+}
+
+async fn metrics(State(pool): State<PgPool>) -> ApiResult {
+    let live_servers = sqlx::query_scalar!(
+        r#"select count(*) as "count!"
+             from servers s
+                join match_series ms on ms.id = s.match_series
+             where ms.completed_at is null"#
+    )
+    .fetch_one(&pool)
+    .await?;
+    LIVE_SERVERS.set(live_servers);
+    let body = TextEncoder::new().encode_to_string(&prometheus::gather())?;
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response())
+}
+
+/// Serves `/healthz` & `/metrics`, started when `METRICS_ADDRESS` is set
+pub async fn serve(pool: PgPool, address: SocketAddr) {
+    let router = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/metrics", get(metrics))
+        .with_state(pool);
+    log::info!("Serving metrics on {}", address);
+    if let Err(err) = axum::Server::bind(&address)
+        .serve(router.into_make_service())
+        .await
+    {
+        log::error!("Metrics server error: {}", err);
+    }
+}
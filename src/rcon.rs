@@ -0,0 +1,183 @@
+use anyhow::{bail, Result};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+const SERVERDATA_AUTH: i32 = 3;
+const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+const SERVERDATA_EXECCOMMAND: i32 = 2;
+const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+/// Id, type & the two terminating null bytes
+const MIN_PACKET_SIZE: i32 = 10;
+const MAX_PACKET_SIZE: i32 = 4096;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Packet {
+    id: i32,
+    kind: i32,
+    body: String,
+}
+
+/// Client for the Source RCON protocol, generic over the stream so it can run against any
+/// `AsyncRead + AsyncWrite`, i.e. a `tokio::io::duplex` pipe to a fake server
+pub struct Rcon<S> {
+    stream: S,
+    next_id: i32,
+}
+
+impl Rcon<TcpStream> {
+    /// Connects to `address` and authenticates with `password`
+    pub async fn connect(address: &str, password: &str) -> Result<Rcon<TcpStream>> {
+        let stream = timeout(TIMEOUT, TcpStream::connect(address)).await??;
+        let mut rcon = Rcon::new(stream);
+        rcon.authenticate(password).await?;
+        Ok(rcon)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Rcon<S> {
+    pub fn new(stream: S) -> Rcon<S> {
+        Rcon { stream, next_id: 1 }
+    }
+
+    pub async fn authenticate(&mut self, password: &str) -> Result<()> {
+        let id = self.send(SERVERDATA_AUTH, password).await?;
+        loop {
+            // Servers send an empty SERVERDATA_RESPONSE_VALUE ahead of the auth response
+            let packet = self.receive().await?;
+            if packet.kind != SERVERDATA_AUTH_RESPONSE {
+                continue;
+            }
+            if packet.id == -1 {
+                bail!("RCON authentication failed");
+            }
+            if packet.id == id {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Runs `command` on the server and returns its console output
+    pub async fn exec(&mut self, command: &str) -> Result<String> {
+        let id = self.send(SERVERDATA_EXECCOMMAND, command).await?;
+        loop {
+            let packet = self.receive().await?;
+            if packet.id == id && packet.kind == SERVERDATA_RESPONSE_VALUE {
+                return Ok(packet.body);
+            }
+        }
+    }
+
+    async fn send(&mut self, kind: i32, body: &str) -> Result<i32> {
+        let size = MIN_PACKET_SIZE + body.len() as i32;
+        if size > MAX_PACKET_SIZE {
+            bail!("RCON command is too long ({} bytes)", body.len());
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut packet = Vec::with_capacity(size as usize + 4);
+        packet.extend_from_slice(&size.to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(body.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        timeout(TIMEOUT, self.stream.write_all(&packet)).await??;
+        Ok(id)
+    }
+
+    async fn receive(&mut self) -> Result<Packet> {
+        let size = timeout(TIMEOUT, self.stream.read_i32_le()).await??;
+        if !(MIN_PACKET_SIZE..=MAX_PACKET_SIZE).contains(&size) {
+            bail!("invalid RCON packet size {}", size);
+        }
+        let mut data = vec![0; size as usize];
+        timeout(TIMEOUT, self.stream.read_exact(&mut data)).await??;
+        let id = i32::from_le_bytes(data[0..4].try_into()?);
+        let kind = i32::from_le_bytes(data[4..8].try_into()?);
+        let body = String::from_utf8_lossy(&data[8..data.len() - 2]).into_owned();
+        Ok(Packet { id, kind, body })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{duplex, DuplexStream};
+
+    const PASSWORD: &str = "secret";
+
+    async fn read_packet(stream: &mut DuplexStream) -> Option<Packet> {
+        let size = stream.read_i32_le().await.ok()?;
+        let mut data = vec![0; size as usize];
+        stream.read_exact(&mut data).await.ok()?;
+        Some(Packet {
+            id: i32::from_le_bytes(data[0..4].try_into().unwrap()),
+            kind: i32::from_le_bytes(data[4..8].try_into().unwrap()),
+            body: String::from_utf8(data[8..data.len() - 2].to_vec()).unwrap(),
+        })
+    }
+
+    async fn write_packet(stream: &mut DuplexStream, id: i32, kind: i32, body: &str) {
+        let mut packet = Vec::new();
+        packet.extend_from_slice(&(MIN_PACKET_SIZE + body.len() as i32).to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(body.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        stream.write_all(&packet).await.unwrap();
+    }
+
+    /// Answers like a Source server: an empty response value ahead of the auth response, and
+    /// a console line of another request ahead of every command reply
+    async fn fake_server(mut stream: DuplexStream) {
+        let auth = read_packet(&mut stream).await.unwrap();
+        assert_eq!(auth.kind, SERVERDATA_AUTH);
+        write_packet(&mut stream, auth.id, SERVERDATA_RESPONSE_VALUE, "").await;
+        if auth.body != PASSWORD {
+            write_packet(&mut stream, -1, SERVERDATA_AUTH_RESPONSE, "").await;
+            return;
+        }
+        write_packet(&mut stream, auth.id, SERVERDATA_AUTH_RESPONSE, "").await;
+        while let Some(command) = read_packet(&mut stream).await {
+            assert_eq!(command.kind, SERVERDATA_EXECCOMMAND);
+            write_packet(&mut stream, 0, SERVERDATA_RESPONSE_VALUE, "L server log").await;
+            let reply = format!("ran {}", command.body);
+            write_packet(&mut stream, command.id, SERVERDATA_RESPONSE_VALUE, &reply).await;
+        }
+    }
+
+    fn connect() -> Rcon<DuplexStream> {
+        let (client, server) = duplex(MAX_PACKET_SIZE as usize);
+        tokio::spawn(fake_server(server));
+        Rcon::new(client)
+    }
+
+    #[tokio::test]
+    async fn runs_commands_after_authenticating() {
+        let mut rcon = connect();
+        rcon.authenticate(PASSWORD).await.unwrap();
+        assert_eq!(rcon.exec("status").await.unwrap(), "ran status");
+        assert_eq!(
+            rcon.exec("matchzy_loadmatch_url \"https://example.com\"")
+                .await
+                .unwrap(),
+            "ran matchzy_loadmatch_url \"https://example.com\""
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_password() {
+        let mut rcon = connect();
+        let err = rcon.authenticate("wrong").await.unwrap_err();
+        assert_eq!(err.to_string(), "RCON authentication failed");
+    }
+
+    #[tokio::test]
+    async fn rejects_commands_longer_than_a_packet() {
+        let mut rcon = connect();
+        rcon.authenticate(PASSWORD).await.unwrap();
+        let command = "x".repeat(MAX_PACKET_SIZE as usize);
+        assert!(rcon.exec(&command).await.is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::metrics::track_request;
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use reqwest::{Client, Method};
+use serde::{Deserialize, Serialize};
+
+const GAME_SERVERS_API: &str = "https://api.steampowered.com/IGameServersService";
+
+/// Game server account (GSLT) of the bot's Steam account
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct GameServerAccount {
+    pub steamid: String,
+    pub appid: u32,
+    pub login_token: String,
+    pub memo: String,
+    pub is_deleted: bool,
+    pub is_expired: bool,
+}
+
+/// `IGameServersService` calls, a trait so the GSLT pool can run against a fake account
+#[async_trait]
+pub trait GameServerAccounts: Send + Sync {
+    async fn create_account(&self, app_id: u32, memo: &str) -> Result<GameServerAccount>;
+    async fn list_accounts(&self) -> Result<Vec<GameServerAccount>>;
+    /// Issues a new token for the account, the old one stops working
+    async fn reset_login_token(&self, steamid: &str) -> Result<String>;
+    async fn delete_account(&self, steamid: &str) -> Result<()>;
+}
+
+#[derive(Serialize)]
+struct CreateAccountRequest<'a> {
+    appid: u32,
+    memo: &'a str,
+}
+
+#[derive(Deserialize)]
+struct SteamResponse<T> {
+    response: T,
+}
+
+/// `servers` is required, a response without it must not read as an account without servers
+#[derive(Deserialize)]
+struct AccountList {
+    servers: Vec<GameServerAccount>,
+}
+
+#[derive(Deserialize)]
+struct LoginToken {
+    login_token: String,
+}
+
+/// Steam Web API client using the bot's `steam_api_key`
+pub struct SteamClient {
+    client: Client,
+    api_key: String,
+}
+
+impl SteamClient {
+    pub fn new(api_key: String) -> SteamClient {
+        SteamClient {
+            client: Client::new(),
+            api_key,
+        }
+    }
+    async fn call<T: for<'de> Deserialize<'de>>(
+        &self,
+        http_method: Method,
+        method: &str,
+        input: serde_json::Value,
+    ) -> Result<T> {
+        let resp = track_request(
+            "steam",
+            self.client
+                .request(http_method, format!("{}/{}/v1/", GAME_SERVERS_API, method))
+                .query(&[
+                    ("key", self.api_key.as_str()),
+                    ("input_json", input.to_string().as_str()),
+                ])
+                .header("Content-Length", 0),
+        )
+        .await?;
+        if !resp.status().is_success() {
+            bail!("Steam {} failed with status {}", method, resp.status());
+        }
+        Ok(resp.json::<SteamResponse<T>>().await?.response)
+    }
+}
+
+/// Stands in for the Steam client when no `steam_api_key` is set, every call fails
+pub struct MissingApiKey;
+
+#[async_trait]
+impl GameServerAccounts for MissingApiKey {
+    async fn create_account(&self, _app_id: u32, _memo: &str) -> Result<GameServerAccount> {
+        bail!("`steam_api_key` (STEAM_API_KEY) is not set, Dathost servers need it for a GSLT")
+    }
+    async fn list_accounts(&self) -> Result<Vec<GameServerAccount>> {
+        bail!("`steam_api_key` (STEAM_API_KEY) is not set")
+    }
+    async fn reset_login_token(&self, _steamid: &str) -> Result<String> {
+        bail!("`steam_api_key` (STEAM_API_KEY) is not set")
+    }
+    async fn delete_account(&self, _steamid: &str) -> Result<()> {
+        bail!("`steam_api_key` (STEAM_API_KEY) is not set")
+    }
+}
+
+#[async_trait]
+impl GameServerAccounts for SteamClient {
+    async fn create_account(&self, app_id: u32, memo: &str) -> Result<GameServerAccount> {
+        let input = serde_json::to_value(CreateAccountRequest {
+            appid: app_id,
+            memo,
+        })?;
+        let account: GameServerAccount = self.call(Method::POST, "CreateAccount", input).await?;
+        if account.login_token.is_empty() {
+            bail!("Steam did not return a login token, the account may have hit its GSLT limit");
+        }
+        Ok(GameServerAccount {
+            appid: app_id,
+            memo: memo.to_string(),
+            ..account
+        })
+    }
+    async fn list_accounts(&self) -> Result<Vec<GameServerAccount>> {
+        let list: AccountList = self
+            .call(Method::GET, "GetAccountList", serde_json::json!({}))
+            .await?;
+        if list.servers.iter().any(|a| a.steamid.is_empty()) {
+            bail!("Steam listed a game server account without a steamid");
+        }
+        Ok(list.servers)
+    }
+    async fn reset_login_token(&self, steamid: &str) -> Result<String> {
+        let token: LoginToken = self
+            .call(
+                Method::POST,
+                "ResetLoginToken",
+                serde_json::json!({ "steamid": steamid }),
+            )
+            .await?;
+        if token.login_token.is_empty() {
+            bail!("Steam did not return a new login token for {}", steamid);
+        }
+        Ok(token.login_token)
+    }
+    async fn delete_account(&self, steamid: &str) -> Result<()> {
+        self.call::<serde_json::Value>(
+            Method::POST,
+            "DeleteAccount",
+            serde_json::json!({ "steamid": steamid }),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn account_list_requires_servers() {
+        assert!(serde_json::from_str::<SteamResponse<AccountList>>(r#"{"response":{}}"#).is_err());
+        let list: SteamResponse<AccountList> = serde_json::from_str(
+            r#"{"response":{"servers":[{"steamid":"85568392920040000","appid":730,
+                "login_token":"ABC","memo":"matchbot","is_deleted":false,"is_expired":true,
+                "rt_last_logon":0}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(list.response.servers.len(), 1);
+        assert!(list.response.servers[0].is_expired);
+    }
+}
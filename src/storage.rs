@@ -0,0 +1,292 @@
+use crate::config::StorageSettings;
+use crate::metrics::track_request;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::Stream;
+use hmac::{Hmac, Mac};
+use reqwest::{Body, Client, Method, Url};
+use sha2::{Digest, Sha256};
+use sqlx::types::time::OffsetDateTime;
+use std::pin::Pin;
+use std::time::Duration;
+use urlencoding::encode;
+
+const DEFAULT_REGION: &str = "us-east-1";
+/// Payload hash of uploads, streamed bodies can't be hashed before they are sent
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+/// Limits of a demo download or upload, demos are a few hundred MB
+pub const DEMO_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEMO_TRANSFER_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Demo passed through in chunks, so it is never held in memory as a whole
+pub type DemoStream = Pin<Box<dyn Stream<Item = Result<Bytes>> + Send + Sync>>;
+
+/// Object storage for demos, a trait so uploads can run against a local stand-in
+#[async_trait]
+pub trait DemoStorage: Send + Sync {
+    /// Uploads the `length` bytes of `body` as `key`
+    async fn put(&self, key: &str, length: u64, body: DemoStream) -> Result<()>;
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+}
+
+/// Client for demo transfers, which must not hang forever on a stalled server
+pub fn demo_client() -> Result<Client> {
+    Ok(Client::builder()
+        .connect_timeout(DEMO_CONNECT_TIMEOUT)
+        .timeout(DEMO_TRANSFER_TIMEOUT)
+        .build()?)
+}
+
+/// S3-compatible storage signed with AWS Signature Version 4. Objects are addressed
+/// path-style (`<endpoint>/<bucket>/<key>`), which S3 and MinIO-like servers both accept
+pub struct S3Storage {
+    client: Client,
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3Storage {
+    /// `None` when no storage is configured
+    pub fn new(settings: &StorageSettings) -> Result<Option<S3Storage>> {
+        let (Some(endpoint), Some(bucket), Some(access_key), Some(secret_key)) = (
+            &settings.endpoint,
+            &settings.bucket,
+            &settings.access_key,
+            &settings.secret_key,
+        ) else {
+            return Ok(None);
+        };
+        Ok(Some(S3Storage {
+            client: demo_client()?,
+            endpoint: Url::parse(endpoint)?,
+            bucket: bucket.clone(),
+            region: settings
+                .region
+                .clone()
+                .unwrap_or_else(|| DEFAULT_REGION.to_string()),
+            access_key: access_key.clone(),
+            secret_key: secret_key.clone(),
+        }))
+    }
+
+    /// Sends a signed request for `key`, failing on error statuses
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        payload_hash: &str,
+        request: impl FnOnce(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let path = format!(
+            "/{}/{}",
+            encode(&self.bucket),
+            key.split('/').map(encode).collect::<Vec<_>>().join("/")
+        );
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(anyhow!("storage endpoint `{}` has no host", url)),
+        };
+        let signature = self.sign(
+            &method,
+            &path,
+            &host,
+            payload_hash,
+            OffsetDateTime::now_utc(),
+        );
+        let builder = self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", signature.timestamp)
+            .header("authorization", signature.authorization);
+        Ok(track_request("storage", request(builder))
+            .await?
+            .error_for_status()?)
+    }
+
+    /// AWS Signature Version 4 of a request signing the host, payload hash & date headers
+    fn sign(
+        &self,
+        method: &Method,
+        path: &str,
+        host: &str,
+        payload_hash: &str,
+        now: OffsetDateTime,
+    ) -> Signature {
+        let date = format!("{:04}{:02}{:02}", now.year(), now.month() as u8, now.day());
+        let timestamp = format!(
+            "{}T{:02}{:02}{:02}Z",
+            date,
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, host, payload_hash, timestamp, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            timestamp,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+        let key = signing_key(&self.secret_key, &date, &self.region, "s3");
+        let signature = hex::encode(hmac_sha256(&key, &string_to_sign));
+        Signature {
+            authorization: format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                self.access_key, scope, signed_headers, signature
+            ),
+            timestamp,
+        }
+    }
+}
+
+/// Headers carrying the signature of a request
+struct Signature {
+    authorization: String,
+    /// `x-amz-date`
+    timestamp: String,
+}
+
+#[async_trait]
+impl DemoStorage for S3Storage {
+    async fn put(&self, key: &str, length: u64, body: DemoStream) -> Result<()> {
+        // a streamed body is sent chunked unless its length is given, which S3 does not accept
+        self.send(Method::PUT, key, UNSIGNED_PAYLOAD, |request| {
+            request
+                .header(reqwest::header::CONTENT_LENGTH, length)
+                .body(Body::wrap_stream(body))
+        })
+        .await?;
+        Ok(())
+    }
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let empty_hash = hex::encode(Sha256::digest(b""));
+        let response = self.send(Method::GET, key, &empty_hash, |r| r).await?;
+        Ok(response.bytes().await?.into())
+    }
+}
+
+/// Key of the day, region & service the signature is valid for
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let mut key = format!("AWS4{}", secret_key).into_bytes();
+    for part in [date, region, service, "aws4_request"] {
+        key = hmac_sha256(&key, part);
+    }
+    key
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use anyhow::bail;
+    use futures::TryStreamExt;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Storage kept in memory
+    #[derive(Default)]
+    pub(crate) struct MemoryStorage {
+        pub(crate) objects: Mutex<HashMap<String, Vec<u8>>>,
+    }
+
+    #[async_trait]
+    impl DemoStorage for MemoryStorage {
+        async fn put(&self, key: &str, length: u64, body: DemoStream) -> Result<()> {
+            let chunks: Vec<Bytes> = body.try_collect().await?;
+            let object = chunks.concat();
+            if object.len() as u64 != length {
+                bail!("expected {} bytes, got {}", length, object.len());
+            }
+            self.objects.lock().unwrap().insert(key.to_string(), object);
+            Ok(())
+        }
+        async fn get(&self, key: &str) -> Result<Vec<u8>> {
+            self.objects
+                .lock()
+                .unwrap()
+                .get(key)
+                .cloned()
+                .ok_or_else(|| anyhow!("no object `{}`", key))
+        }
+    }
+
+    /// Stream of `chunks` as a body would arrive
+    pub(crate) fn demo_stream(chunks: &[&'static [u8]]) -> DemoStream {
+        let chunks: Vec<Result<Bytes>> = chunks.iter().map(|c| Ok(Bytes::from(*c))).collect();
+        Box::pin(futures::stream::iter(chunks))
+    }
+
+    #[test]
+    fn derives_the_signing_key() {
+        // example of the AWS Signature Version 4 documentation
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex::encode(key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn signs_requests() {
+        let storage = S3Storage {
+            client: Client::new(),
+            endpoint: Url::parse("https://s3.example.com:9000").unwrap(),
+            bucket: String::from("demos"),
+            region: String::from("eu-central-1"),
+            access_key: String::from("access"),
+            secret_key: String::from("secret"),
+        };
+        let now = OffsetDateTime::from_unix_timestamp(1688904000).unwrap();
+        let signature = storage.sign(
+            &Method::PUT,
+            "/demos/12/34.dem",
+            "s3.example.com:9000",
+            UNSIGNED_PAYLOAD,
+            now,
+        );
+        assert_eq!(signature.timestamp, "20230709T120000Z");
+        assert_eq!(
+            signature.authorization,
+            "AWS4-HMAC-SHA256 Credential=access/20230709/eu-central-1/s3/aws4_request, \
+             SignedHeaders=host;x-amz-content-sha256;x-amz-date, \
+             Signature=99a83b1d7e8276b1a1c0f8e78a022bae76761f31716cb98a3babafcb54b1583f"
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_storage_checks_the_length() {
+        let storage = MemoryStorage::default();
+        storage
+            .put("1/2.dem", 6, demo_stream(&[b"abc", b"def"]))
+            .await
+            .unwrap();
+        assert_eq!(storage.get("1/2.dem").await.unwrap(), b"abcdef");
+        assert!(storage
+            .put("1/3.dem", 7, demo_stream(&[b"abc"]))
+            .await
+            .is_err());
+    }
+}